@@ -0,0 +1,101 @@
+//! Benchmarks for the parse -> expand -> glob hot path.
+//!
+//! Run with `cargo bench --bench parse_expand`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::hint::black_box;
+use std::path::Path;
+
+use better_shell::{expand_globs, expand_tokens, parse_tokens, ExpansionContext};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+// A config-style script that mixes quoting, substitutions, redirects and
+// compound commands, repeated to a few thousand lines.
+fn realistic_script() -> Vec<String> {
+    let block = [
+        r#"export PATH="$HOME/.local/bin:$PATH""#,
+        r#"alias ll='ls -la --color=auto'"#,
+        r#"if [ -d "$HOME/src" ]; then cd "$HOME/src" && git status --short; fi"#,
+        r#"for f in *.rs; do wc -l "$f" >> /tmp/counts.txt 2>&1; done"#,
+        r#"grep -rn 'TODO\|FIXME' src | sort | uniq -c | head -n 20"#,
+        r#"echo "build: $(date +%Y-%m-%d) on ${HOSTNAME:-unknown}" > build.log"#,
+        r#"cat <<< "$(printf '%s\n' a b c)" | tr a-z A-Z"#,
+        r#"ls ~/Downloads/*.{png,jpg} 2>/dev/null || echo none"#,
+    ];
+    block
+        .iter()
+        .cycle()
+        .take(block.len() * 400)
+        .map(|line| line.to_string())
+        .collect()
+}
+
+fn var_context(vars: &HashMap<String, String>) -> ExpansionContext<'_> {
+    ExpansionContext {
+        lookup_var: Box::new(move |name| vars.get(name).cloned()),
+        lookup_array: Box::new(|_| None),
+        lookup_assoc: Box::new(|_| None),
+        command_subst: Box::new(|_| Ok(String::new())),
+        positional: &[],
+        strict: true,
+    }
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let script = realistic_script();
+    c.bench_function("parse_tokens/realistic_script", |b| {
+        b.iter(|| {
+            for line in &script {
+                black_box(parse_tokens(black_box(line)).unwrap());
+            }
+        })
+    });
+}
+
+fn bench_expand(c: &mut Criterion) {
+    let vars: HashMap<String, String> = (0..64)
+        .map(|idx| (format!("VAR{idx}"), format!("value-{idx}")))
+        .collect();
+    let line: Vec<String> = (0..256)
+        .map(|idx| match idx % 4 {
+            0 => format!("$VAR{}", idx % 64),
+            1 => format!("\"${{VAR{}}}/suffix\"", idx % 64),
+            2 => format!("pre${{VAR{}:-fallback}}post", idx % 64),
+            _ => format!("${{MISSING{idx}:-default}}"),
+        })
+        .collect();
+    let tokens = parse_tokens(&line.join(" ")).unwrap();
+    let ctx = var_context(&vars);
+    c.bench_function("expand_tokens/many_vars", |b| {
+        b.iter(|| black_box(expand_tokens(black_box(tokens.clone()), &ctx).unwrap()))
+    });
+}
+
+fn build_tree(root: &Path) {
+    for dir in 0..20 {
+        let sub = root.join(format!("dir{dir}")).join("nested");
+        fs::create_dir_all(&sub).unwrap();
+        for file in 0..25 {
+            fs::write(root.join(format!("dir{dir}/file{file}.rs")), "").unwrap();
+            fs::write(sub.join(format!("note{file}.txt")), "").unwrap();
+        }
+    }
+}
+
+fn bench_globs(c: &mut Criterion) {
+    let fixture = tempfile::tempdir().unwrap();
+    build_tree(fixture.path());
+    let root = fixture.path().display();
+    let shallow = vec![format!("{root}/*/*.rs")];
+    let recursive = vec![format!("{root}/**/*.txt")];
+    c.bench_function("expand_globs/one_level", |b| {
+        b.iter(|| black_box(expand_globs(black_box(shallow.clone())).unwrap()))
+    });
+    c.bench_function("expand_globs/globstar", |b| {
+        b.iter(|| black_box(expand_globs(black_box(recursive.clone())).unwrap()))
+    });
+}
+
+criterion_group!(benches, bench_parse, bench_expand, bench_globs);
+criterion_main!(benches);
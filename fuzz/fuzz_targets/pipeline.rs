@@ -0,0 +1,23 @@
+#![no_main]
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use libfuzzer_sys::fuzz_target;
+
+// A small fixed tree for glob patterns to match against.
+fn glob_root() -> &'static PathBuf {
+    static ROOT: OnceLock<PathBuf> = OnceLock::new();
+    ROOT.get_or_init(|| {
+        let root = std::env::temp_dir().join(format!("better_shell-fuzz-{}", std::process::id()));
+        let _ = std::fs::create_dir_all(root.join("dir"));
+        for name in ["a.rs", "b.txt", ".hidden", "dir/c.rs"] {
+            let _ = std::fs::write(root.join(name), "");
+        }
+        root
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    better_shell::fuzz_pipeline_bytes(data, glob_root());
+});
@@ -46,10 +46,21 @@ enum Token {
     RParen,
 }
 
+#[allow(dead_code)]
 pub fn eval_arithmetic(expr: &str) -> Result<i64, String> {
+    eval_arithmetic_with(expr, &|name| env::var(name).ok())
+}
+
+/// Like `eval_arithmetic`, but reads variables through `lookup`. Unset or
+/// non-numeric values count as 0; assignments still store into the
+/// environment, where shell variables live.
+pub fn eval_arithmetic_with(
+    expr: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<i64, String> {
     let stripped = strip_markers(expr);
     let tokens = tokenize(&stripped)?;
-    let mut parser = Parser::new(tokens);
+    let mut parser = Parser::new(tokens, lookup);
     let value = parser.parse_expr(0)?.value;
     if parser.peek().is_some() {
         return Err("unexpected tokens at end of expression".to_string());
@@ -258,14 +269,25 @@ struct ExprValue {
     lvalue: Option<String>,
 }
 
-struct Parser {
+struct Parser<'a> {
     tokens: Vec<Token>,
     pos: usize,
+    lookup: &'a dyn Fn(&str) -> Option<String>,
 }
 
-impl Parser {
-    fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, pos: 0 }
+impl<'a> Parser<'a> {
+    fn new(tokens: Vec<Token>, lookup: &'a dyn Fn(&str) -> Option<String>) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            lookup,
+        }
+    }
+
+    fn get_var(&self, name: &str) -> i64 {
+        (self.lookup)(name)
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(0)
     }
 
     fn peek(&self) -> Option<&Token> {
@@ -284,12 +306,7 @@ impl Parser {
     fn parse_expr(&mut self, min_bp: u8) -> Result<ExprValue, String> {
         let mut lhs = self.parse_prefix()?;
 
-        loop {
-            let op = match self.peek() {
-                Some(Token::Op(op)) => *op,
-                _ => break,
-            };
-
+        while let Some(&Token::Op(op)) = self.peek() {
             // Postfix ++ / --
             if matches!(op, Op::Inc | Op::Dec) {
                 self.next();
@@ -297,7 +314,7 @@ impl Parser {
                     .lvalue
                     .take()
                     .ok_or_else(|| "invalid increment target".to_string())?;
-                let current = get_var(&name);
+                let current = self.get_var(&name);
                 let new_val = if op == Op::Inc { current + 1 } else { current - 1 };
                 set_var(&name, new_val);
                 lhs.value = current; // postfix returns old value
@@ -323,7 +340,7 @@ impl Parser {
                         .lvalue
                         .take()
                         .ok_or_else(|| "invalid assignment target".to_string())?;
-                    let base = get_var(&name);
+                    let base = self.get_var(&name);
                     let new_val = match op {
                         Op::Assign => rhs.value,
                         Op::AddAssign => base + rhs.value,
@@ -370,7 +387,7 @@ impl Parser {
                 lvalue: None,
             }),
             Token::Ident(name) => Ok(ExprValue {
-                value: get_var(&name),
+                value: self.get_var(&name),
                 lvalue: Some(name),
             }),
             Token::LParen => {
@@ -404,7 +421,7 @@ impl Parser {
                     Some(Token::Ident(name)) => name,
                     _ => return Err("invalid increment target".to_string()),
                 };
-                let current = get_var(&name);
+                let current = self.get_var(&name);
                 let new_val = if op == Op::Inc { current + 1 } else { current - 1 };
                 set_var(&name, new_val);
                 Ok(ExprValue {
@@ -418,13 +435,6 @@ impl Parser {
     }
 }
 
-fn get_var(name: &str) -> i64 {
-    env::var(name)
-        .ok()
-        .and_then(|v| v.parse::<i64>().ok())
-        .unwrap_or(0)
-}
-
 fn set_var(name: &str, value: i64) {
     env::set_var(name, value.to_string());
 }
@@ -548,4 +558,17 @@ mod tests {
         assert_eq!(eval_arithmetic("--x").unwrap(), 6);
         assert_eq!(env::var("x").unwrap(), "6");
     }
+
+    #[test]
+    fn arithmetic_compound_assignments_store_results() {
+        env::set_var("arith_c", "20");
+        assert_eq!(eval_arithmetic("arith_c -= 2").unwrap(), 18);
+        assert_eq!(eval_arithmetic("arith_c *= 2").unwrap(), 36);
+        assert_eq!(eval_arithmetic("arith_c /= 5").unwrap(), 7);
+        assert_eq!(eval_arithmetic("arith_c %= 4").unwrap(), 3);
+        assert_eq!(env::var("arith_c").unwrap(), "3");
+        assert_eq!(eval_arithmetic("arith_c = arith_c + 1").unwrap(), 4);
+        assert_eq!(env::var("arith_c").unwrap(), "4");
+        assert!(eval_arithmetic("arith_c /= 0").is_err());
+    }
 }
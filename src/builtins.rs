@@ -1,28 +1,47 @@
 pub(crate) mod config_cmds;
 mod control_flow;
+mod dir_cmds;
+mod env_cmd;
 mod job_cmds;
+mod read_cmd;
 mod scripting;
+mod timeout;
 
-pub(crate) use scripting::execute_function;
-pub(crate) use config_cmds::load_assoc_arrays;
+pub(crate) use control_flow::LoopControl;
+pub(crate) use scripting::{
+    execute_function, spawn_function_subshell, wait_for_subshell, CallFrame,
+};
+pub(crate) use config_cmds::{load_assoc_arrays, source_file};
+pub(crate) use timeout::{capture_within, maxtime_watchdog, prompt_timeout, TIMEOUT_STATUS};
 
 use std::fmt::Write;
 use std::io::{self, Read};
 use std::path::Path;
 use std::{env, fs};
 
+use nix::unistd::isatty;
 use rustyline::history::{History, SearchDirection};
 
-use crate::completions::suggest_command;
+use crate::arithmetic::eval_arithmetic;
+use crate::completions::{history_command_counts, suggest_command};
 use crate::error::{ErrorKind, ShellError};
 use crate::execution::{
-    build_command, command_stdin_reader, run_command_in_foreground, sandbox_options_for_command,
-    status_from_error, write_command_output, CaptureResult,
+    apply_sandbox_directive, build_command, command_stdin_reader, format_sandbox_status,
+    run_command_in_foreground, sandbox_options_for_command, status_from_error,
+    write_command_output, CaptureResult,
+};
+use crate::logging::push_json_pair;
+use crate::job_control::{
+    add_job_with_status, list_jobs, resolve_job, signal_status, status_to_description, take_job,
+    JobStatus, WaitOutcome,
 };
-use crate::job_control::{add_job_with_status, list_jobs, parse_job_id, take_job, JobStatus, WaitOutcome};
 use rustyline::{Cmd, KeyCode, KeyEvent, Modifiers, Movement};
-use crate::parse::{parse_line_lenient, token_str, CommandSpec};
+use crate::parse::escapes::{decode_escapes, OctalStyle};
+use crate::parse::{parse_line_lenient, parse_sandbox_value, token_str, CommandSpec};
 use crate::execute_segment;
+use crate::repl::run_logout_file;
+use crate::prompt::PromptCache;
+use crate::safeglob::DEFAULT_SAFEGLOB_THRESHOLD;
 use crate::ShellState;
 
 use config_cmds::{
@@ -34,53 +53,110 @@ use control_flow::{
     execute_coproc, is_brace_group_start, is_case_start, is_coproc_start, is_for_start,
     is_if_start, is_select_start, is_while_start, read_compound_tokens, CompoundKind,
 };
-use job_cmds::{handle_bg, handle_fg};
-use scripting::{define_function, execute_script_tokens, is_function_def_start};
-
-const BUILTINS: &[&str] = &[
-    "exit",
-    "cd",
-    "pwd",
-    "jobs",
-    "fg",
-    "bg",
-    "help",
-    "hash",
-    "echo",
-    "true",
-    "false",
-    "unset",
-    "local",
-    "declare",
-    "readonly",
-    "shift",
-    "eval",
-    "alias",
-    "unalias",
-    "disown",
-    "bind",
-    "getopts",
-    "type",
-    "fc",
-    "abbr",
-    "complete",
-    "set_color",
-    "fish_config",
-    "source",
-    "history",
-    "set",
-    "enable",
-    "shopt",
-    "trap",
-    "return",
+use dir_cmds::{handle_cd, handle_pwd, pwd_for_args};
+use env_cmd::handle_env;
+use job_cmds::{handle_bg, handle_fg, handle_wait, job_spec};
+use read_cmd::handle_read;
+use scripting::{define_function, execute_script_tokens, handle_caller, is_function_def_start};
+use timeout::{handle_timeout, parse_maxtime};
+
+// Every builtin with a one-line usage. `is_builtin`, `help --list` and
+// command completion all read this table so they cannot drift apart.
+const BUILTINS: &[(&str, &str)] = &[
+    ("exit", "exit [code]"),
+    ("cd", "cd [-L|-P] [dir|-]"),
+    ("pwd", "pwd [-L|-P]"),
+    ("jobs", "jobs"),
+    ("fg", "fg [job]"),
+    ("bg", "bg [job]"),
+    ("help", "help [--list|--json|topic]"),
+    ("hash", "hash [-r] [name...]"),
+    ("echo", "echo [-neE] [arg...]"),
+    ("true", "true"),
+    ("false", "false"),
+    ("unset", "unset name..."),
+    ("local", "local [-f] name[=value]..."),
+    ("declare", "declare [-f|-A] [name[=value]...]"),
+    ("readonly", "readonly [-p] [name[=value]...]"),
+    ("export", "export [-p] [name[=value]...]"),
+    ("shift", "shift [n]"),
+    ("eval", "eval arg..."),
+    ("alias", "alias [-p] [name=value...]"),
+    ("unalias", "unalias name..."),
+    ("disown", "disown [job]"),
+    ("wait", "wait [-n] [job...]"),
+    ("bind", "bind [-p] [keyseq:command]"),
+    ("getopts", "getopts optstring name [args...]"),
+    ("read", "read [-rs] [-p prompt] [name...]"),
+    ("type", "type [-a|-t] name..."),
+    ("which", "which [-a] name..."),
+    ("fc", "fc [-lnrs] [first [last]]"),
+    ("abbr", "abbr name expansion..."),
+    ("complete", "complete -c cmd -a 'items...'"),
+    ("set_color", "set_color key value"),
+    ("fish_config", "fish_config"),
+    ("source", "source file"),
+    (".", ". file"),
+    ("history", "history"),
+    ("set", "set [-x] [-o|+o option]"),
+    ("enable", "enable [-n|-p] [name...]"),
+    ("shopt", "shopt [-s|-u|-p] [option...]"),
+    ("trap", "trap [-p] [action signal...]"),
+    ("return", "return [n]"),
+    ("break", "break [n]"),
+    ("continue", "continue [n]"),
+    ("sandbox", "sandbox status|on|off|bwrap|native"),
+    ("timeout", "timeout [-k duration] duration command [args...]"),
+    ("env", "env [-i] [-u name] [name=value]... [command [args...]]"),
+    ("caller", "caller [n]"),
+    ("let", "let expr..."),
+    ("command", "command [-pVv] name [arg...]"),
+    ("builtin", "builtin name [arg...]"),
+    ("clear", "clear [-x]"),
 ];
 
-pub fn builtin_names() -> &'static [&'static str] {
-    BUILTINS
+pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+    BUILTINS.iter().map(|(name, _)| *name)
 }
 
 pub fn is_builtin(cmd: Option<&str>) -> bool {
-    cmd.is_some_and(|name| BUILTINS.contains(&name))
+    cmd.is_some_and(|name| builtin_names().any(|builtin| builtin == name))
+}
+
+/// Output of `help --list` (names, one per line) or `help --json`
+/// (name/usage objects); `None` for any other argument.
+fn help_machine_listing(flag: &str) -> Option<String> {
+    let mut out = String::new();
+    match flag {
+        "--list" => {
+            for name in builtin_names() {
+                out.push_str(name);
+                out.push('\n');
+            }
+        }
+        "--json" => {
+            out.push('[');
+            for (idx, (name, usage)) in BUILTINS.iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                out.push('{');
+                push_json_pair(&mut out, "name", name);
+                out.push(',');
+                push_json_pair(&mut out, "usage", usage);
+                out.push('}');
+            }
+            out.push_str("]\n");
+        }
+        _ => return None,
+    }
+    Some(out)
+}
+
+/// The `Built-ins:` line of plain `help`, with every usage from the table.
+fn help_builtins_line() -> String {
+    let usages: Vec<&str> = BUILTINS.iter().map(|(_, usage)| *usage).collect();
+    format!("Built-ins: {}", usages.join(", "))
 }
 
 pub fn is_builtin_enabled_map(
@@ -147,6 +223,11 @@ pub fn try_execute_compound(
 pub fn execute_builtin(state: &mut ShellState, cmd: &CommandSpec, display: &str) -> io::Result<()> {
     let args = &cmd.args;
     let name = args.first().map(String::as_str);
+    if let Some(bypass @ ("command" | "builtin")) = name {
+        if state.is_builtin_enabled(bypass) {
+            return execute_bypassing_functions(state, cmd, display);
+        }
+    }
     if matches!(name, Some(name) if is_builtin(Some(name)) && state.is_builtin_enabled(name)) {
         let stdin = command_stdin_reader(cmd, None)?;
         let result = execute_builtin_capture(state, cmd, display, stdin)?;
@@ -162,52 +243,7 @@ pub fn execute_builtin(state: &mut ShellState, cmd: &CommandSpec, display: &str)
                 execute_script_tokens(state, body_tokens)?;
                 return Ok(());
             }
-            let mut command = build_command(cmd)?;
-            let sandbox = sandbox_options_for_command(cmd, &state.sandbox, state.trace);
-            match run_command_in_foreground(
-                &mut command,
-                &state.fg_pgid,
-                state.shell_pgid,
-                state.trace,
-                sandbox,
-            ) {
-                Ok(result) => {
-                    if matches!(result.outcome, WaitOutcome::Stopped) {
-                        let job_id = add_job_with_status(
-                            &mut state.jobs,
-                            &mut state.next_job_id,
-                            result.pgid,
-                            result.last_pid,
-                            1,
-                            display,
-                            JobStatus::Stopped,
-                        );
-                        println!("[{job_id}] Stopped {display}");
-                        state.last_status = 128 + libc::SIGTSTP;
-                    } else {
-                        let last = result.status_code.unwrap_or(0);
-                        let pipefail = result.pipefail_status.unwrap_or(last);
-                        state.last_status = if state.pipefail { pipefail } else { last };
-                    }
-                }
-                Err(err) => {
-                    eprintln!("{err}");
-                    if err.kind() == io::ErrorKind::NotFound {
-                        if let Some(suggestion) = suggest_command(
-                            &cmd.args[0],
-                            &state.aliases,
-                            &state.functions,
-                            &state.abbreviations,
-                            &state.completions,
-                        ) {
-                            if suggestion != cmd.args[0] {
-                                eprintln!("Command not found—did you mean '{suggestion}'?");
-                            }
-                        }
-                    }
-                    state.last_status = status_from_error(&err);
-                }
-            }
+            execute_external(state, cmd, display)?;
         }
         None => {
             state.last_status = 0;
@@ -217,6 +253,223 @@ pub fn execute_builtin(state: &mut ShellState, cmd: &CommandSpec, display: &str)
     Ok(())
 }
 
+/// Where `command -p` looks for programs, whatever `PATH` says.
+const DEFAULT_PATH: &str = "/usr/local/bin:/usr/bin:/bin:/usr/sbin:/sbin";
+
+/// What a `command` or `builtin` prefix asks for.
+enum Bypass {
+    /// Nothing follows the prefix.
+    Nothing,
+    /// Run these words without looking for a function.
+    Run(Box<CommandSpec>),
+    /// `command -v` (or `-V` when verbose): say how each name would run.
+    Describe { verbose: bool, names: Vec<String> },
+    /// An option `command` does not take.
+    Invalid(String),
+}
+
+/// Splits the `command` options (`-p`, `-v`, `-V`) and an optional `--` off
+/// the words after `command` or `builtin`. `-p` resolves a program against
+/// [`DEFAULT_PATH`].
+fn bypassed_command(cmd: &CommandSpec) -> Bypass {
+    let mut inner = cmd.clone();
+    let prefix = inner.args.remove(0);
+    let mut default_path = false;
+    let mut describe = None;
+    while let Some(flags) = inner.args.first().and_then(|arg| arg.strip_prefix('-')) {
+        if prefix != "command" || flags.is_empty() || flags == "-" {
+            break;
+        }
+        for flag in flags.chars() {
+            match flag {
+                'p' => default_path = true,
+                'v' => describe = Some(false),
+                'V' => describe = Some(true),
+                _ => return Bypass::Invalid(format!("-{flag}")),
+            }
+        }
+        inner.args.remove(0);
+    }
+    if inner.args.first().is_some_and(|arg| arg == "--") {
+        inner.args.remove(0);
+    }
+    if let Some(verbose) = describe {
+        return Bypass::Describe {
+            verbose,
+            names: inner.args,
+        };
+    }
+    let Some(name) = inner.args.first() else {
+        return Bypass::Nothing;
+    };
+    if default_path && !name.contains('/') && !is_builtin(Some(name)) {
+        if let Some(path) = path_matches(name, DEFAULT_PATH).into_iter().next() {
+            inner.args[0] = path;
+        }
+    }
+    Bypass::Run(Box::new(inner))
+}
+
+/// The `command -v` or `-V` report for `names`, and its status: 1 when any
+/// name is unknown. `lookup` gives the kind of each name ("alias",
+/// "function", "builtin" or "file") and its definition or path.
+fn describe_commands(
+    names: &[String],
+    verbose: bool,
+    lookup: impl Fn(&str) -> Option<(&'static str, String)>,
+) -> (String, i32) {
+    let mut output = String::new();
+    let mut status = 0;
+    for name in names {
+        let Some((kind, detail)) = lookup(name) else {
+            if verbose {
+                eprintln!("command: {name}: not found");
+            }
+            status = 1;
+            continue;
+        };
+        let _ = match (kind, verbose) {
+            ("alias", false) => writeln!(output, "alias {name}='{detail}'"),
+            ("alias", true) => writeln!(output, "{name} is an alias for {detail}"),
+            ("function", true) => writeln!(output, "{name} is a shell function"),
+            ("builtin", true) => writeln!(output, "{name} is a shell builtin"),
+            ("file", _) => {
+                if verbose {
+                    writeln!(output, "{name} is {detail}")
+                } else {
+                    writeln!(output, "{detail}")
+                }
+            }
+            _ => writeln!(output, "{name}"),
+        };
+    }
+    (output, status)
+}
+
+/// How `name` would run in this shell, searched in the order a command is:
+/// aliases, functions, builtins, then `PATH`.
+fn lookup_command(state: &ShellState, name: &str) -> Option<(&'static str, String)> {
+    if let Some(value) = state.aliases.get(name) {
+        return Some(("alias", value.join(" ")));
+    }
+    if state.functions.contains_key(name) {
+        return Some(("function", name.to_string()));
+    }
+    if is_builtin(Some(name)) && state.is_builtin_enabled(name) {
+        return Some(("builtin", name.to_string()));
+    }
+    find_in_path(name).map(|path| ("file", path))
+}
+
+// `command name` runs the builtin or program `name` and `builtin name` only
+// the builtin, both skipping any function of that name. Functions are found
+// first otherwise, so this is how a wrapper reaches what it shadows.
+fn execute_bypassing_functions(
+    state: &mut ShellState,
+    cmd: &CommandSpec,
+    display: &str,
+) -> io::Result<()> {
+    let inner = match bypassed_command(cmd) {
+        Bypass::Nothing => {
+            state.last_status = 0;
+            return Ok(());
+        }
+        Bypass::Run(inner) => inner,
+        Bypass::Describe { verbose, names } => {
+            let (output, status) =
+                describe_commands(&names, verbose, |name| lookup_command(state, name));
+            write_command_output(cmd, &output)?;
+            state.last_status = status;
+            return Ok(());
+        }
+        Bypass::Invalid(flag) => {
+            eprintln!("command: {flag}: invalid option");
+            eprintln!("usage: command [-pVv] name [arg...]");
+            state.last_status = 2;
+            return Ok(());
+        }
+    };
+    let name = inner.args[0].as_str();
+    if is_builtin(Some(name)) && state.is_builtin_enabled(name) {
+        return execute_builtin(state, &inner, display);
+    }
+    if cmd.args[0] == "builtin" {
+        eprintln!("builtin: {name}: not a shell builtin");
+        state.last_status = 1;
+        return Ok(());
+    }
+    execute_external(state, &inner, display)
+}
+
+/// Command substitutions have no functions to skip, so `command name` there
+/// just runs `name`, and so does `builtin name` for a builtin. What is left
+/// (`command -v`, errors) is answered by the `command` builtin itself.
+pub(crate) fn without_bypass_prefix(cmd: CommandSpec) -> CommandSpec {
+    let Some(prefix @ ("command" | "builtin")) = cmd.args.first().map(String::as_str) else {
+        return cmd;
+    };
+    match bypassed_command(&cmd) {
+        Bypass::Run(inner) if prefix == "command" || is_builtin(Some(&inner.args[0])) => *inner,
+        _ => cmd,
+    }
+}
+
+fn execute_external(state: &mut ShellState, cmd: &CommandSpec, display: &str) -> io::Result<()> {
+    let mut command = build_command(cmd)?;
+    let sandbox = sandbox_options_for_command(cmd, &state.sandbox, state.trace);
+    let watchdog = maxtime_watchdog(state);
+    let result = run_command_in_foreground(
+        &mut command,
+        &state.fg_pgid,
+        state.shell_pgid,
+        state.trace,
+        sandbox,
+    );
+    let timed_out = watchdog.is_some_and(|watchdog| watchdog.finish());
+    match result {
+        Ok(_) if timed_out => state.last_status = TIMEOUT_STATUS,
+        Ok(result) => {
+            if matches!(result.outcome, WaitOutcome::Stopped) {
+                let status = result.status_code.unwrap_or(signal_status(libc::SIGTSTP));
+                let job_id = add_job_with_status(
+                    &mut state.jobs,
+                    &mut state.next_job_id,
+                    result.pgid,
+                    result.last_pid,
+                    1,
+                    display,
+                    JobStatus::Stopped(status),
+                );
+                println!("[{job_id}] {} {display}", status_to_description(status));
+                state.last_status = status;
+            } else {
+                let last = result.status_code.unwrap_or(0);
+                let pipefail = result.pipefail_status.unwrap_or(last);
+                state.last_status = if state.pipefail { pipefail } else { last };
+            }
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            if err.kind() == io::ErrorKind::NotFound {
+                if let Some(suggestion) = suggest_command(
+                    &cmd.args[0],
+                    &state.aliases,
+                    &state.functions,
+                    &state.abbreviations,
+                    &state.completions,
+                    &history_command_counts(state.editor.history().iter()),
+                ) {
+                    if suggestion != cmd.args[0] {
+                        eprintln!("Command not found—did you mean '{suggestion}'?");
+                    }
+                }
+            }
+            state.last_status = status_from_error(&err);
+        }
+    }
+    Ok(())
+}
+
 pub fn execute_builtin_capture(
     state: &mut ShellState,
     cmd: &CommandSpec,
@@ -234,42 +487,38 @@ pub fn execute_builtin_capture(
 fn execute_builtin_with_output(
     state: &mut ShellState,
     cmd: &CommandSpec,
-    _display: &str,
-    _stdin: Option<Box<dyn Read>>,
+    display: &str,
+    stdin: Option<Box<dyn Read>>,
     output: &mut String,
 ) -> io::Result<i32> {
     let args = &cmd.args;
     let name = args.first().map(String::as_str);
     match name {
         Some("exit") => {
-            let code = args
-                .get(1)
-                .and_then(|s| s.parse::<i32>().ok())
-                .unwrap_or(state.last_status);
+            let code = exit_status(args.get(1).map(String::as_str), state.last_status)
+                .unwrap_or_else(|err| {
+                    eprintln!("exit: {err}");
+                    2
+                });
+            run_logout_file(state);
             std::process::exit(code);
         }
         Some("cd") => {
-            let target = args.get(1).map(String::as_str).unwrap_or("~");
-            let expanded = if let Some(rest) = target.strip_prefix('~') {
-                if let Ok(home) = std::env::var("HOME") {
-                    format!("{home}{rest}")
-                } else {
-                    target.to_string()
-                }
-            } else {
-                target.to_string()
-            };
-            if let Err(err) = std::env::set_current_dir(&expanded) {
-                eprintln!("cd: {err}");
-                state.last_status = 1;
-            } else {
-                state.last_status = 0;
-            }
+            handle_cd(state, args, output);
         }
         Some("pwd") => {
-            let cwd = std::env::current_dir().unwrap_or_else(|_| "/".into());
-            let _ = writeln!(output, "{}", cwd.display());
-            state.last_status = 0;
+            handle_pwd(state, args, output);
+        }
+        Some("clear") => {
+            let to_tty = !cmd.redirects_stdout() && isatty(libc::STDOUT_FILENO).unwrap_or(false);
+            state.last_status = match write_clear(args, to_tty, output) {
+                Ok(()) => 0,
+                Err(err) => {
+                    eprintln!("clear: {err}");
+                    eprintln!("usage: clear [-x]");
+                    2
+                }
+            };
         }
         Some("jobs") => {
             list_jobs(&state.jobs, output);
@@ -282,6 +531,11 @@ fn execute_builtin_with_output(
             handle_bg(state, args, output)?;
         }
         Some("help") => {
+            if let Some(listing) = args.get(1).and_then(|flag| help_machine_listing(flag)) {
+                output.push_str(&listing);
+                state.last_status = 0;
+                return Ok(state.last_status);
+            }
             if args.len() > 1 {
                 let topic = &args[1];
                 match std::process::Command::new("man").arg(topic).output() {
@@ -300,10 +554,7 @@ fn execute_builtin_with_output(
                 }
                 return Ok(state.last_status);
             }
-            let _ = writeln!(
-                output,
-                "Built-ins: cd [dir], pwd, jobs, fg [id], bg [id], help, exit [code], hash, echo, true, false, unset, local, declare, readonly, shift, eval, alias, unalias, disown, bind, getopts, type, fc, abbr, complete, enable, shopt, trap, return"
-            );
+            let _ = writeln!(output, "{}", help_builtins_line());
             let _ = writeln!(
                 output,
                 "External commands support pipes with |, background jobs with &, and redirection with <, >, >>, 2>, 2>>, 2>&1, &>, &>>, and <<<."
@@ -317,6 +568,14 @@ fn execute_builtin_with_output(
                 output,
                 "Sandbox: prefix commands with sandbox=yes/no or use --sandbox/--no-sandbox."
             );
+            let _ = writeln!(
+                output,
+                "Sandbox status: sandbox status; change with sandbox on|off|bwrap|native."
+            );
+            let _ = writeln!(
+                output,
+                "Timeout: timeout [-k grace] 5|500ms|2m command... (status 124 on expiry)."
+            );
             let _ = writeln!(output, "Completion: commands, filenames, $vars, %jobs.");
             let _ = writeln!(
                 output,
@@ -340,6 +599,7 @@ fn execute_builtin_with_output(
                 return Ok(state.last_status);
             }
             let mut failed = false;
+            let mut invalid = false;
             for name in &args[1..] {
                 if let Some((arr, idx)) = parse_array_unset(name) {
                     state.unset_array_elem(&arr, idx);
@@ -357,7 +617,7 @@ fn execute_builtin_with_output(
                         }
                     }
                     eprintln!("unset: invalid variable name '{name}'");
-                    failed = true;
+                    invalid = true;
                     continue;
                 }
                 if state.readonly_vars.contains(name) {
@@ -367,7 +627,7 @@ fn execute_builtin_with_output(
                 }
                 state.unset_var(name);
             }
-            state.last_status = if failed { 1 } else { 0 };
+            state.last_status = name_error_status(invalid, failed);
         }
         Some("local") => {
             if args.len() < 2 {
@@ -403,6 +663,7 @@ fn execute_builtin_with_output(
                 return Ok(state.last_status);
             }
             let mut failed = false;
+            let mut invalid = false;
             for entry in &args[1..] {
                 let (name, value) = match entry.split_once('=') {
                     Some((name, value)) => (name, value),
@@ -410,7 +671,7 @@ fn execute_builtin_with_output(
                 };
                 if !crate::utils::is_valid_var_name(name) {
                     eprintln!("local: invalid variable name '{name}'");
-                    failed = true;
+                    invalid = true;
                     continue;
                 }
                 if let Err(err) = state.set_local_var(name, value) {
@@ -418,11 +679,17 @@ fn execute_builtin_with_output(
                     failed = true;
                 }
             }
-            state.last_status = if failed { 1 } else { 0 };
+            state.last_status = name_error_status(invalid, failed);
         }
         Some("getopts") => {
             state.last_status = handle_getopts(args)?;
         }
+        Some("read") => {
+            handle_read(state, args, stdin)?;
+        }
+        Some("which") => {
+            handle_which(state, args, output);
+        }
         Some("type") => {
             handle_type(state, args, output)?;
         }
@@ -441,7 +708,7 @@ fn execute_builtin_with_output(
         Some("fish_config") => {
             handle_fish_config(state, output)?;
         }
-        Some("source") => {
+        Some("source") | Some(".") => {
             handle_source(state, args, output)?;
         }
         Some("history") => {
@@ -456,15 +723,27 @@ fn execute_builtin_with_output(
         Some("return") => {
             handle_return(state, args)?;
         }
+        Some("break") | Some("continue") => {
+            control_flow::handle_loop_control(state, args);
+        }
+        Some("caller") => {
+            handle_caller(state, args, output);
+        }
         Some("readonly") => {
             handle_readonly(state, args, output)?;
         }
+        Some("export") => {
+            handle_export(state, args, output);
+        }
         Some("shift") => {
             handle_shift(state, args)?;
         }
         Some("eval") => {
             handle_eval(state, args)?;
         }
+        Some("let") => {
+            handle_let(state, args);
+        }
         Some("alias") => {
             handle_alias(state, args, output)?;
         }
@@ -474,6 +753,9 @@ fn execute_builtin_with_output(
         Some("disown") => {
             handle_disown(state, args)?;
         }
+        Some("wait") => {
+            handle_wait(state, args, output);
+        }
         Some("bind") => {
             handle_bind(state, args, output)?;
         }
@@ -486,11 +768,44 @@ fn execute_builtin_with_output(
         Some("trap") => {
             handle_trap(state, args, output)?;
         }
-        Some("echo") => {
-            let line = args[1..].join(" ");
-            let _ = writeln!(output, "{line}");
-            state.last_status = 0;
+        Some("sandbox") => {
+            handle_sandbox(state, args, output)?;
+        }
+        Some("timeout") => {
+            handle_timeout(state, cmd, display)?;
         }
+        Some("env") => {
+            handle_env(state, cmd, display, output)?;
+        }
+        Some("echo") => {
+            handle_echo(state, args, output);
+        }
+        Some(bypass @ ("command" | "builtin")) => match bypassed_command(cmd) {
+            Bypass::Nothing => state.last_status = 0,
+            Bypass::Describe { verbose, names } => {
+                let (described, status) =
+                    describe_commands(&names, verbose, |name| lookup_command(state, name));
+                output.push_str(&described);
+                state.last_status = status;
+            }
+            Bypass::Invalid(flag) => {
+                eprintln!("command: {flag}: invalid option");
+                state.last_status = 2;
+            }
+            Bypass::Run(inner) if state.is_builtin_enabled(&inner.args[0]) => {
+                return execute_builtin_with_output(state, &inner, display, stdin, output);
+            }
+            // Inside a builtin pipeline only another builtin can run here.
+            Bypass::Run(inner) => {
+                let name = &inner.args[0];
+                if bypass == "builtin" {
+                    eprintln!("builtin: {name}: not a shell builtin");
+                } else {
+                    eprintln!("command: {name}: only builtins can be piped through command");
+                }
+                state.last_status = 1;
+            }
+        },
         Some("true") => {
             state.last_status = 0;
         }
@@ -504,6 +819,74 @@ fn execute_builtin_with_output(
             } else if args.len() >= 3 && args[1] == "+o" && args[2] == "pipefail" {
                 state.pipefail = false;
                 state.last_status = 0;
+            } else if args.len() >= 3 && args[1] == "-o" && args[2] == "stats" {
+                state.stats = true;
+                state.last_status = 0;
+            } else if args.len() >= 3 && args[1] == "+o" && args[2] == "stats" {
+                state.stats = false;
+                state.last_status = 0;
+            } else if args.len() >= 3 && args[1] == "-o" && args[2] == "posix" {
+                state.posix = true;
+                state.last_status = 0;
+            } else if args.len() >= 3 && args[1] == "+o" && args[2] == "posix" {
+                state.posix = false;
+                state.last_status = 0;
+            } else if args.len() >= 3 && args[1] == "-o" && args[2] == "reportstatus" {
+                state.reportstatus = true;
+                state.last_status = 0;
+            } else if args.len() >= 3 && args[1] == "+o" && args[2] == "reportstatus" {
+                state.reportstatus = false;
+                state.last_status = 0;
+            } else if args.len() >= 3 && args[1] == "-o" && args[2] == "physical" {
+                state.physical = true;
+                state.last_status = 0;
+            } else if args.len() >= 3 && args[1] == "+o" && args[2] == "physical" {
+                state.physical = false;
+                state.last_status = 0;
+            } else if args.len() >= 3 && args[1] == "-o" && args[2].starts_with("maxtime=") {
+                match parse_maxtime(&args[2]["maxtime=".len()..]) {
+                    Ok(limit) => {
+                        state.maxtime = limit;
+                        state.last_status = 0;
+                    }
+                    Err(err) => {
+                        eprintln!("set: maxtime: {err}");
+                        state.last_status = 2;
+                    }
+                }
+            } else if args.len() >= 3 && args[1] == "+o" && args[2] == "maxtime" {
+                state.maxtime = None;
+                state.last_status = 0;
+            } else if args.len() >= 3 && args[1] == "-o" && args[2] == "ignoreeof" {
+                state.ignoreeof = true;
+                state.last_status = 0;
+            } else if args.len() >= 3 && args[1] == "+o" && args[2] == "ignoreeof" {
+                state.ignoreeof = false;
+                state.last_status = 0;
+            } else if args.len() >= 3 && args[1] == "-o" && args[2] == "safeglob" {
+                state.safeglob = Some(DEFAULT_SAFEGLOB_THRESHOLD);
+                state.last_status = 0;
+            } else if args.len() >= 3 && args[1] == "-o" && args[2].starts_with("safeglob=") {
+                let value = &args[2]["safeglob=".len()..];
+                match value.parse::<usize>() {
+                    Ok(threshold) if threshold > 0 => {
+                        state.safeglob = Some(threshold);
+                        state.last_status = 0;
+                    }
+                    _ => {
+                        eprintln!("set: safeglob: invalid threshold '{value}'");
+                        state.last_status = 2;
+                    }
+                }
+            } else if args.len() >= 3 && args[1] == "+o" && args[2] == "safeglob" {
+                state.safeglob = None;
+                state.last_status = 0;
+            } else if args.len() >= 3 && args[1] == "-o" && args[2] == "promptcache" {
+                state.prompt_cache.get_or_insert_with(PromptCache::default);
+                state.last_status = 0;
+            } else if args.len() >= 3 && args[1] == "+o" && args[2] == "promptcache" {
+                state.prompt_cache = None;
+                state.last_status = 0;
             } else if args.len() >= 3 && args[1] == "-o" && args[2] == "functrace" {
                 state.functrace = true;
                 state.last_status = 0;
@@ -522,6 +905,55 @@ fn execute_builtin_with_output(
                     "pipefail\t{}",
                     if state.pipefail { "on" } else { "off" }
                 );
+                let _ = writeln!(
+                    output,
+                    "stats\t{}",
+                    if state.stats { "on" } else { "off" }
+                );
+                let _ = writeln!(
+                    output,
+                    "posix\t{}",
+                    if state.posix { "on" } else { "off" }
+                );
+                let _ = writeln!(
+                    output,
+                    "reportstatus\t{}",
+                    if state.reportstatus { "on" } else { "off" }
+                );
+                let _ = writeln!(
+                    output,
+                    "physical\t{}",
+                    if state.physical { "on" } else { "off" }
+                );
+                match state.maxtime {
+                    Some(limit) => {
+                        let _ = writeln!(output, "maxtime\t{}s", limit.as_secs_f64());
+                    }
+                    None => {
+                        let _ = writeln!(output, "maxtime\toff");
+                    }
+                }
+                let _ = writeln!(
+                    output,
+                    "ignoreeof\t{}",
+                    if state.ignoreeof { "on" } else { "off" }
+                );
+                match state.safeglob {
+                    Some(threshold) => {
+                        let _ = writeln!(output, "safeglob\t{threshold}");
+                    }
+                    None => {
+                        let _ = writeln!(output, "safeglob\toff");
+                    }
+                }
+                match state.prompt_cache {
+                    Some(_) => {
+                        let _ = writeln!(output, "promptcache\ton");
+                    }
+                    None => {
+                        let _ = writeln!(output, "promptcache\toff");
+                    }
+                }
                 let _ = writeln!(
                     output,
                     "functrace\t{}",
@@ -557,26 +989,29 @@ pub fn execute_builtin_substitution(pipeline: &[CommandSpec]) -> Result<(String,
     if pipeline.len() != 1 {
         return Err("pipes only work with external commands".to_string());
     }
-    let result = execute_builtin_substitution_capture(&pipeline[0], None)?;
+    let result = execute_builtin_substitution_capture(&pipeline[0], None, false)?;
     Ok((result.output, result.status_code))
 }
 
 pub fn execute_builtin_substitution_capture(
     cmd: &CommandSpec,
     _stdin: Option<Box<dyn Read>>,
+    physical: bool,
 ) -> Result<CaptureResult, String> {
     let args = &cmd.args;
     match args.first().map(String::as_str) {
         Some("pwd") => {
-            let cwd = std::env::current_dir().unwrap_or_else(|_| "/".into());
+            let cwd = pwd_for_args(args, physical).map_err(|err| format!("pwd: {err}"))?;
             Ok(CaptureResult {
                 output: cwd.display().to_string(),
                 status_code: 0,
             })
         }
         Some("help") => Ok(CaptureResult {
-            output: "Built-ins: cd [dir], pwd, jobs, fg [id], bg [id], help, exit [code], hash, echo, true, false, unset, local, declare, readonly, shift, eval, alias, unalias, disown, bind, getopts, type, fc, abbr, complete, enable, shopt, trap, return"
-                .to_string(),
+            output: match args.get(1).and_then(|flag| help_machine_listing(flag)) {
+                Some(listing) => listing,
+                None => help_builtins_line(),
+            },
             status_code: 0,
         }),
         Some("echo") => Ok(CaptureResult {
@@ -584,10 +1019,44 @@ pub fn execute_builtin_substitution_capture(
             status_code: 0,
         }),
         Some("type") => execute_type_substitution(args),
+        Some(bypass @ ("command" | "builtin")) => match bypassed_command(cmd) {
+            Bypass::Nothing => Ok(CaptureResult {
+                output: String::new(),
+                status_code: 0,
+            }),
+            Bypass::Describe { verbose, names } => {
+                let (output, status_code) = describe_commands(&names, verbose, |name| {
+                    if is_builtin(Some(name)) {
+                        Some(("builtin", name.to_string()))
+                    } else {
+                        find_in_path(name).map(|path| ("file", path))
+                    }
+                });
+                Ok(CaptureResult {
+                    output,
+                    status_code,
+                })
+            }
+            Bypass::Invalid(flag) => Err(format!("command: {flag}: invalid option")),
+            Bypass::Run(inner) if bypass == "builtin" && !is_builtin(Some(&inner.args[0])) => {
+                Err(format!("builtin: {}: not a shell builtin", inner.args[0]))
+            }
+            Bypass::Run(inner) => execute_builtin_substitution_capture(&inner, _stdin, physical),
+        },
+        // Assignments would only reach the substitution's own environment.
+        Some("export") if is_export_listing(args) => Ok(CaptureResult {
+            output: export_listing(),
+            status_code: 0,
+        }),
         Some("true") => Ok(CaptureResult {
             output: String::new(),
             status_code: 0,
         }),
+        // Captured output never reaches a terminal, so there is nothing to clear.
+        Some("clear") => Ok(CaptureResult {
+            output: String::new(),
+            status_code: 0,
+        }),
         Some("false") => Ok(CaptureResult {
             output: String::new(),
             status_code: 1,
@@ -633,6 +1102,18 @@ pub fn execute_builtin_substitution_capture(
     }
 }
 
+/// Exit status for builtins that take variable names: a malformed name is a
+/// usage error (2) and outranks ordinary failures such as readonly targets.
+fn name_error_status(invalid: bool, failed: bool) -> i32 {
+    if invalid {
+        2
+    } else if failed {
+        1
+    } else {
+        0
+    }
+}
+
 fn handle_getopts(args: &[String]) -> io::Result<i32> {
     if args.len() < 3 {
         eprintln!("usage: getopts optstring name [args...]");
@@ -640,6 +1121,10 @@ fn handle_getopts(args: &[String]) -> io::Result<i32> {
     }
     let optstring = &args[1];
     let name = &args[2];
+    if !crate::utils::is_valid_var_name(name) {
+        eprintln!("getopts: invalid variable name '{name}'");
+        return Ok(2);
+    }
     let optargs = &args[3..];
     let mut optind = env::var("OPTIND")
         .ok()
@@ -812,6 +1297,67 @@ fn handle_type(state: &mut ShellState, args: &[String], output: &mut String) ->
     Ok(())
 }
 
+fn handle_which(state: &mut ShellState, args: &[String], output: &mut String) {
+    let mut show_all = false;
+    let mut idx = 1usize;
+    while let Some(arg) = args.get(idx) {
+        match arg.as_str() {
+            "-a" => show_all = true,
+            "--" => {
+                idx += 1;
+                break;
+            }
+            _ if arg.starts_with('-') => {
+                eprintln!("which: unsupported option '{arg}'");
+                state.last_status = 2;
+                return;
+            }
+            _ => break,
+        }
+        idx += 1;
+    }
+    if idx >= args.len() {
+        eprintln!("usage: which [-a] name...");
+        state.last_status = 2;
+        return;
+    }
+    let path_var = env::var("PATH").unwrap_or_default();
+    let mut ok = true;
+    for name in &args[idx..] {
+        let mut entries = Vec::new();
+        if let Some(value) = state.aliases.get(name) {
+            entries.push(format!("{name}: aliased to {}", value.join(" ")));
+        }
+        if state.functions.contains_key(name) {
+            entries.push(format!("{name}: shell function"));
+        }
+        if is_builtin(Some(name)) {
+            entries.push(format!("{name}: shell builtin"));
+        }
+        if name.contains('/') {
+            entries.extend(find_in_path(name));
+        } else if show_all {
+            entries.extend(path_matches(name, &path_var));
+        } else if let Some(path) = state.command_hash.get(name) {
+            entries.push(path.clone());
+        } else {
+            entries.extend(path_matches(name, &path_var).into_iter().take(1));
+        }
+        if entries.is_empty() {
+            ok = false;
+            eprintln!("which: {name} not found");
+            continue;
+        }
+        if !show_all {
+            entries.truncate(1);
+        }
+        for entry in entries {
+            let _ = writeln!(output, "{entry}");
+        }
+    }
+    state.last_status = if ok { 0 } else { 1 };
+}
+
 fn handle_fc(state: &mut ShellState, args: &[String], output: &mut String) -> io::Result<()> {
     let mut _list_only = false;
     let mut no_numbers = false;
@@ -964,16 +1510,22 @@ pub(crate) fn find_in_path(name: &str) -> Option<String> {
         return None;
     }
     let path_var = env::var("PATH").ok()?;
+    path_matches(name, &path_var).into_iter().next()
+}
+
+/// Every executable called `name` along `path_var`, in search order.
+fn path_matches(name: &str, path_var: &str) -> Vec<String> {
+    let mut matches = Vec::new();
     for part in path_var.split(':') {
         if part.is_empty() {
             continue;
         }
         let candidate = Path::new(part).join(name);
         if candidate.is_file() && is_executable(&candidate) {
-            return Some(candidate.display().to_string());
+            matches.push(candidate.display().to_string());
         }
     }
-    None
+    matches
 }
 
 fn is_executable(path: &Path) -> bool {
@@ -1158,7 +1710,7 @@ fn handle_enable(state: &mut ShellState, args: &[String], output: &mut String) -
 
     if print {
         for name in builtin_names() {
-            let enabled = state.builtin_enabled.get(*name).copied().unwrap_or(false);
+            let enabled = state.builtin_enabled.get(name).copied().unwrap_or(false);
             if enabled {
                 let _ = writeln!(output, "enable {name}");
             } else {
@@ -1354,6 +1906,62 @@ fn handle_trap(state: &mut ShellState, args: &[String], output: &mut String) ->
     Ok(())
 }
 
+fn handle_echo(state: &mut ShellState, args: &[String], output: &mut String) {
+    // Leading -n/-e/-E clusters are options; anything else starts the text.
+    let mut newline = true;
+    let mut escapes = false;
+    let mut idx = 1;
+    while let Some(flags) = args.get(idx).and_then(|arg| arg.strip_prefix('-')) {
+        if flags.is_empty() || !flags.chars().all(|ch| matches!(ch, 'n' | 'e' | 'E')) {
+            break;
+        }
+        for flag in flags.chars() {
+            match flag {
+                'n' => newline = false,
+                'e' => escapes = true,
+                _ => escapes = false,
+            }
+        }
+        idx += 1;
+    }
+    let line = args[idx..].join(" ");
+    if escapes {
+        output.push_str(&decode_escapes(&line, OctalStyle::LeadingZero));
+    } else {
+        output.push_str(&line);
+    }
+    if newline {
+        output.push('\n');
+    }
+    state.last_status = 0;
+}
+
+fn handle_sandbox(state: &mut ShellState, args: &[String], output: &mut String) -> io::Result<()> {
+    // A bare `sandbox` word is rejected so it never reads like a `sandbox=` prefix.
+    match (args.get(1).map(String::as_str), args.len()) {
+        (Some("status"), 2) => {
+            output.push_str(&format_sandbox_status(&state.sandbox));
+            state.last_status = 0;
+        }
+        (Some(value), 2) => match parse_sandbox_value(value) {
+            Ok(directive) => {
+                apply_sandbox_directive(&mut state.sandbox, directive);
+                state.last_status = 0;
+            }
+            Err(err) => {
+                eprintln!("sandbox: {err}");
+                state.last_status = 2;
+            }
+        },
+        _ => {
+            eprintln!("usage: sandbox status");
+            eprintln!("       sandbox on|off|bwrap|native");
+            state.last_status = 2;
+        }
+    }
+    Ok(())
+}
+
 fn handle_hash(state: &mut ShellState, args: &[String], output: &mut String) -> io::Result<()> {
     let mut clear = false;
     let mut idx = 1usize;
@@ -1403,6 +2011,56 @@ fn handle_hash(state: &mut ShellState, args: &[String], output: &mut String) ->
     Ok(())
 }
 
+fn is_export_listing(args: &[String]) -> bool {
+    args.len() == 1 || (args.len() == 2 && args[1] == "-p")
+}
+
+// Every shell variable lives in the environment, so the listing is the
+// environment sorted by name.
+fn export_listing() -> String {
+    let mut vars: Vec<(String, String)> = env::vars().collect();
+    vars.sort();
+    let mut listing = String::new();
+    for (name, value) in vars {
+        let _ = writeln!(listing, "{name}={value}");
+    }
+    listing
+}
+
+fn handle_export(state: &mut ShellState, args: &[String], output: &mut String) {
+    if is_export_listing(args) {
+        output.push_str(&export_listing());
+        state.last_status = 0;
+        return;
+    }
+
+    let idx = if args[1] == "-p" { 2 } else { 1 };
+    let mut failed = false;
+    let mut invalid = false;
+    for entry in &args[idx..] {
+        let (name, value) = match entry.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (entry.as_str(), None),
+        };
+        if !crate::utils::is_valid_var_name(name) {
+            eprintln!("export: invalid name '{name}'");
+            invalid = true;
+            continue;
+        }
+        // A bare name is already visible to children once it has a value.
+        let Some(value) = value else {
+            continue;
+        };
+        if state.readonly_vars.contains(name) {
+            eprintln!("export: {name}: readonly variable");
+            failed = true;
+            continue;
+        }
+        env::set_var(name, value);
+    }
+    state.last_status = name_error_status(invalid, failed);
+}
+
 fn handle_readonly(state: &mut ShellState, args: &[String], output: &mut String) -> io::Result<()> {
     if args.len() == 1 || (args.len() == 2 && args[1] == "-p") {
         let mut entries: Vec<_> = state.readonly_vars.iter().collect();
@@ -1419,6 +2077,7 @@ fn handle_readonly(state: &mut ShellState, args: &[String], output: &mut String)
         idx += 1;
     }
     let mut failed = false;
+    let mut invalid = false;
     for entry in &args[idx..] {
         let (name, value) = match entry.split_once('=') {
             Some((name, value)) => (name, Some(value)),
@@ -1426,7 +2085,7 @@ fn handle_readonly(state: &mut ShellState, args: &[String], output: &mut String)
         };
         if !crate::utils::is_valid_var_name(name) {
             eprintln!("readonly: invalid name '{name}'");
-            failed = true;
+            invalid = true;
             continue;
         }
         if let Some(value) = value {
@@ -1439,7 +2098,7 @@ fn handle_readonly(state: &mut ShellState, args: &[String], output: &mut String)
         }
         state.readonly_vars.insert(name.to_string());
     }
-    state.last_status = if failed { 1 } else { 0 };
+    state.last_status = name_error_status(invalid, failed);
     Ok(())
 }
 
@@ -1488,6 +2147,28 @@ fn handle_eval(state: &mut ShellState, args: &[String]) -> io::Result<()> {
     Ok(())
 }
 
+/// `let expr...`: evaluates each argument as arithmetic; assignments write
+/// back to the named variables. Succeeds when the last value is non-zero.
+fn handle_let(state: &mut ShellState, args: &[String]) {
+    if args.len() < 2 {
+        eprintln!("let: expression expected");
+        state.last_status = 1;
+        return;
+    }
+    let mut last = 0;
+    for expr in &args[1..] {
+        match eval_arithmetic(expr) {
+            Ok(value) => last = value,
+            Err(err) => {
+                eprintln!("let: {expr}: {err}");
+                state.last_status = 1;
+                return;
+            }
+        }
+    }
+    state.last_status = if last == 0 { 1 } else { 0 };
+}
+
 fn handle_alias(state: &mut ShellState, args: &[String], output: &mut String) -> io::Result<()> {
     if args.len() == 1 || (args.len() == 2 && args[1] == "-p") {
         let mut entries: Vec<_> = state.aliases.iter().collect();
@@ -1556,13 +2237,17 @@ fn handle_unalias(state: &mut ShellState, args: &[String]) -> io::Result<()> {
 }
 
 fn handle_disown(state: &mut ShellState, args: &[String]) -> io::Result<()> {
-    let id = parse_job_id(args.get(1))?;
-    if take_job(&mut state.jobs, id).is_none() {
-        eprintln!("disown: no such job");
-        state.last_status = 1;
-        return Ok(());
+    match resolve_job(&state.jobs, job_spec(args)) {
+        Ok(job) => {
+            let id = job.id;
+            take_job(&mut state.jobs, id);
+            state.last_status = 0;
+        }
+        Err(err) => {
+            eprintln!("disown: {err}");
+            state.last_status = 1;
+        }
     }
-    state.last_status = 0;
     Ok(())
 }
 
@@ -1657,6 +2342,38 @@ fn parse_bind_cmd(input: &str) -> Option<Cmd> {
     }
 }
 
+/// The status `exit [n]` leaves with: `n` wrapped into 0-255 as bash does,
+/// so `exit 256` is 0 and `exit -1` is 255, or the last status without `n`.
+fn exit_status(arg: Option<&str>, last_status: i32) -> Result<i32, String> {
+    let Some(arg) = arg else {
+        return Ok(last_status);
+    };
+    let code = arg
+        .trim()
+        .parse::<i64>()
+        .map_err(|_| "numeric argument required".to_string())?;
+    Ok(code.rem_euclid(256) as i32)
+}
+
+// Home and erase the screen; `-x` erases only the scrollback instead.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const CLEAR_SCROLLBACK: &str = "\x1b[3J";
+
+/// `clear [-x]`: writes the erase sequence to `output`, or nothing when it
+/// would not reach a terminal.
+fn write_clear(args: &[String], to_tty: bool, output: &mut String) -> Result<(), String> {
+    let sequence = match args.get(1).map(String::as_str) {
+        None => CLEAR_SCREEN,
+        Some("-x") if args.len() == 2 => CLEAR_SCROLLBACK,
+        Some(other) if args.len() == 2 => return Err(format!("invalid option '{other}'")),
+        Some(_) => return Err("too many arguments".to_string()),
+    };
+    if to_tty {
+        output.push_str(sequence);
+    }
+    Ok(())
+}
+
 fn shell_quote(token: &str) -> String {
     if token.is_empty() || token.chars().any(needs_quotes) {
         let mut out = String::from("'");
@@ -1763,3 +2480,89 @@ fn execute_type_substitution(args: &[String]) -> Result<CaptureResult, String> {
         status_code: if ok { 0 } else { 1 },
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn help_list_covers_every_builtin() {
+        let listing = help_machine_listing("--list").unwrap();
+        let listed: Vec<&str> = listing.lines().collect();
+        assert_eq!(listed, builtin_names().collect::<Vec<_>>());
+        assert!(listed.iter().all(|name| is_builtin(Some(name))));
+        assert!(listed.contains(&"cd") && listed.contains(&"let"));
+
+        let json = help_machine_listing("--json").unwrap();
+        assert!(json.starts_with("[{\"name\":\"exit\",\"usage\":\"exit [code]\"},"));
+        assert_eq!(json.matches("\"name\":").count(), BUILTINS.len());
+        assert!(help_machine_listing("cd").is_none());
+
+        let line = help_builtins_line();
+        assert!(BUILTINS.iter().all(|(_, usage)| line.contains(usage)));
+        assert!(line.contains("read [-rs] [-p prompt] [name...]"));
+    }
+
+    #[test]
+    fn exit_status_wraps_into_a_byte() {
+        assert_eq!(exit_status(Some("256"), 0), Ok(0));
+        assert_eq!(exit_status(Some("-1"), 0), Ok(255));
+        assert_eq!(exit_status(Some("257"), 0), Ok(1));
+        assert_eq!(exit_status(Some("3"), 0), Ok(3));
+        assert_eq!(exit_status(None, 7), Ok(7));
+        assert_eq!(
+            exit_status(Some("abc"), 7),
+            Err("numeric argument required".to_string())
+        );
+    }
+
+    #[test]
+    fn clear_writes_the_erase_sequence_only_to_a_terminal() {
+        let args = |list: &[&str]| list.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let mut output = String::new();
+        write_clear(&args(&["clear"]), true, &mut output).unwrap();
+        assert_eq!(output, "\x1b[2J\x1b[H");
+
+        let mut output = String::new();
+        write_clear(&args(&["clear", "-x"]), true, &mut output).unwrap();
+        assert_eq!(output, "\x1b[3J");
+
+        let mut output = String::new();
+        write_clear(&args(&["clear"]), false, &mut output).unwrap();
+        assert_eq!(output, "");
+        assert_eq!(
+            write_clear(&args(&["clear", "-y"]), true, &mut output).unwrap_err(),
+            "invalid option '-y'"
+        );
+    }
+
+    #[test]
+    fn path_matches_lists_every_executable_in_order() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dirs: Vec<_> = (0..3).map(|_| tempfile::tempdir().unwrap()).collect();
+        for dir in &dirs[..2] {
+            let tool = dir.path().join("tool");
+            fs::write(&tool, "#!/bin/sh\n").unwrap();
+            fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        // Present but not executable, so it is skipped.
+        fs::write(dirs[2].path().join("tool"), "").unwrap();
+        let path_var = format!(
+            "{}::{}:{}",
+            dirs[2].path().display(),
+            dirs[0].path().display(),
+            dirs[1].path().display()
+        );
+
+        let matches = path_matches("tool", &path_var);
+        assert_eq!(
+            matches,
+            vec![
+                dirs[0].path().join("tool").display().to_string(),
+                dirs[1].path().join("tool").display().to_string(),
+            ]
+        );
+        assert!(path_matches("missing", &path_var).is_empty());
+    }
+}
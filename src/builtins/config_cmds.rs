@@ -6,10 +6,11 @@ use rustyline::history::{History, SearchDirection};
 
 use crate::colors::{apply_color_setting, format_color_lines, resolve_color, save_colors};
 use crate::completions::{
-    apply_completion_tokens, format_completion_lines, save_completion_file,
+    apply_completion_tokens, format_completion_lines, load_bash_completion_file,
+    save_completion_file,
 };
 use crate::config::{format_abbreviation_line, save_abbreviations};
-use crate::parse::parse_line;
+use crate::parse::{parse_line, OPERATOR_TOKEN_MARKER};
 use crate::utils::is_valid_var_name;
 use crate::ShellState;
 
@@ -85,6 +86,9 @@ pub(crate) fn handle_complete(
         state.last_status = 0;
         return Ok(());
     }
+    if args[1] == "--from-bash" {
+        return import_bash_completion(state, args);
+    }
     match apply_completion_tokens(args, &mut state.completions) {
         Ok(()) => {
             if let Err(err) = save_completion_file(&state.completions) {
@@ -99,12 +103,41 @@ pub(crate) fn handle_complete(
             eprintln!("usage: complete -c cmd -a 'items...'");
             eprintln!("       complete -c cmd -x 'script'");
             eprintln!("       complete -c cmd -r");
+            eprintln!("       complete --from-bash file");
             state.last_status = 2;
         }
     }
     Ok(())
 }
 
+fn import_bash_completion(state: &mut ShellState, args: &[String]) -> io::Result<()> {
+    let Some(path) = args.get(2) else {
+        eprintln!("complete: missing file after --from-bash");
+        state.last_status = 2;
+        return Ok(());
+    };
+    let result = match load_bash_completion_file(path, &mut state.completions) {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("complete: {path}: {err}");
+            state.last_status = 1;
+            return Ok(());
+        }
+    };
+    for reason in &result.skipped {
+        eprintln!("complete: skipping {reason}");
+    }
+    if result.imported > 0 {
+        if let Err(err) = save_completion_file(&state.completions) {
+            eprintln!("complete: failed to save completions: {err}");
+            state.last_status = 1;
+            return Ok(());
+        }
+    }
+    state.last_status = 0;
+    Ok(())
+}
+
 pub(crate) fn handle_set_color(
     state: &mut ShellState,
     args: &[String],
@@ -160,7 +193,9 @@ pub(crate) fn handle_fish_config(state: &mut ShellState, output: &mut String) ->
     let _ = writeln!(output, "Use: set_color key value");
     let _ = writeln!(
         output,
-        "Keys: prompt_status, prompt_cwd, prompt_git, prompt_symbol, hint"
+        "Keys: prompt_status, prompt_cwd, prompt_git, prompt_symbol[_ok], prompt_symbol_err, \
+         prompt_glyph_ok, prompt_glyph_err, vi_insert, vi_normal, vi_glyph_insert, \
+         vi_glyph_normal, hint"
     );
     state.last_status = 0;
     Ok(())
@@ -172,23 +207,7 @@ pub(crate) fn handle_source(
     _output: &mut String,
 ) -> io::Result<()> {
     if let Some(file) = args.get(1) {
-        match std::fs::read_to_string(file) {
-            Ok(content) => {
-                let tokens = match parse_line(&content) {
-                    Ok(t) => t,
-                    Err(msg) => {
-                        eprintln!("parse error: {msg}");
-                        state.last_status = 2;
-                        return Ok(());
-                    }
-                };
-                execute_script_tokens(state, tokens)?;
-            }
-            Err(err) => {
-                eprintln!("source: {err}");
-                state.last_status = 1;
-            }
-        }
+        source_file(state, file)?;
     } else {
         eprintln!("source: missing file");
         state.last_status = 2;
@@ -196,6 +215,73 @@ pub(crate) fn handle_source(
     Ok(())
 }
 
+/// Runs `file` in the current shell. The status left behind is that of the
+/// last command in the file, or 0 when it has none.
+pub(crate) fn source_file(state: &mut ShellState, file: &str) -> io::Result<()> {
+    match std::fs::read_to_string(file) {
+        Ok(content) => {
+            let tokens = match script_tokens(&content) {
+                Ok(t) => t,
+                Err(msg) => {
+                    eprintln!("parse error: {msg}");
+                    state.last_status = 2;
+                    return Ok(());
+                }
+            };
+            state.last_status = 0;
+            execute_script_tokens(state, tokens)?;
+        }
+        Err(err) => {
+            eprintln!("source: {err}");
+            state.last_status = 1;
+        }
+    }
+    Ok(())
+}
+
+// Tokenizes a whole file, ending each line with `;` the way continuation
+// lines of a compound are joined. A line that does not parse on its own
+// (an open quote) is retried together with the lines after it; one that
+// ends in `&&`, `||` or `|` runs on into the next, and a trailing backslash
+// joins the next line onto it.
+fn script_tokens(content: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut pending = String::new();
+    let mut continued = false;
+    let mut lines = content.lines().peekable();
+    while let Some(line) = lines.next() {
+        pending.push_str(line);
+        let backslashes = pending.len() - pending.trim_end_matches('\\').len();
+        if backslashes % 2 == 1 && lines.peek().is_some() {
+            pending.pop();
+            continue;
+        }
+        let more = match parse_line(&pending) {
+            Ok(more) => more,
+            Err(_) if lines.peek().is_some() => {
+                pending.push('\n');
+                continue;
+            }
+            Err(msg) => return Err(msg),
+        };
+        pending.clear();
+        if more.is_empty() {
+            continue;
+        }
+        if !tokens.is_empty() && !continued {
+            tokens.push(format!("{OPERATOR_TOKEN_MARKER};"));
+        }
+        continued = more.last().is_some_and(|token| {
+            matches!(
+                token.strip_prefix(OPERATOR_TOKEN_MARKER),
+                Some("&&" | "||" | "|")
+            )
+        });
+        tokens.extend(more);
+    }
+    Ok(tokens)
+}
+
 pub(crate) fn handle_history(
     state: &mut ShellState,
     args: &[String],
@@ -361,3 +447,50 @@ fn parse_assoc_literal_item(
     values.insert(key.to_string(), value.to_string());
     Some(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{strip_markers, token_str};
+
+    #[test]
+    fn script_tokens_end_each_line_with_a_separator() {
+        let tokens = script_tokens("echo in\n\nfalse\necho 'a\nb'\n").unwrap();
+        let words: Vec<String> = tokens
+            .iter()
+            .map(|token| strip_markers(token_str(token)))
+            .collect();
+        assert_eq!(words, ["echo", "in", ";", "false", ";", "echo", "a\nb"]);
+        assert!(script_tokens("echo 'open\n").is_err());
+    }
+
+    fn words(content: &str) -> Vec<String> {
+        script_tokens(content)
+            .unwrap()
+            .iter()
+            .map(|token| strip_markers(token_str(token)))
+            .collect()
+    }
+
+    #[test]
+    fn script_tokens_continue_lines_ending_in_an_operator() {
+        assert_eq!(words("true &&\necho a\n"), ["true", "&&", "echo", "a"]);
+        assert_eq!(words("false ||\n\necho b\n"), ["false", "||", "echo", "b"]);
+        assert_eq!(
+            words("echo c |\ntr c C\n"),
+            ["echo", "c", "|", "tr", "c", "C"]
+        );
+    }
+
+    #[test]
+    fn script_tokens_join_lines_after_a_trailing_backslash() {
+        assert_eq!(
+            words("echo a \\\n  b\necho c\n"),
+            ["echo", "a", "b", ";", "echo", "c"]
+        );
+        assert_eq!(
+            words("echo d\\\\\necho e\n"),
+            ["echo", "d\\", ";", "echo", "e"]
+        );
+    }
+}
@@ -1,17 +1,17 @@
 use std::fs;
-use std::io;
+use std::io::{self, BufReader};
 use std::os::unix::fs::OpenOptionsExt;
 use std::sync::Arc;
 
 use glob::Pattern;
 
-use crate::expansion::{expand_globs_with, expand_tokens};
+use crate::expansion::{expand_globs_with, expand_tokens, is_heredoc_operator};
 use crate::expansion::GlobOptions;
-use crate::io_helpers::read_input_line;
+use crate::io_helpers::{read_heredoc, read_input_line};
 use crate::execution::spawn_pipeline_background;
 use crate::parse::{
-    parse_line, split_pipeline, split_sequence, token_str, OutputRedirection, SeqOp,
-    OPERATOR_TOKEN_MARKER,
+    parse_line, split_pipeline, split_sequence, strip_markers, token_str, OutputRedirection,
+    SeqOp, HEREDOC_BODY_MARKER, OPERATOR_TOKEN_MARKER,
 };
 use crate::process_subst::{apply_process_subst, FdGuard, ProcessSubstResult};
 use crate::{build_expansion_context, trace_tokens, ShellState};
@@ -20,6 +20,80 @@ use nix::unistd::mkfifo;
 
 use super::scripting::execute_script_tokens;
 
+/// A pending `break n` or `continue n`, counted in enclosing loops still
+/// to unwind.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum LoopControl {
+    Break(usize),
+    Continue(usize),
+}
+
+/// `break [n]` / `continue [n]`. A count larger than the number of
+/// enclosing loops applies to all of them.
+pub(crate) fn handle_loop_control(state: &mut ShellState, args: &[String]) {
+    let name = args[0].as_str();
+    if state.loop_depth == 0 {
+        eprintln!("{name}: only valid inside a loop");
+        state.last_status = 2;
+        return;
+    }
+    let count = match args.get(1).map(|arg| arg.parse::<usize>()) {
+        None => 1,
+        Some(Ok(count)) if count > 0 => count.min(state.loop_depth),
+        Some(Ok(_)) => {
+            eprintln!("{name}: loop count out of range");
+            state.last_status = 1;
+            return;
+        }
+        Some(Err(_)) => {
+            eprintln!("{name}: numeric argument required");
+            state.last_status = 2;
+            return;
+        }
+    };
+    state.loop_control = Some(if name == "break" {
+        LoopControl::Break(count)
+    } else {
+        LoopControl::Continue(count)
+    });
+    state.last_status = 0;
+}
+
+// Runs a loop with `loop_depth` raised so `break` and `continue` inside it
+// are accepted.
+fn run_loop(
+    state: &mut ShellState,
+    body: impl FnOnce(&mut ShellState) -> io::Result<()>,
+) -> io::Result<()> {
+    state.loop_depth += 1;
+    let result = body(state);
+    state.loop_depth -= 1;
+    result
+}
+
+impl LoopControl {
+    // What the innermost loop still unwinding does: whether it stops, and
+    // what is left for the loop around it.
+    fn unwind(self) -> (bool, Option<LoopControl>) {
+        match self {
+            LoopControl::Break(1) => (true, None),
+            LoopControl::Continue(1) => (false, None),
+            LoopControl::Break(count) => (true, Some(LoopControl::Break(count - 1))),
+            LoopControl::Continue(count) => (true, Some(LoopControl::Continue(count - 1))),
+        }
+    }
+}
+
+// Called after each part of a loop runs; true when the loop should stop.
+fn loop_should_stop(state: &mut ShellState) -> bool {
+    let Some(control) = state.loop_control.take() else {
+        return state.return_requested.is_some();
+    };
+    let (stop, outer) = control.unwind();
+    state.loop_control = outer;
+    stop
+}
+
 #[derive(Copy, Clone)]
 pub(crate) enum CompoundKind {
     If,
@@ -66,6 +140,7 @@ pub(crate) fn read_compound_tokens(
     kind: CompoundKind,
 ) -> io::Result<Vec<String>> {
     // Interactive loop collects lines until the compound is complete.
+    read_pending_heredocs(state, &mut tokens)?;
     while needs_more_compound(&tokens, kind) {
         let line = match read_input_line(&mut state.editor, state.interactive, "> ")? {
             Some(line) => line,
@@ -76,9 +151,10 @@ pub(crate) fn read_compound_tokens(
                 ));
             }
         };
-        let more = parse_line(line.trim_end()).map_err(|err| {
+        let mut more = parse_line(line.trim_end()).map_err(|err| {
             io::Error::new(io::ErrorKind::InvalidInput, format!("parse error: {err}"))
         })?;
+        read_pending_heredocs(state, &mut more)?;
         if !more.is_empty() {
             tokens.push(format!("{OPERATOR_TOKEN_MARKER};"));
             tokens.extend(more);
@@ -87,6 +163,29 @@ pub(crate) fn read_compound_tokens(
     Ok(tokens)
 }
 
+// Reads the body of each heredoc on a line right away, as the line after it
+// belongs to the heredoc rather than to the compound, and keeps it in the
+// delimiter word so every run of the body sees it.
+fn read_pending_heredocs(state: &mut ShellState, tokens: &mut [String]) -> io::Result<()> {
+    for idx in 1..tokens.len() {
+        if !is_heredoc_operator(&tokens[idx - 1]) || tokens[idx].contains(HEREDOC_BODY_MARKER) {
+            continue;
+        }
+        let delimiter = strip_markers(&tokens[idx]);
+        let strip_tabs = tokens[idx - 1].ends_with('-');
+        let body = read_heredoc(
+            Some(&mut state.editor),
+            state.interactive,
+            &delimiter,
+            strip_tabs,
+        )
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        tokens[idx].push(HEREDOC_BODY_MARKER);
+        tokens[idx].push_str(&body);
+    }
+    Ok(())
+}
+
 fn needs_more_compound(tokens: &[String], kind: CompoundKind) -> bool {
     // Count open/close keywords to handle nesting across multi-line compounds.
     let mut if_count = 0i32;
@@ -105,6 +204,7 @@ fn needs_more_compound(tokens: &[String], kind: CompoundKind) -> bool {
             "done" => {
                 while_count -= 1;
                 for_count -= 1;
+                select_count -= 1;
             }
             "for" => for_count += 1,
             "select" => select_count += 1,
@@ -130,17 +230,25 @@ pub(crate) fn execute_if(
     tokens: Vec<String>,
     display: &str,
 ) -> io::Result<()> {
-    let (cond_tokens, then_tokens, else_tokens) = parse_if_tokens(tokens)?;
-    execute_script_tokens(state, cond_tokens)?;
-    if state.return_requested.is_some() {
-        return Ok(());
+    let (branches, else_tokens) = parse_if_tokens(tokens)?;
+    let mut taken = false;
+    for (cond_tokens, then_tokens) in branches {
+        execute_script_tokens(state, cond_tokens)?;
+        if state.unwinding() {
+            return Ok(());
+        }
+        if state.last_status == 0 {
+            execute_script_tokens(state, then_tokens)?;
+            taken = true;
+            break;
+        }
     }
-    if state.last_status == 0 {
-        execute_script_tokens(state, then_tokens)?;
-    } else if let Some(tokens) = else_tokens {
-        execute_script_tokens(state, tokens)?;
+    if !taken {
+        if let Some(tokens) = else_tokens {
+            execute_script_tokens(state, tokens)?;
+        }
     }
-    if state.return_requested.is_some() {
+    if state.unwinding() {
         return Ok(());
     }
     trace_tokens(state, "if display", &[display.to_string()]);
@@ -152,21 +260,35 @@ pub(crate) fn execute_while(
     tokens: Vec<String>,
     _display: &str,
 ) -> io::Result<()> {
-    let (cond_tokens, body_tokens) = parse_while_tokens(tokens)?;
-    loop {
-        execute_script_tokens(state, cond_tokens.clone())?;
-        if state.return_requested.is_some() {
-            break;
-        }
-        if state.last_status != 0 {
-            break;
-        }
-        execute_script_tokens(state, body_tokens.clone())?;
-        if state.return_requested.is_some() {
-            break;
+    let (cond_tokens, body_tokens, trailing) = parse_while_tokens(tokens)?;
+    let input = loop_input_file(state, trailing)?;
+    let outer_input = input.map(|file| state.loop_input.replace(BufReader::new(file)));
+    let result = run_loop(state, |state| {
+        loop {
+            if loop_interrupted(state) {
+                break;
+            }
+            execute_script_tokens(state, cond_tokens.clone())?;
+            if state.unwinding() {
+                if loop_should_stop(state) {
+                    break;
+                }
+                continue;
+            }
+            if state.last_status != 0 {
+                break;
+            }
+            execute_script_tokens(state, body_tokens.clone())?;
+            if loop_should_stop(state) {
+                break;
+            }
         }
+        Ok(())
+    });
+    if let Some(outer_input) = outer_input {
+        state.loop_input = outer_input;
     }
-    Ok(())
+    result
 }
 
 pub(crate) fn execute_for(
@@ -174,7 +296,15 @@ pub(crate) fn execute_for(
     tokens: Vec<String>,
     _display: &str,
 ) -> io::Result<()> {
-    let (var, list_tokens, body_tokens) = parse_for_tokens(tokens)?;
+    let (vars, list_tokens, body_tokens) = parse_for_tokens(tokens)?;
+    if let Some(var) = vars
+        .iter()
+        .find(|var| !crate::utils::is_valid_var_name(var))
+    {
+        eprintln!("for: invalid variable name '{var}'");
+        state.last_status = 2;
+        return Ok(());
+    }
     let glob_options = GlobOptions {
         extglob: state.extglob,
         nullglob: state.nullglob,
@@ -194,6 +324,7 @@ pub(crate) fn execute_for(
             state.builtin_enabled.clone(),
             glob_options,
             &positional,
+            state.special_params(),
             true,
         );
         let list_expanded = expand_tokens(list_tokens, &ctx)
@@ -201,14 +332,32 @@ pub(crate) fn execute_for(
         expand_globs_with(list_expanded, glob_options)
             .map_err(|msg| io::Error::new(io::ErrorKind::InvalidInput, msg))?
     };
-    for item in list {
-        std::env::set_var(&var, item);
-        execute_script_tokens(state, body_tokens.clone())?;
-        if state.return_requested.is_some() {
-            break;
+    // `for k v in ...` takes as many items per pass as it has variables;
+    // a short final chunk leaves the trailing variables empty.
+    run_loop(state, |state| {
+        for items in list.chunks(vars.len()) {
+            if loop_interrupted(state) {
+                break;
+            }
+            for (idx, var) in vars.iter().enumerate() {
+                std::env::set_var(var, items.get(idx).map(String::as_str).unwrap_or_default());
+            }
+            execute_script_tokens(state, body_tokens.clone())?;
+            if loop_should_stop(state) {
+                break;
+            }
         }
+        Ok(())
+    })
+}
+
+fn loop_interrupted(state: &mut ShellState) -> bool {
+    // The flag stays set so enclosing loops unwind too; run_once clears it.
+    if state.interrupt_requested() {
+        state.last_status = 130;
+        return true;
     }
-    Ok(())
+    false
 }
 
 pub(crate) fn execute_select(
@@ -217,6 +366,11 @@ pub(crate) fn execute_select(
     _display: &str,
 ) -> io::Result<()> {
     let (var, list_tokens, body_tokens) = parse_select_tokens(tokens)?;
+    if !crate::utils::is_valid_var_name(&var) {
+        eprintln!("select: invalid variable name '{var}'");
+        state.last_status = 2;
+        return Ok(());
+    }
     let glob_options = GlobOptions {
         extglob: state.extglob,
         nullglob: state.nullglob,
@@ -236,6 +390,7 @@ pub(crate) fn execute_select(
             state.builtin_enabled.clone(),
             glob_options,
             &positional,
+            state.special_params(),
             true,
         );
         expand_tokens(list_tokens, &ctx)
@@ -250,6 +405,7 @@ pub(crate) fn execute_select(
         state.assoc_arrays.clone(),
         state.builtin_enabled.clone(),
         glob_options,
+        state.special_params(),
         true,
     )?;
     let _fd_guard = FdGuard(keep_fds);
@@ -257,9 +413,12 @@ pub(crate) fn execute_select(
         .map_err(|msg| io::Error::new(io::ErrorKind::InvalidInput, msg))?;
     let ps3 = std::env::var("PS3").unwrap_or_else(|_| "#? ".to_string());
 
-    loop {
-        for (idx, item) in items.iter().enumerate() {
-            println!("{:>2}) {}", idx + 1, item);
+    // Like bash, the menu is shown again only after an empty reply.
+    let mut show_menu = true;
+    run_loop(state, |state| loop {
+        if show_menu {
+            eprint!("{}", format_select_menu(&items));
+            show_menu = false;
         }
         let line = match read_input_line(&mut state.editor, state.interactive, &ps3)? {
             Some(line) => line,
@@ -268,22 +427,36 @@ pub(crate) fn execute_select(
                 return Ok(());
             }
         };
-        let input = line.trim();
-        if input.is_empty() {
+        if line.trim().is_empty() {
+            show_menu = true;
             continue;
         }
-        std::env::set_var("REPLY", input);
-        let selected = input
-            .parse::<usize>()
-            .ok()
-            .and_then(|idx| items.get(idx.saturating_sub(1)).cloned())
-            .unwrap_or_default();
+        std::env::set_var("REPLY", &line);
+        let selected = select_choice(&items, &line).unwrap_or_default();
         std::env::set_var(&var, selected);
         execute_script_tokens(state, body_tokens.clone())?;
-        if state.return_requested.is_some() {
+        if loop_should_stop(state) || loop_interrupted(state) {
             return Ok(());
         }
+    })
+}
+
+/// The numbered `select` menu, one `N) item` per line with the numbers
+/// right-aligned to the widest one.
+fn format_select_menu(items: &[String]) -> String {
+    let width = items.len().to_string().len();
+    let mut out = String::new();
+    for (idx, item) in items.iter().enumerate() {
+        out.push_str(&format!("{:>width$}) {item}\n", idx + 1));
     }
+    out
+}
+
+/// Maps a reply to the chosen item; anything but an in-range number is
+/// `None`, which leaves the `select` variable empty.
+fn select_choice<'a>(items: &'a [String], reply: &str) -> Option<&'a str> {
+    let choice = reply.trim().parse::<usize>().ok()?;
+    items.get(choice.checked_sub(1)?).map(String::as_str)
 }
 
 pub(crate) fn execute_brace_group(
@@ -349,6 +522,7 @@ pub(crate) fn execute_coproc(
         state.builtin_enabled.clone(),
         glob_options,
         &positional,
+        state.special_params(),
         true,
     );
     let expanded = expand_tokens(body_tokens, &ctx)
@@ -362,6 +536,7 @@ pub(crate) fn execute_coproc(
         state.assoc_arrays.clone(),
         state.builtin_enabled.clone(),
         glob_options,
+        state.special_params(),
         true,
     )?;
     let _fd_guard = FdGuard(keep_fds);
@@ -395,7 +570,7 @@ pub(crate) fn execute_coproc(
             "coproc does not allow stdin redirection",
         ));
     }
-    if pipeline.last().and_then(|cmd| cmd.stdout.as_ref()).is_some() {
+    if pipeline.last().is_some_and(|cmd| cmd.redirects_stdout()) {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
             "coproc does not allow stdout redirection",
@@ -483,6 +658,7 @@ pub(crate) fn execute_case(
         state.builtin_enabled.clone(),
         glob_options,
         &positional,
+        state.special_params(),
         true,
     );
     let word_expanded = match expand_tokens(word_tokens, &ctx) {
@@ -521,7 +697,7 @@ pub(crate) fn execute_case(
         }
         if matched {
             execute_script_tokens(state, clause.body)?;
-            if state.return_requested.is_some() {
+            if state.unwinding() {
                 return Ok(());
             }
             trace_tokens(state, "case display", &[display.to_string()]);
@@ -534,10 +710,12 @@ pub(crate) fn execute_case(
     Ok(())
 }
 
-type IfParts = (Vec<String>, Vec<String>, Option<Vec<String>>);
+// Each `if`/`elif` condition with its `then` body, in order, plus `else`.
+type IfParts = (Vec<(Vec<String>, Vec<String>)>, Option<Vec<String>>);
 
 fn parse_if_tokens(tokens: Vec<String>) -> io::Result<IfParts> {
     let iter = tokens.into_iter().peekable();
+    let mut branches = Vec::new();
     let mut condition = Vec::new();
     let mut then_body = Vec::new();
     let mut else_body = None;
@@ -548,17 +726,26 @@ fn parse_if_tokens(tokens: Vec<String>) -> io::Result<IfParts> {
             "if" => {
                 if t == "then" {
                     stage = "then";
-                } else if t == "if" {
+                } else if t == "if" && branches.is_empty() && condition.is_empty() {
                     continue;
                 } else {
                     condition.push(token);
                 }
             }
             "then" => {
-                if t == "else" {
+                if t == "elif" || t == "else" || t == "fi" {
+                    branches.push((
+                        std::mem::take(&mut condition),
+                        std::mem::take(&mut then_body),
+                    ));
+                }
+                if t == "elif" {
+                    stage = "if";
+                } else if t == "else" {
                     stage = "else";
                     else_body = Some(Vec::new());
                 } else if t == "fi" {
+                    stage = "fi";
                     break;
                 } else {
                     then_body.push(token);
@@ -575,19 +762,29 @@ fn parse_if_tokens(tokens: Vec<String>) -> io::Result<IfParts> {
             _ => {}
         }
     }
-    if condition.is_empty() || then_body.is_empty() {
+    if stage == "then" {
+        // The input ended without `fi`.
+        branches.push((condition, then_body));
+    }
+    if stage == "if"
+        || branches
+            .iter()
+            .any(|(condition, body)| condition.is_empty() || body.is_empty())
+    {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
             "invalid if statement",
         ));
     }
-    Ok((condition, then_body, else_body))
+    Ok((branches, else_body))
 }
 
-fn parse_while_tokens(tokens: Vec<String>) -> io::Result<(Vec<String>, Vec<String>)> {
+// Returns the condition, the body and whatever follows `done`.
+fn parse_while_tokens(tokens: Vec<String>) -> io::Result<(Vec<String>, Vec<String>, Vec<String>)> {
     let iter = tokens.into_iter().peekable();
     let mut condition = Vec::new();
     let mut body = Vec::new();
+    let mut trailing = Vec::new();
     let mut stage = "while";
     for token in iter {
         let t = token_str(&token).to_string();
@@ -603,12 +800,12 @@ fn parse_while_tokens(tokens: Vec<String>) -> io::Result<(Vec<String>, Vec<Strin
             }
             "do" => {
                 if t == "done" {
-                    break;
+                    stage = "done";
                 } else {
                     body.push(token);
                 }
             }
-            _ => {}
+            _ => trailing.push(token),
         }
     }
     if condition.is_empty() || body.is_empty() {
@@ -617,12 +814,52 @@ fn parse_while_tokens(tokens: Vec<String>) -> io::Result<(Vec<String>, Vec<Strin
             "invalid while statement",
         ));
     }
-    Ok((condition, body))
+    Ok((condition, body, trailing))
 }
 
-fn parse_for_tokens(tokens: Vec<String>) -> io::Result<(String, Vec<String>, Vec<String>)> {
+// Opens the file of a `done < file` redirection, if there is one.
+fn loop_input_file(state: &mut ShellState, trailing: Vec<String>) -> io::Result<Option<fs::File>> {
+    let mut words = trailing.into_iter();
+    if words.next().is_none_or(|op| token_str(&op) != "<") {
+        return Ok(None);
+    }
+    let glob_options = GlobOptions {
+        extglob: state.extglob,
+        nullglob: state.nullglob,
+        failglob: state.failglob,
+        dotglob: state.dotglob,
+        nocaseglob: state.nocaseglob,
+        dirspell: state.dirspell,
+    };
+    let positional = state.current_positional().to_vec();
+    let ctx = build_expansion_context(
+        Arc::clone(&state.fg_pgid),
+        state.trace,
+        state.sandbox.clone(),
+        state.arrays.clone(),
+        state.assoc_arrays.clone(),
+        state.builtin_enabled.clone(),
+        glob_options,
+        &positional,
+        state.special_params(),
+        true,
+    );
+    let target = expand_tokens(words.collect(), &ctx)
+        .map_err(|msg| io::Error::new(io::ErrorKind::InvalidInput, msg))?;
+    let [path] = target.as_slice() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "done: ambiguous redirect",
+        ));
+    };
+    fs::File::open(path)
+        .map(Some)
+        .map_err(|err| io::Error::new(err.kind(), format!("{path}: {err}")))
+}
+
+fn parse_for_tokens(tokens: Vec<String>) -> io::Result<(Vec<String>, Vec<String>, Vec<String>)> {
     let mut iter = tokens.into_iter();
-    let mut var = String::new();
+    let mut vars = Vec::new();
     let mut list = Vec::new();
     let mut body = Vec::new();
     let mut stage = "for";
@@ -633,13 +870,15 @@ fn parse_for_tokens(tokens: Vec<String>) -> io::Result<(String, Vec<String>, Vec
                 if t == "for" {
                     continue;
                 } else {
-                    var = token;
+                    vars.push(token);
                     stage = "in";
                 }
             }
             "in" => {
                 if t == "in" {
                     stage = "list";
+                } else if t != ";" && t != "do" {
+                    vars.push(token);
                 } else {
                     return Err(io::Error::new(
                         io::ErrorKind::InvalidInput,
@@ -648,12 +887,10 @@ fn parse_for_tokens(tokens: Vec<String>) -> io::Result<(String, Vec<String>, Vec
                 }
             }
             "list" => {
-                if t == ";" || t == "do" {
-                    if t == "do" {
-                        stage = "do";
-                    }
+                if t == "do" {
+                    stage = "do";
                     break;
-                } else {
+                } else if t != ";" {
                     list.push(token);
                 }
             }
@@ -675,13 +912,13 @@ fn parse_for_tokens(tokens: Vec<String>) -> io::Result<(String, Vec<String>, Vec
             "expected 'do' in for statement",
         ));
     }
-    if var.is_empty() || body.is_empty() {
+    if vars.is_empty() || body.is_empty() {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
             "invalid for statement",
         ));
     }
-    Ok((var, list, body))
+    Ok((vars, list, body))
 }
 
 fn parse_select_tokens(tokens: Vec<String>) -> io::Result<(String, Vec<String>, Vec<String>)> {
@@ -712,12 +949,10 @@ fn parse_select_tokens(tokens: Vec<String>) -> io::Result<(String, Vec<String>,
                 }
             }
             "list" => {
-                if t == ";" || t == "do" {
-                    if t == "do" {
-                        stage = "do";
-                    }
+                if t == "do" {
+                    stage = "do";
                     break;
-                } else {
+                } else if t != ";" {
                     list.push(token);
                 }
             }
@@ -916,6 +1151,95 @@ mod tests {
     use super::*;
     use crate::parse::parse_line;
 
+    #[test]
+    fn loop_control_unwinds_one_loop_per_level() {
+        assert_eq!(LoopControl::Break(1).unwind(), (true, None));
+        assert_eq!(LoopControl::Continue(1).unwind(), (false, None));
+        // `break 2`: the inner loop stops and hands a plain break outward.
+        assert_eq!(
+            LoopControl::Break(2).unwind(),
+            (true, Some(LoopControl::Break(1)))
+        );
+        // `continue 2`: the inner loop stops, the outer one moves on.
+        let (inner_stops, outer) = LoopControl::Continue(2).unwind();
+        assert!(inner_stops);
+        assert_eq!(outer.map(LoopControl::unwind), Some((false, None)));
+    }
+
+    #[test]
+    fn parse_for_reads_several_variables() {
+        let tokens = parse_line("for k v in a 1 b 2; do echo $k; done").unwrap();
+        let (vars, list, body) = parse_for_tokens(tokens).unwrap();
+        assert_eq!(vars, vec!["k", "v"]);
+        assert_eq!(list, vec!["a", "1", "b", "2"]);
+        assert_eq!(token_str(&body[0]), "echo");
+        assert!(parse_for_tokens(parse_line("for k v do echo; done").unwrap()).is_err());
+    }
+
+    #[test]
+    fn parse_if_collects_elif_branches() {
+        let words = |tokens: &[String]| {
+            tokens
+                .iter()
+                .map(|token| token_str(token))
+                .filter(|word| *word != ";")
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let line = "if test a; then echo 1; elif test b; then echo 2; elif test c; then echo 3; \
+                    else echo 4; fi";
+        let (branches, else_body) = parse_if_tokens(parse_line(line).unwrap()).unwrap();
+        let branches: Vec<_> = branches
+            .iter()
+            .map(|(condition, body)| (words(condition), words(body)))
+            .collect();
+        assert_eq!(
+            branches,
+            [
+                ("test a".to_string(), "echo 1".to_string()),
+                ("test b".to_string(), "echo 2".to_string()),
+                ("test c".to_string(), "echo 3".to_string()),
+            ]
+        );
+        assert_eq!(else_body.as_deref().map(words).as_deref(), Some("echo 4"));
+
+        let dangling = parse_line("if true; then echo 1; elif true; fi").unwrap();
+        assert!(parse_if_tokens(dangling).is_err());
+
+        let mut tokens = parse_line("if false; then echo 1").unwrap();
+        for line in ["elif true; then", "echo 2", "else"] {
+            tokens.extend(parse_line(line).unwrap());
+            assert!(needs_more_compound(&tokens, CompoundKind::If), "{line}");
+        }
+        tokens.extend(parse_line("fi").unwrap());
+        assert!(!needs_more_compound(&tokens, CompoundKind::If));
+    }
+
+    #[test]
+    fn comments_and_quoted_keywords_do_not_close_compounds() {
+        let mut tokens = parse_line("if true").unwrap();
+        for line in ["# fi", "then", "echo \"fi\" 'done' # fi"] {
+            tokens.extend(parse_line(line).unwrap());
+            assert!(needs_more_compound(&tokens, CompoundKind::If), "{line}");
+        }
+        tokens.extend(parse_line("fi # trailing").unwrap());
+        assert!(!needs_more_compound(&tokens, CompoundKind::If));
+    }
+
+    #[test]
+    fn select_menu_and_choices() {
+        let items: Vec<String> = (1..=10).map(|idx| format!("item{idx}")).collect();
+        let menu = format_select_menu(&items);
+        assert!(menu.starts_with(" 1) item1\n 2) item2\n"));
+        assert!(menu.ends_with("10) item10\n"));
+
+        assert_eq!(select_choice(&items, "3"), Some("item3"));
+        assert_eq!(select_choice(&items, " 10 "), Some("item10"));
+        assert_eq!(select_choice(&items, "0"), None);
+        assert_eq!(select_choice(&items, "11"), None);
+        assert_eq!(select_choice(&items, "item1"), None);
+    }
+
     #[test]
     fn parse_case_basic() {
         let tokens = parse_line("case x in foo) echo hi ;; esac").unwrap();
@@ -0,0 +1,246 @@
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::ShellState;
+
+/// The logical working directory: `$PWD` when it still names the current
+/// directory (possibly through symlinks), otherwise the physical path.
+pub(crate) fn logical_pwd() -> PathBuf {
+    let physical = env::current_dir().unwrap_or_else(|_| "/".into());
+    match env::var_os("PWD").map(PathBuf::from) {
+        Some(pwd) if pwd.is_absolute() && same_file(&pwd, &physical) => pwd,
+        _ => physical,
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Joins `target` onto `base` and folds `.` and `..` textually, the way
+/// `cd -L` walks back out of a symlinked directory.
+pub(crate) fn logical_join(base: &Path, target: &str) -> PathBuf {
+    let mut out = PathBuf::from("/");
+    for component in base.join(target).components() {
+        match component {
+            Component::Normal(part) => out.push(part),
+            Component::ParentDir => {
+                out.pop();
+            }
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+    out
+}
+
+/// `cd [-L|-P] [dir|-]`: keeps `PWD` and `OLDPWD` exported alongside the
+/// process working directory. `set -o physical` makes `-P` the default.
+pub(crate) fn handle_cd(state: &mut ShellState, args: &[String], output: &mut String) {
+    let mut physical = state.physical;
+    let mut idx = 1usize;
+    while let Some(arg) = args.get(idx) {
+        match arg.as_str() {
+            "-L" => physical = false,
+            "-P" => physical = true,
+            "--" => {
+                idx += 1;
+                break;
+            }
+            _ => break,
+        }
+        idx += 1;
+    }
+    let mut announce = false;
+    let target = match args.get(idx).map(String::as_str) {
+        None => match env::var("HOME") {
+            Ok(home) => home,
+            Err(_) => {
+                eprintln!("cd: HOME not set");
+                state.last_status = 1;
+                return;
+            }
+        },
+        Some("-") => match env::var("OLDPWD") {
+            Ok(old) => {
+                announce = true;
+                old
+            }
+            Err(_) => {
+                eprintln!("cd: OLDPWD not set");
+                state.last_status = 1;
+                return;
+            }
+        },
+        Some(target) => match expand_cd_tilde(target) {
+            Ok(target) => target,
+            Err(err) => {
+                eprintln!("cd: {err}");
+                state.last_status = 1;
+                return;
+            }
+        },
+    };
+
+    let old = logical_pwd();
+    let attempted = if physical {
+        PathBuf::from(&target)
+    } else {
+        logical_join(&old, &target)
+    };
+    let new_pwd = if physical {
+        env::set_current_dir(&attempted).and_then(|_| env::current_dir())
+    } else {
+        env::set_current_dir(&attempted).map(|_| attempted.clone())
+    };
+    match new_pwd {
+        Ok(new_pwd) => {
+            env::set_var("OLDPWD", &old);
+            env::set_var("PWD", &new_pwd);
+            if announce {
+                let _ = writeln!(output, "{}", new_pwd.display());
+            }
+            state.last_status = 0;
+        }
+        Err(err) => {
+            eprintln!("cd: {target}: {}", cd_error_message(&attempted, &err));
+            state.last_status = 1;
+        }
+    }
+}
+
+/// Resolves a leading `~` or `~user` in a `cd` operand. Unlike word
+/// expansion, an unset `HOME` or unknown user is an error rather than a
+/// literal path. A tilde still here was quoted or named no known user, so
+/// an existing path spelled that way is taken as it is.
+fn expand_cd_tilde(target: &str) -> Result<String, String> {
+    let Some(rest) = target.strip_prefix('~') else {
+        return Ok(target.to_string());
+    };
+    if Path::new(target).exists() {
+        return Ok(target.to_string());
+    }
+    let (user, tail) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let home = if user.is_empty() {
+        env::var("HOME").map_err(|_| "HOME not set".to_string())?
+    } else {
+        home_for_user(user).ok_or_else(|| format!("{target}: no such user"))?
+    };
+    Ok(format!("{home}{tail}"))
+}
+
+fn home_for_user(user: &str) -> Option<String> {
+    let name = std::ffi::CString::new(user).ok()?;
+    // SAFETY: getpwnam returns null or a pointer to static storage that
+    // stays valid until the next passwd lookup; the directory is copied out
+    // immediately.
+    unsafe {
+        let entry = libc::getpwnam(name.as_ptr());
+        if entry.is_null() || (*entry).pw_dir.is_null() {
+            return None;
+        }
+        let dir = std::ffi::CStr::from_ptr((*entry).pw_dir);
+        Some(dir.to_string_lossy().into_owned())
+    }
+}
+
+/// Bash-style reason for a failed `cd`, checking the target itself so a
+/// regular file reads as "not a directory" whatever errno the kernel chose.
+fn cd_error_message(path: &Path, err: &io::Error) -> String {
+    if fs::metadata(path).is_ok_and(|meta| !meta.is_dir()) {
+        return "not a directory".to_string();
+    }
+    match err.kind() {
+        io::ErrorKind::NotFound => "no such file or directory".to_string(),
+        io::ErrorKind::PermissionDenied => "permission denied".to_string(),
+        io::ErrorKind::NotADirectory => "not a directory".to_string(),
+        _ => err.to_string(),
+    }
+}
+
+/// `pwd [-L|-P]`: the logical `$PWD` by default, or the resolved path.
+/// `physical` picks the default when neither flag is given.
+pub(crate) fn pwd_for_args(args: &[String], physical: bool) -> Result<PathBuf, String> {
+    let mut physical = physical;
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "-L" => physical = false,
+            "-P" => physical = true,
+            other => return Err(format!("invalid option '{other}'")),
+        }
+    }
+    if physical {
+        env::current_dir().map_err(|err| err.to_string())
+    } else {
+        Ok(logical_pwd())
+    }
+}
+
+pub(crate) fn handle_pwd(state: &mut ShellState, args: &[String], output: &mut String) {
+    match pwd_for_args(args, state.physical) {
+        Ok(cwd) => {
+            let _ = writeln!(output, "{}", cwd.display());
+            state.last_status = 0;
+        }
+        Err(err) => {
+            eprintln!("pwd: {err}");
+            eprintln!("usage: pwd [-L|-P]");
+            state.last_status = 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logical_join_folds_dot_segments() {
+        let base = Path::new("/home/user/link");
+        assert_eq!(logical_join(base, ".."), PathBuf::from("/home/user"));
+        assert_eq!(
+            logical_join(base, "./a/../b"),
+            PathBuf::from("/home/user/link/b")
+        );
+        assert_eq!(logical_join(base, "/tmp/x/.."), PathBuf::from("/tmp"));
+        assert_eq!(logical_join(Path::new("/"), "../.."), PathBuf::from("/"));
+    }
+
+    #[test]
+    fn cd_tilde_resolves_users_and_rejects_unknown_ones() {
+        assert_eq!(expand_cd_tilde("plain/~x").unwrap(), "plain/~x");
+        let root_home = home_for_user("root").expect("root has a passwd entry");
+        assert_eq!(
+            expand_cd_tilde("~root/sub").unwrap(),
+            format!("{root_home}/sub")
+        );
+        assert_eq!(
+            expand_cd_tilde("~no_such_user_here/x").unwrap_err(),
+            "~no_such_user_here/x: no such user"
+        );
+    }
+
+    #[test]
+    fn cd_errors_name_the_reason() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("plain");
+        fs::write(&file, "").unwrap();
+        let err = env::set_current_dir(&file).unwrap_err();
+        assert_eq!(cd_error_message(&file, &err), "not a directory");
+
+        let missing = dir.path().join("missing");
+        let err = env::set_current_dir(&missing).unwrap_err();
+        assert_eq!(
+            cd_error_message(&missing, &err),
+            "no such file or directory"
+        );
+
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(cd_error_message(dir.path(), &denied), "permission denied");
+    }
+}
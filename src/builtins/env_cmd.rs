@@ -0,0 +1,171 @@
+use std::fmt::Write;
+use std::io;
+use std::process::Command;
+
+use crate::execution::{build_command, run_command_in_foreground, sandbox_options_for_command};
+use crate::parse::CommandSpec;
+use crate::utils::is_valid_var_name;
+use crate::ShellState;
+
+use super::timeout::{finish_foreground, maxtime_watchdog};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct EnvRequest {
+    pub clear: bool,
+    pub unset: Vec<String>,
+    pub set: Vec<(String, String)>,
+    pub command: Vec<String>,
+}
+
+impl EnvRequest {
+    fn apply(&self, command: &mut Command) {
+        if self.clear {
+            command.env_clear();
+        }
+        for name in &self.unset {
+            command.env_remove(name);
+        }
+        for (name, value) in &self.set {
+            command.env(name, value);
+        }
+    }
+
+    // What `env` prints when no command follows the options.
+    fn listing(&self) -> Vec<(String, String)> {
+        let mut vars: Vec<(String, String)> = if self.clear {
+            Vec::new()
+        } else {
+            std::env::vars()
+                .filter(|(name, _)| !self.unset.contains(name))
+                .collect()
+        };
+        for (name, value) in &self.set {
+            match vars.iter_mut().find(|(existing, _)| existing == name) {
+                Some(entry) => entry.1 = value.clone(),
+                None => vars.push((name.clone(), value.clone())),
+            }
+        }
+        vars
+    }
+}
+
+/// Parses `env [-i] [-u NAME]... [NAME=value]... [command [args...]]`.
+pub(crate) fn parse_env_args(args: &[String]) -> Result<EnvRequest, String> {
+    let mut request = EnvRequest::default();
+    let mut idx = 1;
+    while let Some(arg) = args.get(idx) {
+        match arg.as_str() {
+            "-i" | "-" | "--ignore-environment" => request.clear = true,
+            "-u" | "--unset" => {
+                let name = args
+                    .get(idx + 1)
+                    .ok_or_else(|| "option requires an argument -- 'u'".to_string())?;
+                request.unset.push(name.clone());
+                idx += 1;
+            }
+            "--" => {
+                idx += 1;
+                break;
+            }
+            other if other.starts_with('-') && other.len() > 1 => {
+                return Err(format!("invalid option '{other}'"));
+            }
+            _ => break,
+        }
+        idx += 1;
+    }
+    while let Some((name, value)) = args.get(idx).and_then(|arg| arg.split_once('=')) {
+        if !is_valid_var_name(name) {
+            break;
+        }
+        request.set.push((name.to_string(), value.to_string()));
+        idx += 1;
+    }
+    request.command = args[idx.min(args.len())..].to_vec();
+    Ok(request)
+}
+
+pub(crate) fn handle_env(
+    state: &mut ShellState,
+    cmd: &CommandSpec,
+    display: &str,
+    output: &mut String,
+) -> io::Result<()> {
+    let request = match parse_env_args(&cmd.args) {
+        Ok(request) => request,
+        Err(err) => {
+            eprintln!("env: {err}");
+            eprintln!("usage: env [-i] [-u name] [name=value]... [command [args...]]");
+            state.last_status = 2;
+            return Ok(());
+        }
+    };
+    if request.command.is_empty() {
+        for (name, value) in request.listing() {
+            let _ = writeln!(output, "{name}={value}");
+        }
+        state.last_status = 0;
+        return Ok(());
+    }
+
+    let mut inner = cmd.clone();
+    inner.args = request.command.clone();
+    let mut command = build_command(&inner)?;
+    request.apply(&mut command);
+    let sandbox = sandbox_options_for_command(&inner, &state.sandbox, state.trace);
+    let watchdog = maxtime_watchdog(state);
+    let result = run_command_in_foreground(
+        &mut command,
+        &state.fg_pgid,
+        state.shell_pgid,
+        state.trace,
+        sandbox,
+    );
+    let timed_out = watchdog.is_some_and(|watchdog| watchdog.finish());
+    finish_foreground(state, result, timed_out, "env", display);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_options_assignments_and_command() {
+        let request = parse_env_args(&args(&[
+            "env", "-i", "-u", "HOME", "A=1", "B=x=y", "cmd", "C=2",
+        ]))
+        .unwrap();
+        assert_eq!(
+            request,
+            EnvRequest {
+                clear: true,
+                unset: vec!["HOME".to_string()],
+                set: vec![
+                    ("A".to_string(), "1".to_string()),
+                    ("B".to_string(), "x=y".to_string())
+                ],
+                command: args(&["cmd", "C=2"]),
+            }
+        );
+        assert!(parse_env_args(&args(&["env", "-u"])).is_err());
+        assert!(parse_env_args(&args(&["env", "-z", "ls"])).is_err());
+        assert_eq!(
+            parse_env_args(&args(&["env"])).unwrap(),
+            EnvRequest::default()
+        );
+    }
+
+    #[test]
+    fn clear_listing_only_has_assignments() {
+        let request = parse_env_args(&args(&["env", "-i", "ONLY=1"])).unwrap();
+        assert_eq!(
+            request.listing(),
+            vec![("ONLY".to_string(), "1".to_string())]
+        );
+    }
+}
@@ -2,7 +2,8 @@ use std::fmt::Write;
 use std::io;
 
 use crate::job_control::{
-    bring_job_foreground, continue_job, find_job, parse_job_id, take_job, JobStatus,
+    bring_job_foreground, continue_job, find_job, resolve_job, signal_status, take_job, touch_job,
+    wait_for_next_job, wait_for_process_group, JobNotices, JobStatus, NextJob, WaitOutcome,
 };
 use crate::ShellState;
 
@@ -11,19 +12,23 @@ pub(crate) fn handle_fg(
     args: &[String],
     _output: &mut String,
 ) -> io::Result<()> {
-    let job_id = parse_job_id(args.get(1))?;
-    let job = match take_job(&mut state.jobs, job_id) {
-        Some(job) => job,
-        None => {
-            eprintln!("fg: no such job");
+    let job_id = match resolve_job(&state.jobs, job_spec(args)) {
+        Ok(job) => job.id,
+        Err(err) => {
+            eprintln!("fg: {err}");
             state.last_status = 1;
             return Ok(());
         }
     };
+    let Some(job) = take_job(&mut state.jobs, job_id) else {
+        return Ok(());
+    };
     match bring_job_foreground(job, &state.fg_pgid, state.shell_pgid) {
         Ok(result) => {
             if let Some(stopped) = result.stopped_job {
+                let id = stopped.id;
                 state.jobs.push(stopped);
+                touch_job(&mut state.jobs, id);
             }
             state.last_status = result.status_code.unwrap_or(0);
         }
@@ -40,22 +45,100 @@ pub(crate) fn handle_bg(
     args: &[String],
     output: &mut String,
 ) -> io::Result<()> {
-    let job_id = parse_job_id(args.get(1))?;
-    let job = match find_job(&mut state.jobs, job_id) {
-        Some(job) => job,
-        None => {
-            eprintln!("bg: no such job");
+    let job_id = match resolve_job(&state.jobs, job_spec(args)) {
+        Ok(job) => job.id,
+        Err(err) => {
+            eprintln!("bg: {err}");
             state.last_status = 1;
             return Ok(());
         }
     };
+    let Some(job) = find_job(&mut state.jobs, job_id) else {
+        return Ok(());
+    };
     if let Err(err) = continue_job(job.pgid) {
         eprintln!("bg: {err}");
         state.last_status = 1;
     } else {
         job.status = JobStatus::Running;
         let _ = writeln!(output, "[{}] Running {}", job.id, job.command);
+        let id = job.id;
+        touch_job(&mut state.jobs, id);
         state.last_status = 0;
     }
     Ok(())
 }
+
+/// `wait [-n] [job...]`: waits for the given jobs, or for all of them, and
+/// returns the last one's status. `-n` returns as soon as any job finishes,
+/// or 127 when there are none.
+pub(crate) fn handle_wait(state: &mut ShellState, args: &[String], output: &mut String) {
+    let mut notices = JobNotices::default();
+    if args.get(1).is_some_and(|arg| arg == "-n") {
+        state.last_status = if state.jobs.is_empty() {
+            127
+        } else {
+            match wait_for_next_job(&mut state.jobs, &mut notices, &state.interrupt_flag) {
+                Some(NextJob::Done(code) | NextJob::Stopped(code)) => code,
+                None if state.interrupt_requested() => signal_status(libc::SIGINT),
+                None => 127,
+            }
+        };
+    } else if args.len() == 1 {
+        // Like bash, a job stopping ends the wait with its 128+signal status.
+        let mut status = 0;
+        while let Some(next) =
+            wait_for_next_job(&mut state.jobs, &mut notices, &state.interrupt_flag)
+        {
+            if let NextJob::Stopped(code) = next {
+                status = code;
+                break;
+            }
+        }
+        state.last_status = if state.interrupt_requested() {
+            signal_status(libc::SIGINT)
+        } else {
+            status
+        };
+    } else {
+        for spec in &args[1..] {
+            state.last_status = wait_for_spec(state, spec);
+        }
+    }
+    if let Some(block) = notices.take() {
+        output.push_str(&block);
+    }
+}
+
+fn wait_for_spec(state: &mut ShellState, spec: &str) -> i32 {
+    let job_id = match resolve_job(&state.jobs, spec) {
+        Ok(job) => job.id,
+        Err(err) => {
+            eprintln!("wait: {err}");
+            return 127;
+        }
+    };
+    let Some(mut job) = take_job(&mut state.jobs, job_id) else {
+        return 127;
+    };
+    match wait_for_process_group(job.pgid, job.count, job.last_pid) {
+        Ok(result) => {
+            let code = result.status_code.unwrap_or(0);
+            if matches!(result.outcome, WaitOutcome::Stopped) {
+                job.status = JobStatus::Stopped(code);
+                state.jobs.push(job);
+                touch_job(&mut state.jobs, job_id);
+            }
+            code
+        }
+        Err(err) => {
+            eprintln!("wait: {err}");
+            1
+        }
+    }
+}
+
+/// The job spec a job builtin was given, defaulting to the current job.
+pub(crate) fn job_spec(args: &[String]) -> &str {
+    args.get(1).map_or("%+", String::as_str)
+}
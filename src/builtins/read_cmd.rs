@@ -0,0 +1,208 @@
+use std::env;
+use std::io::{self, BufRead, BufReader, Read};
+use std::os::fd::AsFd;
+
+use nix::sys::termios::{tcgetattr, tcsetattr, LocalFlags, SetArg};
+use nix::unistd::isatty;
+
+use crate::io_helpers::{decode_input_line, read_input_line};
+use crate::utils::is_valid_var_name;
+use crate::ShellState;
+
+struct ReadOptions<'a> {
+    prompt: Option<&'a str>,
+    silent: bool,
+    names: &'a [String],
+}
+
+/// `read [-rs] [-p prompt] [name...]`: reads one line from stdin. With no
+/// names the line goes to `REPLY` untouched; otherwise it is split on
+/// whitespace and the last name takes whatever fields are left over.
+/// Backslashes are always kept, as with `-r`. Status 1 at end of input.
+///
+/// `stdin` is the command's own redirection or pipeline input; without it
+/// the line comes from an enclosing `done < file`, then from the shell's
+/// own input.
+pub(crate) fn handle_read(
+    state: &mut ShellState,
+    args: &[String],
+    stdin: Option<Box<dyn Read>>,
+) -> io::Result<()> {
+    let options = match parse_read_args(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("read: {err}");
+            eprintln!("usage: read [-rs] [-p prompt] [name...]");
+            state.last_status = 2;
+            return Ok(());
+        }
+    };
+    if let Some(name) = options.names.iter().find(|name| !is_valid_var_name(name)) {
+        eprintln!("read: invalid variable name '{name}'");
+        state.last_status = 2;
+        return Ok(());
+    }
+    if let Some(name) = options
+        .names
+        .iter()
+        .find(|name| state.readonly_vars.contains(name.as_str()))
+    {
+        eprintln!("read: {name}: readonly variable");
+        state.last_status = 1;
+        return Ok(());
+    }
+
+    let prompt = options.prompt.unwrap_or("");
+    let line = if let Some(stdin) = stdin {
+        read_line_from(&mut BufReader::new(stdin))?
+    } else if let Some(input) = state.loop_input.as_mut() {
+        read_line_from(input)?
+    } else if options.silent {
+        read_silently(state, prompt)?
+    } else if state.interactive {
+        read_input_line(&mut state.editor, true, prompt)?
+    } else {
+        // Like bash, only prompt when someone is there to see it.
+        if isatty(libc::STDIN_FILENO).unwrap_or(false) {
+            eprint!("{prompt}");
+        }
+        read_input_line(&mut state.editor, false, prompt)?
+    };
+    let Some(line) = line else {
+        state.last_status = 1;
+        return Ok(());
+    };
+    let line = line.strip_suffix('\n').unwrap_or(&line);
+
+    if options.names.is_empty() {
+        env::set_var("REPLY", line);
+    } else {
+        let fields = split_fields(line, options.names.len());
+        for (name, value) in options.names.iter().zip(fields) {
+            env::set_var(name, value);
+        }
+    }
+    state.last_status = 0;
+    Ok(())
+}
+
+fn parse_read_args(args: &[String]) -> Result<ReadOptions<'_>, String> {
+    let mut options = ReadOptions {
+        prompt: None,
+        silent: false,
+        names: &[],
+    };
+    let mut idx = 1;
+    while let Some(arg) = args.get(idx) {
+        if arg == "--" {
+            idx += 1;
+            break;
+        }
+        let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            break;
+        };
+        idx += 1;
+        for (pos, flag) in flags.char_indices() {
+            match flag {
+                'r' => {}
+                's' => options.silent = true,
+                'p' => {
+                    let rest = &flags[pos + 1..];
+                    options.prompt = if rest.is_empty() {
+                        idx += 1;
+                        Some(args.get(idx - 1).ok_or("-p: option requires an argument")?)
+                    } else {
+                        Some(rest)
+                    };
+                    break;
+                }
+                other => return Err(format!("-{other}: invalid option")),
+            }
+        }
+    }
+    options.names = &args[idx..];
+    Ok(options)
+}
+
+// Redirected input is never a terminal, so there is no prompt to show.
+fn read_line_from(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut buf = Vec::new();
+    if input.read_until(b'\n', &mut buf)? == 0 {
+        return Ok(None);
+    }
+    decode_input_line(buf).map(Some)
+}
+
+/// Reads with terminal echo switched off, for passwords and the like.
+fn read_silently(state: &mut ShellState, prompt: &str) -> io::Result<Option<String>> {
+    let stdin = io::stdin();
+    let saved = tcgetattr(stdin.as_fd()).ok();
+    if let Some(saved) = &saved {
+        let mut quiet = saved.clone();
+        quiet.local_flags.remove(LocalFlags::ECHO);
+        let _ = tcsetattr(stdin.as_fd(), SetArg::TCSANOW, &quiet);
+        eprint!("{prompt}");
+    }
+    let line = read_input_line(&mut state.editor, false, prompt);
+    if let Some(saved) = &saved {
+        let _ = tcsetattr(stdin.as_fd(), SetArg::TCSANOW, saved);
+        // The Enter that ended the line was not echoed either.
+        eprintln!();
+    }
+    line
+}
+
+/// Splits `line` into exactly `count` values: one whitespace-separated
+/// field per name, with the remainder of the line in the last one.
+fn split_fields(line: &str, count: usize) -> Vec<String> {
+    let mut fields = Vec::with_capacity(count);
+    let mut rest = line.trim();
+    while fields.len() + 1 < count {
+        match rest.split_once(char::is_whitespace) {
+            Some((field, tail)) => {
+                fields.push(field.to_string());
+                rest = tail.trim_start();
+            }
+            None => {
+                fields.push(rest.to_string());
+                rest = "";
+            }
+        }
+    }
+    fields.push(rest.to_string());
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_fields_gives_the_last_name_the_rest() {
+        assert_eq!(split_fields("  one  two three ", 1), vec!["one  two three"]);
+        assert_eq!(split_fields("one  two three", 2), vec!["one", "two three"]);
+        assert_eq!(split_fields("one", 3), vec!["one", "", ""]);
+        assert_eq!(split_fields("", 2), vec!["", ""]);
+    }
+
+    #[test]
+    fn read_options_accept_bundled_flags() {
+        let args: Vec<String> = ["read", "-rsp", "Password: ", "pw"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let options = parse_read_args(&args).unwrap();
+        assert!(options.silent);
+        assert_eq!(options.prompt, Some("Password: "));
+        assert_eq!(options.names, ["pw".to_string()]);
+
+        let args: Vec<String> = ["read", "-pName? ", "-x"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert_eq!(
+            parse_read_args(&args).err().as_deref(),
+            Some("-x: invalid option")
+        );
+    }
+}
@@ -1,80 +1,17 @@
-use std::io;
-use std::sync::Arc;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::os::fd::{AsRawFd, OwnedFd};
 
-use crate::expansion::{expand_globs_with, expand_tokens};
-use crate::expansion::GlobOptions;
-use crate::process_subst::{apply_process_subst, FdGuard, ProcessSubstResult};
-use crate::parse::{split_sequence, token_str, SeqOp};
+use crate::job_control::signal_status;
+use crate::parse::{drop_line_break_separators, split_sequence, token_str, SeqOp, SeqSegment};
 use crate::utils::is_valid_var_name;
-use crate::{build_expansion_context, execute_segment, run_return_trap, trace_tokens, ShellState};
+use nix::errno::Errno;
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::unistd::{dup2, fork, pipe, ForkResult, Pid};
+use crate::{execute_segment, expand_segment, run_return_trap, ShellState};
 
 pub(crate) fn execute_script_tokens(state: &mut ShellState, tokens: Vec<String>) -> io::Result<()> {
-    let glob_options = GlobOptions {
-        extglob: state.extglob,
-        nullglob: state.nullglob,
-        failglob: state.failglob,
-        dotglob: state.dotglob,
-        nocaseglob: state.nocaseglob,
-        dirspell: state.dirspell,
-    };
-    let positional = state.current_positional().to_vec();
-    let ctx = build_expansion_context(
-        Arc::clone(&state.fg_pgid),
-        state.trace,
-        state.sandbox.clone(),
-        state.arrays.clone(),
-        state.assoc_arrays.clone(),
-        state.builtin_enabled.clone(),
-        glob_options,
-        &positional,
-        true,
-    );
-    let expanded = match expand_tokens(tokens, &ctx) {
-        Ok(v) => v,
-        Err(msg) => {
-            state.last_status = 2;
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("parse error: {msg}"),
-            ));
-        }
-    };
-    trace_tokens(state, "expanded tokens", &expanded);
-
-    if expanded.is_empty() {
-        return Ok(());
-    }
-
-    let ProcessSubstResult { tokens: expanded, keep_fds } = apply_process_subst(
-        expanded,
-        Arc::clone(&state.fg_pgid),
-        state.trace,
-        state.sandbox.clone(),
-        state.arrays.clone(),
-        state.assoc_arrays.clone(),
-        state.builtin_enabled.clone(),
-        glob_options,
-        true,
-    )?;
-    let _fd_guard = FdGuard(keep_fds);
-
-    let expanded = match expand_globs_with(expanded, glob_options) {
-        Ok(v) => v,
-        Err(msg) => {
-            state.last_status = 2;
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("parse error: {msg}"),
-            ));
-        }
-    };
-    trace_tokens(state, "globbed tokens", &expanded);
-
-    if expanded.is_empty() {
-        return Ok(());
-    }
-
-    let segments = match split_sequence(expanded) {
+    let segments = match split_sequence(drop_line_break_separators(tokens)) {
         Ok(v) => v,
         Err(msg) => {
             state.last_status = 2;
@@ -84,9 +21,14 @@ pub(crate) fn execute_script_tokens(state: &mut ShellState, tokens: Vec<String>)
             ));
         }
     };
+    run_segments(state, segments)
+}
 
+// Runs each segment whose `&&`/`||` condition holds, expanding it only once
+// the segments before it have run.
+fn run_segments(state: &mut ShellState, segments: Vec<SeqSegment>) -> io::Result<()> {
     for segment in segments {
-        if state.return_requested.is_some() {
+        if state.unwinding() {
             break;
         }
         let should_run = match segment.op {
@@ -95,8 +37,11 @@ pub(crate) fn execute_script_tokens(state: &mut ShellState, tokens: Vec<String>)
             SeqOp::Or => state.last_status != 0,
         };
         if should_run {
+            let Some(segment) = expand_segment(state, segment, true)? else {
+                continue;
+            };
             execute_segment(state, segment.tokens, &segment.display)?;
-            if state.return_requested.is_some() {
+            if state.unwinding() {
                 break;
             }
         }
@@ -105,110 +50,58 @@ pub(crate) fn execute_script_tokens(state: &mut ShellState, tokens: Vec<String>)
     Ok(())
 }
 
+/// One active function call, recorded for `caller`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CallFrame {
+    pub(crate) function: String,
+    // Input line the call was made from.
+    pub(crate) line: usize,
+}
+
+// Nesting allowed when FUNCNEST is unset or not a positive number; deep
+// enough for real recursion, shallow enough to stay clear of the Rust stack.
+const DEFAULT_FUNCNEST: usize = 1000;
+
+/// The maximum function call depth from `FUNCNEST`.
+pub(crate) fn function_nest_limit(var: Option<&str>) -> usize {
+    var.and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|limit| *limit > 0)
+        .unwrap_or(DEFAULT_FUNCNEST)
+}
+
 pub(crate) fn execute_function(
     state: &mut ShellState,
+    name: &str,
     func_tokens: Vec<String>,
     args: &[String],
 ) -> io::Result<()> {
+    let limit = function_nest_limit(std::env::var("FUNCNEST").ok().as_deref());
+    if state.call_stack.len() >= limit {
+        // Unwinds every active call back to the top-level command.
+        state.last_status = 1;
+        return Err(io::Error::other(format!(
+            "{name}: maximum function recursion depth exceeded ({limit})"
+        )));
+    }
+    state.call_stack.push(CallFrame {
+        function: name.to_string(),
+        line: state.lineno,
+    });
+    // Loops in the caller are out of reach of `break` in the body.
+    let caller_loop_depth = std::mem::take(&mut state.loop_depth);
     state.push_local_scope();
     state.push_function_scope();
     state.push_positional(args.to_vec());
-    let glob_options = GlobOptions {
-        extglob: state.extglob,
-        nullglob: state.nullglob,
-        failglob: state.failglob,
-        dotglob: state.dotglob,
-        nocaseglob: state.nocaseglob,
-        dirspell: state.dirspell,
-    };
-    let positional = state.current_positional().to_vec();
-    let ctx = build_expansion_context(
-        Arc::clone(&state.fg_pgid),
-        state.trace,
-        state.sandbox.clone(),
-        state.arrays.clone(),
-        state.assoc_arrays.clone(),
-        state.builtin_enabled.clone(),
-        glob_options,
-        &positional,
-        true,
-    );
-    let result = (|| {
-    let expanded = match expand_tokens(func_tokens, &ctx) {
-        Ok(v) => v,
+    let result = match split_sequence(drop_line_break_separators(func_tokens)) {
+        Ok(segments) => run_segments(state, segments),
         Err(msg) => {
             state.last_status = 2;
-            return Err(io::Error::new(
+            Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
-                    format!("parse error: {msg}"),
-                ));
-            }
-        };
-    trace_tokens(state, "function expanded tokens", &expanded);
-
-    if expanded.is_empty() {
-        return Ok(());
-    }
-
-    let ProcessSubstResult { tokens: expanded, keep_fds } = apply_process_subst(
-        expanded,
-        Arc::clone(&state.fg_pgid),
-        state.trace,
-        state.sandbox.clone(),
-        state.arrays.clone(),
-        state.assoc_arrays.clone(),
-        state.builtin_enabled.clone(),
-        glob_options,
-        true,
-    )?;
-    let _fd_guard = FdGuard(keep_fds);
-
-    let expanded = match expand_globs_with(expanded, glob_options) {
-        Ok(v) => v,
-        Err(msg) => {
-            state.last_status = 2;
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    format!("parse error: {msg}"),
-                ));
-            }
-        };
-        trace_tokens(state, "function globbed tokens", &expanded);
-
-        if expanded.is_empty() {
-            return Ok(());
-        }
-
-        let segments = match split_sequence(expanded) {
-            Ok(v) => v,
-            Err(msg) => {
-                state.last_status = 2;
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    format!("parse error: {msg}"),
-                ));
-            }
-        };
-
-        for segment in segments {
-            if state.return_requested.is_some() {
-                break;
-            }
-            let should_run = match segment.op {
-                SeqOp::Always => true,
-                SeqOp::And => state.last_status == 0,
-                SeqOp::Or => state.last_status != 0,
-            };
-            if should_run {
-                execute_segment(state, segment.tokens, &segment.display)?;
-                if state.return_requested.is_some() {
-                    break;
-                }
-            }
+                format!("parse error: {msg}"),
+            ))
         }
-
-        Ok(())
-    })();
+    };
     if let Some(code) = state.return_requested.take() {
         state.last_status = code;
     }
@@ -218,9 +111,96 @@ pub(crate) fn execute_function(
     state.pop_positional();
     state.pop_function_scope();
     state.pop_local_scope();
+    state.call_stack.pop();
+    state.loop_depth = caller_loop_depth;
     result
 }
 
+/// Starts function `name` as the last stage of a pipeline: in a forked copy
+/// of the shell, so variable changes and `cd` stay there. Returns the child
+/// and the write end of its standard input; the function sees end of input
+/// once every copy of that end is closed.
+pub(crate) fn spawn_function_subshell(
+    state: &mut ShellState,
+    name: &str,
+    func_tokens: Vec<String>,
+    args: &[String],
+) -> io::Result<(Pid, OwnedFd)> {
+    let (read_end, write_end) = pipe()?;
+    // SAFETY: the child only runs shell code on its own copy of the state and
+    // leaves through `_exit`, never returning into the parent's call stack.
+    match unsafe { fork() }? {
+        ForkResult::Child => {
+            drop(write_end);
+            let status = match dup2(read_end.as_raw_fd(), libc::STDIN_FILENO) {
+                Ok(_) => {
+                    drop(read_end);
+                    match execute_function(state, name, func_tokens, args) {
+                        Ok(()) => state.last_status,
+                        Err(err) => {
+                            eprintln!("{name}: {err}");
+                            1
+                        }
+                    }
+                }
+                Err(err) => {
+                    eprintln!("{name}: {err}");
+                    1
+                }
+            };
+            let _ = io::stdout().flush();
+            let _ = io::stderr().flush();
+            // SAFETY: `_exit` ends the child without running the parent's
+            // exit handlers a second time.
+            unsafe { libc::_exit(status) }
+        }
+        ForkResult::Parent { child } => Ok((child, write_end)),
+    }
+}
+
+/// Waits for a subshell from `spawn_function_subshell` and returns its
+/// exit status.
+pub(crate) fn wait_for_subshell(child: Pid) -> io::Result<i32> {
+    loop {
+        match waitpid(child, None) {
+            Ok(WaitStatus::Exited(_, code)) => return Ok(code),
+            Ok(WaitStatus::Signaled(_, signal, _)) => return Ok(signal_status(signal as i32)),
+            Ok(_) => continue,
+            Err(Errno::EINTR) => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// `caller [N]`: prints the active call frames, innermost first, or only
+/// frame N. Returns 1 outside a function or when N is past the outermost call.
+pub(crate) fn handle_caller(state: &mut ShellState, args: &[String], output: &mut String) {
+    let frames = match args.get(1) {
+        Some(arg) => match arg.parse::<usize>() {
+            Ok(depth) => frame_at(&state.call_stack, depth).into_iter().collect(),
+            Err(_) => {
+                eprintln!("caller: {arg}: invalid frame number");
+                state.last_status = 2;
+                return;
+            }
+        },
+        None => state.call_stack.iter().rev().collect::<Vec<_>>(),
+    };
+    if frames.is_empty() {
+        state.last_status = 1;
+        return;
+    }
+    for frame in frames {
+        let _ = writeln!(output, "{} {}", frame.line, frame.function);
+    }
+    state.last_status = 0;
+}
+
+// Frame 0 is the function currently running.
+fn frame_at(stack: &[CallFrame], depth: usize) -> Option<&CallFrame> {
+    stack.len().checked_sub(depth + 1).map(|idx| &stack[idx])
+}
+
 pub(crate) fn is_function_def_start(tokens: &[String]) -> bool {
     if tokens.len() >= 2 && tokens[0] == "function" {
         return is_valid_var_name(&tokens[1]);
@@ -284,3 +264,16 @@ fn parse_function_tokens(tokens: Vec<String>) -> io::Result<(String, Vec<String>
     let body = tokens[brace_pos + 1..end_pos].to_vec();
     Ok((name, body))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn funcnest_limit_defaults_for_unset_or_bad_values() {
+        assert_eq!(function_nest_limit(None), DEFAULT_FUNCNEST);
+        assert_eq!(function_nest_limit(Some("0")), DEFAULT_FUNCNEST);
+        assert_eq!(function_nest_limit(Some("deep")), DEFAULT_FUNCNEST);
+        assert_eq!(function_nest_limit(Some(" 25 ")), 25);
+    }
+}
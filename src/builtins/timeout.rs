@@ -0,0 +1,322 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+
+use crate::execution::{
+    build_command, run_command_in_foreground, sandbox_options_for_command, status_from_error,
+    ForegroundResult,
+};
+use crate::job_control::{
+    add_job_with_status, signal_status, status_to_description, JobStatus, WaitOutcome,
+};
+use crate::parse::CommandSpec;
+use crate::ShellState;
+
+// Matches coreutils: 124 when the deadline fires.
+pub(crate) const TIMEOUT_STATUS: i32 = 124;
+const DEFAULT_KILL_AFTER: Duration = Duration::from_secs(5);
+
+/// Parses `5`, `1.5`, `500ms`, `2m`, `1h` or `1d`; a bare number is seconds.
+pub(crate) fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text
+        .find(|ch: char| !(ch.is_ascii_digit() || ch == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let scale = match unit {
+        "" | "s" => 1.0,
+        "ms" => 0.001,
+        "m" => 60.0,
+        "h" => 3600.0,
+        "d" => 86400.0,
+        _ => return Err(format!("invalid duration '{text}'")),
+    };
+    let value = number
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite() && *value >= 0.0)
+        .ok_or_else(|| format!("invalid duration '{text}'"))?;
+    Duration::try_from_secs_f64(value * scale).map_err(|_| format!("invalid duration '{text}'"))
+}
+
+pub(crate) fn handle_timeout(
+    state: &mut ShellState,
+    cmd: &CommandSpec,
+    display: &str,
+) -> io::Result<()> {
+    let args = &cmd.args;
+    let mut idx = 1;
+    let mut kill_after = DEFAULT_KILL_AFTER;
+    if args.get(idx).map(String::as_str) == Some("-k") {
+        let Some(value) = args.get(idx + 1) else {
+            eprintln!("timeout: -k requires a duration");
+            state.last_status = 2;
+            return Ok(());
+        };
+        match parse_duration(value) {
+            Ok(duration) => kill_after = duration,
+            Err(err) => {
+                eprintln!("timeout: {err}");
+                state.last_status = 2;
+                return Ok(());
+            }
+        }
+        idx += 2;
+    }
+    if args.len() < idx + 2 {
+        eprintln!("usage: timeout [-k duration] duration command [args...]");
+        state.last_status = 2;
+        return Ok(());
+    }
+    let limit = match parse_duration(&args[idx]) {
+        Ok(limit) => limit,
+        Err(err) => {
+            eprintln!("timeout: {err}");
+            state.last_status = 2;
+            return Ok(());
+        }
+    };
+
+    let mut inner = cmd.clone();
+    inner.args = args[idx + 1..].to_vec();
+    let mut command = build_command(&inner)?;
+    let sandbox = sandbox_options_for_command(&inner, &state.sandbox, state.trace);
+
+    let watchdog = Watchdog::start(state.fg_pgid.clone(), limit, kill_after, false);
+    let result = run_command_in_foreground(
+        &mut command,
+        &state.fg_pgid,
+        state.shell_pgid,
+        state.trace,
+        sandbox,
+    );
+    let timed_out = watchdog.finish();
+
+    finish_foreground(state, result, timed_out, "timeout", display);
+    Ok(())
+}
+
+/// Records how a foreground command run by `timeout` or `env` ended. One
+/// that stopped goes into the job table even past its deadline, so it can
+/// still be resumed or killed.
+pub(crate) fn finish_foreground(
+    state: &mut ShellState,
+    result: io::Result<ForegroundResult>,
+    timed_out: bool,
+    name: &str,
+    display: &str,
+) {
+    match result {
+        Ok(result) if matches!(result.outcome, WaitOutcome::Stopped) => {
+            let status = result.status_code.unwrap_or(signal_status(libc::SIGTSTP));
+            let job_id = add_job_with_status(
+                &mut state.jobs,
+                &mut state.next_job_id,
+                result.pgid,
+                result.last_pid,
+                1,
+                display,
+                JobStatus::Stopped(status),
+            );
+            println!("[{job_id}] {} {display}", status_to_description(status));
+            state.last_status = status;
+        }
+        Ok(_) if timed_out => state.last_status = TIMEOUT_STATUS,
+        Ok(result) => state.last_status = result.status_code.unwrap_or(0),
+        Err(err) => {
+            eprintln!("{name}: {err}");
+            state.last_status = status_from_error(&err);
+        }
+    }
+}
+
+/// Parses the value of `set -o maxtime=N`; `0` or `off` disables the limit.
+pub(crate) fn parse_maxtime(value: &str) -> Result<Option<Duration>, String> {
+    if value.eq_ignore_ascii_case("off") {
+        return Ok(None);
+    }
+    let limit = parse_duration(value)?;
+    Ok((!limit.is_zero()).then_some(limit))
+}
+
+// How long a prompt function may run before the default prompt is used.
+const DEFAULT_PROMPT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The prompt function limit from `MINISHELL_PROMPT_TIMEOUT`, where `0` or
+/// `off` disables it; an unset or invalid value gives the default.
+pub(crate) fn prompt_timeout(var: Option<&str>) -> Option<Duration> {
+    match var.map(parse_maxtime) {
+        Some(Ok(limit)) => limit,
+        Some(Err(_)) | None => Some(DEFAULT_PROMPT_TIMEOUT),
+    }
+}
+
+/// Runs `capture`, returning `None` if it outlives `limit`. Past the
+/// deadline every foreground group it runs is killed, so the commands left
+/// in a stuck function fail fast instead of holding up the caller.
+pub(crate) fn capture_within<T>(
+    fg_pgid: &Arc<AtomicI32>,
+    limit: Duration,
+    capture: impl FnOnce() -> T,
+) -> Option<T> {
+    let (done_tx, done_rx) = mpsc::channel::<()>();
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let flag = timed_out.clone();
+    let fg_pgid = fg_pgid.clone();
+    let handle = thread::spawn(move || {
+        if done_rx.recv_timeout(limit) != Err(RecvTimeoutError::Timeout) {
+            return;
+        }
+        flag.store(true, Ordering::SeqCst);
+        loop {
+            let pgid = fg_pgid.load(Ordering::SeqCst);
+            if pgid > 0 {
+                let _ = kill(Pid::from_raw(-pgid), Signal::SIGKILL);
+            }
+            if done_rx.recv_timeout(Duration::from_millis(10)) != Err(RecvTimeoutError::Timeout) {
+                return;
+            }
+        }
+    });
+    let result = capture();
+    let _ = done_tx.send(());
+    let _ = handle.join();
+    (!timed_out.load(Ordering::SeqCst)).then_some(result)
+}
+
+/// Arms the global `maxtime` limit, if any, for the next foreground command.
+pub(crate) fn maxtime_watchdog(state: &ShellState) -> Option<Watchdog> {
+    let limit = state.maxtime?;
+    Some(Watchdog::start(
+        state.fg_pgid.clone(),
+        limit,
+        DEFAULT_KILL_AFTER,
+        state.interactive,
+    ))
+}
+
+// Signals whatever group is in the foreground once the deadline passes;
+// `finish` cancels the timer and reports whether the command was killed.
+pub(crate) struct Watchdog {
+    done_tx: mpsc::Sender<()>,
+    handle: thread::JoinHandle<()>,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl Watchdog {
+    pub(crate) fn start(
+        fg_pgid: Arc<AtomicI32>,
+        limit: Duration,
+        kill_after: Duration,
+        warn_only: bool,
+    ) -> Self {
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let flag = timed_out.clone();
+        let handle = thread::spawn(move || {
+            if done_rx.recv_timeout(limit) != Err(RecvTimeoutError::Timeout) {
+                return;
+            }
+            let pgid = fg_pgid.load(Ordering::SeqCst);
+            if pgid <= 0 {
+                return;
+            }
+            // Interactive shells only warn so a long editor or pager session is never lost.
+            if warn_only {
+                eprintln!(
+                    "maxtime: command still running after {}s",
+                    limit.as_secs_f64()
+                );
+            } else {
+                flag.store(true, Ordering::SeqCst);
+                let _ = kill(Pid::from_raw(-pgid), Signal::SIGTERM);
+                if done_rx.recv_timeout(kill_after) == Err(RecvTimeoutError::Timeout) {
+                    let _ = kill(Pid::from_raw(-pgid), Signal::SIGKILL);
+                }
+            }
+        });
+        Self {
+            done_tx,
+            handle,
+            timed_out,
+        }
+    }
+
+    pub(crate) fn finish(self) -> bool {
+        let _ = self.done_tx.send(());
+        let _ = self.handle.join();
+        self.timed_out.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations_with_units() {
+        assert_eq!(parse_duration("5"), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_duration("5s"), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1.5h"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("0.25"), Ok(Duration::from_millis(250)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("ms").is_err());
+        assert!(parse_duration("5x").is_err());
+        assert!(parse_duration("-1").is_err());
+    }
+
+    #[test]
+    fn parses_maxtime_option() {
+        assert_eq!(parse_maxtime("30"), Ok(Some(Duration::from_secs(30))));
+        assert_eq!(parse_maxtime("2m"), Ok(Some(Duration::from_secs(120))));
+        assert_eq!(parse_maxtime("0"), Ok(None));
+        assert_eq!(parse_maxtime("off"), Ok(None));
+        assert!(parse_maxtime("soon").is_err());
+    }
+
+    #[test]
+    fn prompt_timeout_defaults_and_can_be_disabled() {
+        assert_eq!(prompt_timeout(None), Some(DEFAULT_PROMPT_TIMEOUT));
+        assert_eq!(
+            prompt_timeout(Some("500ms")),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(prompt_timeout(Some("off")), None);
+        assert_eq!(prompt_timeout(Some("0")), None);
+        assert_eq!(prompt_timeout(Some("soon")), Some(DEFAULT_PROMPT_TIMEOUT));
+    }
+
+    #[test]
+    fn capture_within_kills_a_capture_that_overruns() {
+        use std::os::unix::process::CommandExt;
+        use std::process::Command;
+        use std::time::Instant;
+
+        let fg_pgid = Arc::new(AtomicI32::new(0));
+        let started = Instant::now();
+        let result = capture_within(&fg_pgid, Duration::from_millis(100), || {
+            let mut child = Command::new("sleep")
+                .arg("5")
+                .process_group(0)
+                .spawn()
+                .unwrap();
+            fg_pgid.store(child.id() as i32, Ordering::SeqCst);
+            let status = child.wait().unwrap();
+            fg_pgid.store(0, Ordering::SeqCst);
+            status
+        });
+        assert!(result.is_none());
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let result = capture_within(&fg_pgid, Duration::from_secs(5), || "prompt> ");
+        assert_eq!(result, Some("prompt> "));
+    }
+}
@@ -7,7 +7,16 @@ pub struct ColorConfig {
     pub prompt_status: String,
     pub prompt_cwd: String,
     pub prompt_git: String,
+    // Symbol color after a successful command; `prompt_symbol_ok` sets it too.
     pub prompt_symbol: String,
+    pub prompt_symbol_err: String,
+    pub prompt_glyph_ok: String,
+    pub prompt_glyph_err: String,
+    // Vi mode indicator shown before the prompt in `MINISHELL_EDITMODE=vi`.
+    pub vi_insert: String,
+    pub vi_normal: String,
+    pub vi_glyph_insert: String,
+    pub vi_glyph_normal: String,
     pub hint: String,
 }
 
@@ -18,6 +27,13 @@ impl Default for ColorConfig {
             prompt_cwd: "cyan".to_string(),
             prompt_git: "yellow".to_string(),
             prompt_symbol: "green".to_string(),
+            prompt_symbol_err: "red".to_string(),
+            prompt_glyph_ok: ">".to_string(),
+            prompt_glyph_err: ">".to_string(),
+            vi_insert: "green".to_string(),
+            vi_normal: "yellow".to_string(),
+            vi_glyph_insert: "[I]".to_string(),
+            vi_glyph_normal: "[N]".to_string(),
             hint: "bright_black".to_string(),
         }
     }
@@ -63,7 +79,14 @@ pub fn apply_color_setting(config: &mut ColorConfig, key: &str, value: &str) ->
         "prompt_status" => config.prompt_status = value.to_string(),
         "prompt_cwd" => config.prompt_cwd = value.to_string(),
         "prompt_git" => config.prompt_git = value.to_string(),
-        "prompt_symbol" => config.prompt_symbol = value.to_string(),
+        "prompt_symbol" | "prompt_symbol_ok" => config.prompt_symbol = value.to_string(),
+        "prompt_symbol_err" => config.prompt_symbol_err = value.to_string(),
+        "prompt_glyph_ok" => config.prompt_glyph_ok = value.to_string(),
+        "prompt_glyph_err" => config.prompt_glyph_err = value.to_string(),
+        "vi_insert" => config.vi_insert = value.to_string(),
+        "vi_normal" => config.vi_normal = value.to_string(),
+        "vi_glyph_insert" => config.vi_glyph_insert = value.to_string(),
+        "vi_glyph_normal" => config.vi_glyph_normal = value.to_string(),
         "hint" => config.hint = value.to_string(),
         _ => return Err(format!("unknown color key '{key}'")),
     }
@@ -76,6 +99,13 @@ pub fn format_color_lines(config: &ColorConfig) -> Vec<String> {
         format!("color.prompt_cwd={}", config.prompt_cwd),
         format!("color.prompt_git={}", config.prompt_git),
         format!("color.prompt_symbol={}", config.prompt_symbol),
+        format!("color.prompt_symbol_err={}", config.prompt_symbol_err),
+        format!("color.prompt_glyph_ok={}", config.prompt_glyph_ok),
+        format!("color.prompt_glyph_err={}", config.prompt_glyph_err),
+        format!("color.vi_insert={}", config.vi_insert),
+        format!("color.vi_normal={}", config.vi_normal),
+        format!("color.vi_glyph_insert={}", config.vi_glyph_insert),
+        format!("color.vi_glyph_normal={}", config.vi_glyph_normal),
         format!("color.hint={}", config.hint),
     ]
 }
@@ -1,132 +1,205 @@
 use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
 use rustyline::completion::{Completer, FilenameCompleter, Pair};
 use rustyline::error::ReadlineError;
 use rustyline::highlight::{Highlighter, MatchingBracketHighlighter};
 use rustyline::hint::Hinter;
-use rustyline::history::SearchDirection;
+use rustyline::history::{DefaultHistory, SearchDirection};
 use rustyline::validate::{MatchingBracketValidator, Validator};
-use rustyline::{Context, Helper};
+use rustyline::{Context, Editor, Event, EventHandler, Helper, KeyEvent};
 
 #[cfg(feature = "tree-sitter")]
-use tree_sitter::Parser;
+use tree_sitter::{InputEdit, Parser, Point, Query, QueryCursor, Tree};
 #[cfg(feature = "tree-sitter")]
 use tree_sitter_bash;
-#[cfg(feature = "tree-sitter")]
-use tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, Highlighter as TSHighlighter};
 
 use std::cell::RefCell;
 
 use crate::colors::{resolve_color, ColorConfig};
-use crate::completions::{completion_candidates, CompletionSet};
+use crate::completions::{completion_candidates, CompletionSet, HistoryArguments};
+use crate::config::shell_quote;
+use crate::prompt::{
+    split_nonprinting, vi_mode_glyph, vi_mode_indicator, ViMode, PROMPT_IGNORE_START,
+};
 
+mod last_arg;
 pub(crate) mod matching;
 mod suggestions;
+mod vi_mode;
 
 pub use suggestions::update_completion_context;
 
 use crate::completion::matching::best_suggestion;
 use crate::completion::suggestions::{
-    command_for_position, complete_from_list, current_token, is_command_position,
+    assignment_at, command_for_position, complete_from_list, current_token, current_token_with,
+    is_command_position, managed_name_candidates,
 };
+
+// Keeps a pathological line from stalling every keystroke; a parse that
+// runs over fails and the line is shown without highlighting.
+#[cfg(feature = "tree-sitter")]
+const HIGHLIGHT_TIMEOUT_MICROS: u64 = 50_000;
+
 pub struct SyntaxHighlighter {
     bracket_highlighter: MatchingBracketHighlighter,
-    ts_highlighter: RefCell<TSHighlighter>,
-    config: HighlightConfiguration,
+    parse_cache: RefCell<ParseCache>,
+    // `None` when the bash grammar or the query could not be loaded.
+    query: Option<Query>,
+}
+
+// The previous line and its tree, so the next keystroke only reparses the
+// part of the line that changed.
+#[cfg(feature = "tree-sitter")]
+struct ParseCache {
+    parser: Parser,
+    line: String,
+    tree: Option<Tree>,
+}
+
+#[cfg(feature = "tree-sitter")]
+impl ParseCache {
+    fn parse(&mut self, line: &str) -> Option<&Tree> {
+        let old_tree = match self.tree.as_mut() {
+            Some(tree) => {
+                tree.edit(&line_edit(&self.line, line));
+                Some(&*tree)
+            }
+            None => None,
+        };
+        let parsed = self.parser.parse(line, old_tree);
+        if parsed.is_none() {
+            // A timed-out parse would otherwise resume on the next call.
+            self.parser.reset();
+        }
+        self.tree = parsed;
+        self.line = line.to_string();
+        self.tree.as_ref()
+    }
+}
+
+/// The edit turning `old` into `new`, as the single changed span left
+/// between their common prefix and suffix.
+#[cfg(feature = "tree-sitter")]
+fn line_edit(old: &str, new: &str) -> InputEdit {
+    let mut start = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(start) || !new.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut suffix = old[start..]
+        .bytes()
+        .rev()
+        .zip(new[start..].bytes().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+    let old_end = old.len() - suffix;
+    let new_end = new.len() - suffix;
+    InputEdit {
+        start_byte: start,
+        old_end_byte: old_end,
+        new_end_byte: new_end,
+        start_position: point_at(old, start),
+        old_end_position: point_at(old, old_end),
+        new_end_position: point_at(new, new_end),
+    }
+}
+
+#[cfg(feature = "tree-sitter")]
+fn point_at(text: &str, byte: usize) -> Point {
+    let before = &text[..byte];
+    match before.rfind('\n') {
+        Some(newline) => Point::new(before.matches('\n').count(), byte - newline - 1),
+        None => Point::new(0, byte),
+    }
+}
+
+#[cfg(feature = "tree-sitter")]
+fn capture_color(name: &str) -> &'static str {
+    match name {
+        "attribute" | "string" | "string.special" => "\x1b[32m",
+        "constant" => "\x1b[34m",
+        "function" | "function.builtin" => "\x1b[35m",
+        "keyword" => "\x1b[31m",
+        "operator" => "\x1b[33m",
+        "property" | "tag" | "type" | "type.builtin" => "\x1b[36m",
+        "punctuation" | "punctuation.bracket" | "punctuation.delimiter" => "\x1b[37m",
+        "variable" | "variable.builtin" | "variable.parameter" => "\x1b[37m",
+        _ => "",
+    }
 }
 
 #[cfg(feature = "tree-sitter")]
 impl SyntaxHighlighter {
     pub fn new() -> Self {
         let highlight_query = "";
-        let mut config =
-            HighlightConfiguration::new(tree_sitter_bash::language(), highlight_query, "", "")
-                .unwrap();
-        config.configure(&[
-            "attribute",
-            "constant",
-            "function.builtin",
-            "function",
-            "keyword",
-            "operator",
-            "property",
-            "punctuation",
-            "punctuation.bracket",
-            "punctuation.delimiter",
-            "string",
-            "string.special",
-            "tag",
-            "type",
-            "type.builtin",
-            "variable",
-            "variable.builtin",
-            "variable.parameter",
-        ]);
+        let mut parser = Parser::new();
+        parser.set_timeout_micros(HIGHLIGHT_TIMEOUT_MICROS);
+        let query = parser
+            .set_language(tree_sitter_bash::language())
+            .ok()
+            .and_then(|_| Query::new(tree_sitter_bash::language(), highlight_query).ok());
         Self {
             bracket_highlighter: MatchingBracketHighlighter::new(),
-            ts_highlighter: RefCell::new(TSHighlighter::new()),
-            config,
+            parse_cache: RefCell::new(ParseCache {
+                parser,
+                line: String::new(),
+                tree: None,
+            }),
+            query,
         }
     }
+
+    // `None` on any highlighting error, so the caller can fall back to the
+    // plain line rather than panic inside the line editor.
+    fn try_highlight(&self, line: &str) -> Option<String> {
+        let query = self.query.as_ref()?;
+        let mut cache = self.parse_cache.try_borrow_mut().ok()?;
+        let tree = cache.parse(line)?;
+        let mut cursor = QueryCursor::new();
+        let mut result = String::new();
+        let mut pos = 0;
+        for (found, idx) in cursor.captures(query, tree.root_node(), line.as_bytes()) {
+            let capture = found.captures[idx];
+            let range = capture.node.byte_range();
+            // Nested captures inside an already colored span keep its color.
+            if range.start < pos {
+                continue;
+            }
+            let name = &query.capture_names()[capture.index as usize];
+            result.push_str(line.get(pos..range.start)?);
+            let text = line.get(range.clone())?;
+            match capture_color(name) {
+                "" => result.push_str(text),
+                color => {
+                    result.push_str(color);
+                    result.push_str(text);
+                    result.push_str("\x1b[0m");
+                }
+            }
+            pos = range.end;
+        }
+        result.push_str(line.get(pos..)?);
+        Some(result)
+    }
 }
 
 #[cfg(feature = "tree-sitter")]
 impl Highlighter for SyntaxHighlighter {
     fn highlight<'l>(&self, line: &'l str, _pos: usize) -> std::borrow::Cow<'l, str> {
-        let mut parser = Parser::new();
-        parser.set_language(tree_sitter_bash::language()).unwrap();
-        let _tree = parser.parse(line, None).unwrap();
-        let highlights = {
-            let mut highlighter = self.ts_highlighter.borrow_mut();
-            highlighter
-                .highlight(&self.config, line.as_bytes(), None, |_| None)
-                .unwrap()
-                .collect::<Vec<_>>()
-        };
-        let mut result = String::new();
-        let mut current_highlight: Option<usize> = None;
-        for event in highlights {
-            match event.unwrap() {
-                HighlightEvent::HighlightStart(s) => {
-                    current_highlight = Some(s.0);
-                }
-                HighlightEvent::HighlightEnd => {
-                    current_highlight = None;
-                }
-                HighlightEvent::Source { start, end } => {
-                    let text = &line[start..end];
-                    if let Some(idx) = current_highlight {
-                        let color = match idx {
-                            0 => "\x1b[32m",  // green for attribute
-                            1 => "\x1b[34m",  // blue for constant
-                            2 => "\x1b[35m",  // magenta for function.builtin
-                            3 => "\x1b[35m",  // magenta for function
-                            4 => "\x1b[31m",  // red for keyword
-                            5 => "\x1b[33m",  // yellow for operator
-                            6 => "\x1b[36m",  // cyan for property
-                            7 => "\x1b[37m",  // white for punctuation
-                            8 => "\x1b[37m",  // white for punctuation.bracket
-                            9 => "\x1b[37m",  // white for punctuation.delimiter
-                            10 => "\x1b[32m", // green for string
-                            11 => "\x1b[32m", // green for string.special
-                            12 => "\x1b[36m", // cyan for tag
-                            13 => "\x1b[36m", // cyan for type
-                            14 => "\x1b[36m", // cyan for type.builtin
-                            15 => "\x1b[37m", // white for variable
-                            16 => "\x1b[37m", // white for variable.builtin
-                            17 => "\x1b[37m", // white for variable.parameter
-                            _ => "",
-                        };
-                        result.push_str(color);
-                        result.push_str(text);
-                        result.push_str("\x1b[0m");
-                    } else {
-                        result.push_str(text);
-                    }
-                }
-            }
+        match self.try_highlight(line) {
+            Some(highlighted) => std::borrow::Cow::Owned(highlighted),
+            None => std::borrow::Cow::Borrowed(line),
         }
-        std::borrow::Cow::Owned(result)
     }
 
     fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
@@ -165,9 +238,18 @@ pub struct LineHelper {
     commands: Vec<String>,
     vars: Vec<String>,
     jobs: Vec<String>,
+    aliases: Vec<String>,
     abbreviations: HashMap<String, Vec<String>>,
     completions: CompletionSet,
-    hint_color: String,
+    // Arguments each command was given in history, newest first.
+    history_args: HistoryArguments,
+    colors: ColorConfig,
+    // Set while vi normal mode is active; `None` outside vi edit mode.
+    vi_normal: Option<Arc<AtomicBool>>,
+    last_arg: Arc<Mutex<last_arg::LastArgState>>,
+    // The current prompt as measured by the editor and as shown, when it
+    // has non-printing spans.
+    shown_prompt: Option<(String, String)>,
 }
 
 impl LineHelper {
@@ -183,9 +265,21 @@ impl LineHelper {
             commands: Vec::new(),
             vars: Vec::new(),
             jobs: Vec::new(),
+            aliases: Vec::new(),
             abbreviations: HashMap::new(),
             completions: CompletionSet::default(),
-            hint_color: ColorConfig::default().hint,
+            history_args: HistoryArguments::default(),
+            colors: ColorConfig::default(),
+            vi_normal: None,
+            last_arg: Arc::default(),
+            shown_prompt: None,
+        }
+    }
+
+    fn shown_prompt<'a>(&'a self, prompt: &'a str) -> &'a str {
+        match &self.shown_prompt {
+            Some((printable, shown)) if printable == prompt => shown,
+            _ => prompt,
         }
     }
 
@@ -203,7 +297,7 @@ impl LineHelper {
         self.jobs = jobs;
         self.abbreviations = abbreviations;
         self.completions = completions;
-        self.hint_color = colors.hint.clone();
+        self.colors = colors.clone();
     }
 
     fn abbreviation_hint(&self, line: &str, pos: usize) -> Option<String> {
@@ -226,6 +320,85 @@ impl LineHelper {
         Some(format!(" -> {expansion_text}"))
     }
 
+    // Filename candidates for the word at `start`. The filename completer
+    // has its own word breaks, so its span is realigned to ours.
+    fn path_pairs(&self, line: &str, start: usize, pos: usize) -> Result<Vec<Pair>, ReadlineError> {
+        let word = &line[start..pos];
+        let (offset, mut pairs) = self.completer.complete_path(word, word.len())?;
+        for pair in &mut pairs {
+            pair.replacement.insert_str(0, &word[..offset]);
+        }
+        Ok(pairs)
+    }
+
+    // Directories only, for `cd` and `pushd`: matches beside the word first,
+    // then, for a relative word, matches under each `CDPATH` entry.
+    fn directory_pairs(
+        &self,
+        line: &str,
+        start: usize,
+        pos: usize,
+    ) -> Result<Vec<Pair>, ReadlineError> {
+        let word = &line[start..pos];
+        let mut pairs = self.path_pairs(line, start, pos)?;
+        pairs.retain(|pair| pair.replacement.ends_with('/'));
+        if !word.starts_with(['/', '.', '~']) {
+            let cdpath = std::env::var("CDPATH").unwrap_or_default();
+            for pair in cdpath_directories(&cdpath, word) {
+                if !pairs
+                    .iter()
+                    .any(|seen| seen.replacement == pair.replacement)
+                {
+                    pairs.push(pair);
+                }
+            }
+        }
+        Ok(pairs)
+    }
+
+    // Values for `NAME=value` at command position: paths for the part after
+    // the last `:`, or the variable's current value when nothing is typed.
+    fn assignment_pairs(
+        &self,
+        line: &str,
+        word_start: usize,
+        value_start: usize,
+        pos: usize,
+    ) -> Result<Vec<Pair>, ReadlineError> {
+        let (start, _) = current_token_with(line, pos, ":");
+        let start = start.max(value_start);
+        let lead = &line[word_start..start];
+        let mut pairs = self.path_pairs(line, start, pos)?;
+        for pair in &mut pairs {
+            pair.replacement.insert_str(0, lead);
+        }
+        if start == pos {
+            let name = &line[word_start..value_start - 1];
+            if let Ok(value) = std::env::var(name) {
+                pairs.insert(
+                    0,
+                    Pair {
+                        replacement: format!("{lead}{}", shell_quote(&value)),
+                        display: value,
+                    },
+                );
+            }
+        }
+        Ok(pairs)
+    }
+
+    // Configured completions for `command`, then the arguments it was given
+    // in earlier history entries, newest first.
+    fn argument_candidates(&self, command: &str) -> Vec<String> {
+        let mut candidates = completion_candidates(&self.completions, command);
+        for previous in self.history_args.for_command(command) {
+            if !candidates.contains(previous) {
+                candidates.push(previous.clone());
+            }
+        }
+        candidates
+    }
+
     fn completion_hint(&self, line: &str, pos: usize) -> Option<String> {
         let (start, token) = current_token(line, pos);
         if token.is_empty() {
@@ -235,7 +408,7 @@ impl LineHelper {
         if is_command_position(line, start) {
             candidates.extend(self.commands.iter().cloned());
         } else if let Some(command) = command_for_position(line, start) {
-            candidates.extend(completion_candidates(&self.completions, &command));
+            candidates.extend(self.argument_candidates(&command));
         }
         if candidates.is_empty() {
             return None;
@@ -318,20 +491,112 @@ impl Completer for LineHelper {
             pairs.extend(self.completer.complete(line, pos, ctx)?.1);
             return Ok((start, pairs));
         }
-        let mut pairs = self.completer.complete(line, pos, ctx)?.1;
+        if let Some((word_start, value_start)) = assignment_at(line, pos) {
+            let pairs = self.assignment_pairs(line, word_start, value_start, pos)?;
+            return Ok((word_start, pairs));
+        }
+        let (quote, bare) = split_open_quote(&token);
+        let names = managed_name_candidates(line, start, &self.aliases, &self.abbreviations);
+        if !names.is_empty() {
+            let pairs = complete_from_list(bare, &names, "");
+            return Ok((start, quote_pairs(pairs, quote)));
+        }
+        if matches!(
+            command_for_position(line, start).as_deref(),
+            Some("cd" | "pushd")
+        ) {
+            return Ok((start, self.directory_pairs(line, start, pos)?));
+        }
+        let mut words = Vec::new();
         if is_command_position(line, start) || !token.contains('/') {
-            pairs.extend(complete_from_list(token.as_str(), &self.commands, ""));
+            words.extend(complete_from_list(bare, &self.commands, ""));
         }
         if let Some(command) = command_for_position(line, start) {
-            let candidates = completion_candidates(&self.completions, &command);
+            let candidates = self.argument_candidates(&command);
             if !candidates.is_empty() {
-                pairs.extend(complete_from_list(token.as_str(), &candidates, ""));
+                words.extend(complete_from_list(bare, &candidates, ""));
             }
         }
+        // The filename completer already escapes its own replacements.
+        let mut pairs = self.path_pairs(line, start, pos)?;
+        pairs.extend(quote_pairs(words, quote));
         Ok((start, pairs))
     }
 }
 
+/// Directories under the `CDPATH` entries whose names continue `word`,
+/// offered relative to the entry the way `cd` resolves them. Empty and `.`
+/// entries are skipped, since the filename completer covers those.
+fn cdpath_directories(cdpath: &str, word: &str) -> Vec<Pair> {
+    let (dir, prefix) = match word.rsplit_once('/') {
+        Some((dir, prefix)) => (Some(dir), prefix),
+        None => (None, word),
+    };
+    let mut pairs = Vec::new();
+    for base in cdpath.split(':').filter(|base| !matches!(*base, "" | ".")) {
+        let parent = dir.map_or_else(|| PathBuf::from(base), |dir| Path::new(base).join(dir));
+        let Ok(entries) = fs::read_dir(parent) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with(prefix) || !entry.path().is_dir() {
+                continue;
+            }
+            let path = match dir {
+                Some(dir) => format!("{dir}/{name}/"),
+                None => format!("{name}/"),
+            };
+            pairs.push(Pair {
+                display: name,
+                replacement: shell_quote(&path),
+            });
+        }
+    }
+    // The same name can turn up under more than one entry.
+    pairs.sort_by(|a, b| a.replacement.cmp(&b.replacement));
+    pairs.dedup_by(|a, b| a.replacement == b.replacement);
+    pairs
+}
+
+// Splits a leading unclosed `'` or `"` off the word being completed.
+fn split_open_quote(token: &str) -> (Option<char>, &str) {
+    match token.chars().next() {
+        Some(quote @ ('\'' | '"')) if !token[1..].contains(quote) => (Some(quote), &token[1..]),
+        _ => (None, token),
+    }
+}
+
+/// Quotes a completion so it stays one word, keeping the quote the user
+/// opened; otherwise falls back to `shell_quote`.
+fn quote_completion(candidate: &str, quote: Option<char>) -> String {
+    match quote {
+        Some('\'') => format!("'{}'", candidate.replace('\'', "'\\''")),
+        Some(_) => {
+            let mut out = String::from("\"");
+            for ch in candidate.chars() {
+                if matches!(ch, '"' | '\\' | '$' | '`') {
+                    out.push('\\');
+                }
+                out.push(ch);
+            }
+            out.push('"');
+            out
+        }
+        None => shell_quote(candidate),
+    }
+}
+
+fn quote_pairs(pairs: Vec<Pair>, quote: Option<char>) -> Vec<Pair> {
+    pairs
+        .into_iter()
+        .map(|pair| Pair {
+            replacement: quote_completion(&pair.replacement, quote),
+            display: pair.display,
+        })
+        .collect()
+}
+
 impl Hinter for LineHelper {
     type Hint = String;
 
@@ -346,6 +611,68 @@ impl Hinter for LineHelper {
     }
 }
 
+/// Binds Alt-. to insert the last argument of earlier commands.
+pub(crate) fn enable_last_argument(editor: &mut Editor<LineHelper, DefaultHistory>) {
+    let Some(state) = editor.helper().map(|helper| Arc::clone(&helper.last_arg)) else {
+        return;
+    };
+    editor.bind_sequence(
+        KeyEvent::alt('.'),
+        EventHandler::Conditional(Box::new(last_arg::LastArgument { state })),
+    );
+}
+
+/// Shows the vi mode in front of every prompt from now on.
+pub(crate) fn enable_vi_mode_indicator(editor: &mut Editor<LineHelper, DefaultHistory>) {
+    let normal = Arc::new(AtomicBool::new(false));
+    editor.bind_sequence(
+        Event::Any,
+        EventHandler::Conditional(Box::new(vi_mode::ViModeTracker {
+            normal: Arc::clone(&normal),
+        })),
+    );
+    if let Some(helper) = editor.helper_mut() {
+        helper.vi_normal = Some(normal);
+    }
+}
+
+/// Gives the editor only the printable text of `prompt` to measure; the
+/// `\001...\002` spans come back in `highlight_prompt`.
+pub(crate) fn measured_prompt(
+    editor: &mut Editor<LineHelper, DefaultHistory>,
+    prompt: String,
+) -> String {
+    let Some(helper) = editor.helper_mut() else {
+        return split_nonprinting(&prompt).1;
+    };
+    if !prompt.contains(PROMPT_IGNORE_START) {
+        helper.shown_prompt = None;
+        return prompt;
+    }
+    let (printable, shown) = split_nonprinting(&prompt);
+    helper.shown_prompt = Some((printable.clone(), shown));
+    printable
+}
+
+/// Prefixes `prompt` with the insert-mode indicator when vi mode is shown,
+/// as every line starts in insert mode. The highlighter swaps in the
+/// current mode's indicator as keys are pressed.
+pub(crate) fn vi_mode_prompt(
+    editor: &Editor<LineHelper, DefaultHistory>,
+    prompt: String,
+) -> String {
+    match editor
+        .helper()
+        .and_then(|helper| Some((helper, helper.vi_normal.as_ref()?)))
+    {
+        Some((helper, normal)) => {
+            normal.store(false, Ordering::Relaxed);
+            format!("{}{prompt}", vi_mode_glyph(&helper.colors, ViMode::Insert))
+        }
+        None => prompt,
+    }
+}
+
 impl Highlighter for LineHelper {
     fn highlight<'l>(&self, line: &'l str, pos: usize) -> std::borrow::Cow<'l, str> {
         self.highlighter.highlight(line, pos)
@@ -356,14 +683,35 @@ impl Highlighter for LineHelper {
         prompt: &'p str,
         default: bool,
     ) -> std::borrow::Cow<'b, str> {
-        self.highlighter.highlight_prompt(prompt, default)
+        let Some(normal) = &self.vi_normal else {
+            return self
+                .highlighter
+                .highlight_prompt(self.shown_prompt(prompt), default);
+        };
+        let plain = vi_mode_glyph(&self.colors, ViMode::Insert);
+        let Some(rest) = prompt.strip_prefix(plain.as_str()) else {
+            return self
+                .highlighter
+                .highlight_prompt(self.shown_prompt(prompt), default);
+        };
+        let mode = if normal.load(Ordering::Relaxed) {
+            ViMode::Normal
+        } else {
+            ViMode::Insert
+        };
+        std::borrow::Cow::Owned(format!(
+            "{}{}",
+            vi_mode_indicator(&self.colors, mode),
+            self.highlighter
+                .highlight_prompt(self.shown_prompt(rest), default)
+        ))
     }
 
     fn highlight_hint<'h>(&self, hint: &'h str) -> std::borrow::Cow<'h, str> {
         if hint.is_empty() {
             return std::borrow::Cow::Borrowed(hint);
         }
-        let color = resolve_color(&self.hint_color);
+        let color = resolve_color(&self.colors.hint);
         if color.is_empty() {
             return std::borrow::Cow::Borrowed(hint);
         }
@@ -391,3 +739,163 @@ impl Validator for LineHelper {
         self.validator.validate(ctx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyline::history::DefaultHistory;
+
+    #[cfg(feature = "tree-sitter")]
+    #[test]
+    fn highlighting_errors_leave_the_line_unchanged() {
+        let highlighter = SyntaxHighlighter::new();
+        let highlighted = highlighter.highlight("echo hi", 0);
+        assert!(matches!(highlighted, std::borrow::Cow::Owned(text) if text == "echo hi"));
+
+        // A parse that overruns its time budget is a highlight error.
+        highlighter
+            .parse_cache
+            .borrow_mut()
+            .parser
+            .set_timeout_micros(1);
+        let line = "echo $(( 1 + ${x:-\"$(printf '%s' a)\"} )) | ".repeat(500);
+        let highlighted = highlighter.highlight(&line, 0);
+        assert!(matches!(highlighted, std::borrow::Cow::Borrowed(text) if text == line));
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    #[test]
+    fn appending_a_character_reparses_incrementally() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+        use tree_sitter::LogType;
+
+        // Counts the characters the lexer reads, which is the work a parse
+        // does outside the subtrees it reuses.
+        let counting_cache = || {
+            let mut parser = Parser::new();
+            parser.set_language(tree_sitter_bash::language()).unwrap();
+            let lexed = Rc::new(Cell::new(0usize));
+            let counter = Rc::clone(&lexed);
+            parser.set_logger(Some(Box::new(move |kind, message: &str| {
+                if kind == LogType::Lex
+                    && (message.starts_with("consume") || message.starts_with("skip"))
+                {
+                    counter.set(counter.get() + 1);
+                }
+            })));
+            let cache = ParseCache {
+                parser,
+                line: String::new(),
+                tree: None,
+            };
+            (cache, lexed)
+        };
+        let line = "grep -n \"$pattern\" ${files[@]} | sort -u > out.txt; ".repeat(300);
+        let typed = format!("{line}e");
+
+        let (mut incremental, incremental_lexed) = counting_cache();
+        incremental.parse(&line).unwrap();
+        incremental_lexed.set(0);
+        incremental.parse(&typed).unwrap();
+
+        let (mut full, full_lexed) = counting_cache();
+        full.parse(&typed).unwrap();
+
+        let edited = incremental.tree.unwrap().root_node().to_sexp();
+        assert_eq!(edited, full.tree.unwrap().root_node().to_sexp());
+        assert!(full_lexed.get() >= line.len());
+        assert!(
+            incremental_lexed.get() * 2 < full_lexed.get(),
+            "incremental lexed {} characters vs full {}",
+            incremental_lexed.get(),
+            full_lexed.get()
+        );
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    #[test]
+    fn line_edit_covers_only_the_changed_span() {
+        let span = |edit: InputEdit| (edit.start_byte, edit.old_end_byte, edit.new_end_byte);
+        assert_eq!(span(line_edit("echo hi", "echo high")), (7, 7, 9));
+        assert_eq!(span(line_edit("ls é x", "ls ü x")), (3, 5, 5));
+        let edit = line_edit("if x\nthen y", "if x\nthen yz");
+        assert_eq!(edit.new_end_position, Point::new(1, 7));
+    }
+
+    #[test]
+    fn completes_the_value_after_an_equals_sign() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("alpha.txt"), "").unwrap();
+        let helper = LineHelper::new();
+        let history = DefaultHistory::new();
+        let line = format!("cmd --file={}/al", dir.path().display());
+        let (start, pairs) = helper
+            .complete(&line, line.len(), &Context::new(&history))
+            .unwrap();
+        assert_eq!(&line[..start], "cmd --file=");
+        let expected = format!("{}/alpha.txt", dir.path().display());
+        assert!(pairs.iter().any(|pair| pair.replacement == expected));
+    }
+
+    #[test]
+    fn assignment_values_complete_with_the_name_kept() {
+        let helper = LineHelper::new();
+        let history = DefaultHistory::new();
+        let ctx = Context::new(&history);
+        let (start, pairs) = helper.complete("FOO=/tm", 7, &ctx).unwrap();
+        assert_eq!(start, 0);
+        assert!(pairs.iter().any(|pair| pair.replacement == "FOO=/tmp/"));
+
+        let (start, pairs) = helper.complete("X=1 P=/usr:/tm", 14, &ctx).unwrap();
+        assert_eq!(start, 4);
+        assert!(pairs.iter().any(|pair| pair.replacement == "P=/usr:/tmp/"));
+    }
+
+    #[test]
+    fn cd_completes_only_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::create_dir(dir.path().join("scripts")).unwrap();
+        std::fs::write(dir.path().join("setup.sh"), "").unwrap();
+        let helper = LineHelper::new();
+        let history = DefaultHistory::new();
+        let ctx = Context::new(&history);
+        let root = dir.path().display();
+        for command in ["cd", "pushd"] {
+            let line = format!("{command} {root}/s");
+            let (_, pairs) = helper.complete(&line, line.len(), &ctx).unwrap();
+            let mut found: Vec<_> = pairs.iter().map(|pair| pair.replacement.clone()).collect();
+            found.sort();
+            let expected = [format!("{root}/scripts/"), format!("{root}/src/")];
+            assert_eq!(found, expected);
+        }
+
+        let cdpath = format!("/nonexistent:.:{root}");
+        let pairs = cdpath_directories(&cdpath, "s");
+        let found: Vec<_> = pairs.iter().map(|pair| pair.replacement.as_str()).collect();
+        assert_eq!(found, ["scripts/", "src/"]);
+        assert!(cdpath_directories(&cdpath, "setup").is_empty());
+    }
+
+    #[test]
+    fn candidates_with_spaces_are_escaped() {
+        assert_eq!(quote_completion("my file.txt", None), "'my file.txt'");
+        assert_eq!(quote_completion("plain", None), "plain");
+        assert_eq!(quote_completion("it's", Some('\'')), "'it'\\''s'");
+        assert_eq!(quote_completion("a \"b\"", Some('"')), "\"a \\\"b\\\"\"");
+        assert_eq!(split_open_quote("'my f"), (Some('\''), "my f"));
+        assert_eq!(split_open_quote("'done'"), (None, "'done'"));
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("my file.txt"), "").unwrap();
+        let helper = LineHelper::new();
+        let history = DefaultHistory::new();
+        let line = format!("cat {}/my", dir.path().display());
+        let (_, pairs) = helper
+            .complete(&line, line.len(), &Context::new(&history))
+            .unwrap();
+        let expected = format!("{}/my\\ file.txt", dir.path().display());
+        assert!(pairs.iter().any(|pair| pair.replacement == expected));
+    }
+}
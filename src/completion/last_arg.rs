@@ -0,0 +1,142 @@
+//! Alt-. inserts the last argument of the previous history entry; pressing
+//! it again swaps that word for the last argument of the entry before.
+
+use std::sync::{Arc, Mutex};
+
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, Movement, RepeatCount};
+
+use crate::config::shell_quote;
+use crate::parse::{
+    parse_line, strip_markers, ESCAPE_MARKER, NOGLOB_MARKER, OPERATOR_TOKEN_MARKER,
+};
+
+#[derive(Default)]
+pub(crate) struct LastArgState {
+    // History entries, oldest first, as of the current prompt.
+    entries: Vec<String>,
+    previous: Option<Insertion>,
+}
+
+// What the previous press left behind, so the next one can tell it follows
+// directly and replace the word instead of inserting another.
+struct Insertion {
+    depth: usize,
+    chars: usize,
+    line: String,
+    pos: usize,
+}
+
+impl LastArgState {
+    /// Starts over from the newest entry; called once per prompt.
+    pub(crate) fn reset(&mut self, entries: Vec<String>) {
+        self.entries = entries;
+        self.previous = None;
+    }
+}
+
+/// Bound to Alt-.; walks back through history one entry per press.
+pub(crate) struct LastArgument {
+    pub(crate) state: Arc<Mutex<LastArgState>>,
+}
+
+impl ConditionalEventHandler for LastArgument {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, ctx: &EventContext<'_>) -> Option<Cmd> {
+        let mut state = self.state.lock().ok()?;
+        let repeated = state
+            .previous
+            .as_ref()
+            .filter(|prev| prev.line == ctx.line() && prev.pos == ctx.pos());
+        let (start, replace) = match repeated {
+            Some(prev) => (prev.depth + 1, prev.chars),
+            None => (0, 0),
+        };
+        let found = state
+            .entries
+            .iter()
+            .rev()
+            .enumerate()
+            .skip(start)
+            .find_map(|(depth, entry)| Some((depth, last_argument(entry)?)));
+        let Some((depth, word)) = found else {
+            return Some(Cmd::Noop);
+        };
+        let chars = word.chars().count();
+        let mut line = ctx.line().to_string();
+        let at = match replace {
+            0 => ctx.pos(),
+            n => line[..ctx.pos()]
+                .char_indices()
+                .rev()
+                .nth(n - 1)
+                .map_or(0, |(idx, _)| idx),
+        };
+        line.replace_range(at..ctx.pos(), &word);
+        state.previous = Some(Insertion {
+            depth,
+            chars,
+            pos: at + word.len(),
+            line,
+        });
+        if replace == 0 {
+            Some(Cmd::Insert(1, word))
+        } else {
+            Some(Cmd::Replace(Movement::BackwardChar(replace), Some(word)))
+        }
+    }
+}
+
+/// The last argument of a history line, ready to insert. Operators and
+/// redirection targets (`> out`, `2>&1`, a heredoc delimiter) are skipped,
+/// so `grep x file > hits` gives `file`; a line with no arguments gives
+/// its command name. Quoted or escaped words are re-quoted, while plain
+/// words such as `$HOME` or `*.rs` are kept as written.
+pub(crate) fn last_argument(line: &str) -> Option<String> {
+    let tokens = parse_line(line).ok()?;
+    let mut last = None;
+    let mut redirect_target = false;
+    for token in &tokens {
+        if let Some(operator) = token.strip_prefix(OPERATOR_TOKEN_MARKER) {
+            redirect_target = operator.contains(['<', '>']);
+            continue;
+        }
+        if !std::mem::take(&mut redirect_target) {
+            last = Some(token);
+        }
+    }
+    let token = last?;
+    let word = strip_markers(token);
+    if token.contains([ESCAPE_MARKER, NOGLOB_MARKER]) {
+        Some(shell_quote(&word))
+    } else {
+        Some(word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_argument_skips_operators_and_redirection_targets() {
+        assert_eq!(last_argument("ls -la src").as_deref(), Some("src"));
+        assert_eq!(
+            last_argument("grep -n TODO src/main.rs > hits.txt 2>&1").as_deref(),
+            Some("src/main.rs")
+        );
+        assert_eq!(last_argument("cat <<EOF").as_deref(), Some("cat"));
+        assert_eq!(last_argument("ls | wc -l >>log").as_deref(), Some("-l"));
+        assert_eq!(last_argument("echo done;").as_deref(), Some("done"));
+    }
+
+    #[test]
+    fn last_argument_requotes_quoted_words() {
+        assert_eq!(
+            last_argument("cp notes.txt \"my docs\"").as_deref(),
+            Some("'my docs'")
+        );
+        assert_eq!(last_argument("rm it\\'s").as_deref(), Some("'it'\\''s'"));
+        assert_eq!(last_argument("echo $HOME").as_deref(), Some("$HOME"));
+        assert_eq!(last_argument("echo \"unterminated"), None);
+        assert_eq!(last_argument(""), None);
+    }
+}
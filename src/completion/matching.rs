@@ -1,4 +1,16 @@
+use std::collections::HashMap;
+
 pub(crate) fn best_suggestion(token: &str, candidates: &[String]) -> Option<String> {
+    best_suggestion_weighted(token, candidates, &HashMap::new())
+}
+
+/// Like `best_suggestion`, but among fuzzy matches at the same distance the
+/// command used most often (per `frequency`) wins. Prefix matches still win.
+pub(crate) fn best_suggestion_weighted(
+    token: &str,
+    candidates: &[String],
+    frequency: &HashMap<String, usize>,
+) -> Option<String> {
     let mut best_prefix: Option<&String> = None;
     for candidate in candidates {
         if candidate.starts_with(token) {
@@ -12,14 +24,15 @@ pub(crate) fn best_suggestion(token: &str, candidates: &[String]) -> Option<Stri
         return Some(candidate.clone());
     }
     let mut best = None;
-    let mut best_dist = usize::MAX;
+    let mut best_rank = (usize::MAX, 0);
     for candidate in candidates {
         if candidate.is_empty() {
             continue;
         }
         let dist = edit_distance(token, candidate, 2);
-        if dist <= 2 && dist < best_dist {
-            best_dist = dist;
+        let uses = frequency.get(candidate).copied().unwrap_or(0);
+        if dist <= 2 && (dist < best_rank.0 || (dist == best_rank.0 && uses > best_rank.1)) {
+            best_rank = (dist, uses);
             best = Some(candidate.clone());
         }
     }
@@ -64,3 +77,43 @@ fn edit_distance(a: &str, b: &str, max: usize) -> usize {
     }
     prev[blen]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn frequency_breaks_ties_between_fuzzy_matches() {
+        let candidates = names(&["grab", "grep", "grp"]);
+        let mut frequency = HashMap::new();
+        assert_eq!(
+            best_suggestion("grap", &candidates).as_deref(),
+            Some("grab")
+        );
+
+        frequency.insert("grep".to_string(), 12);
+        frequency.insert("grab".to_string(), 1);
+        assert_eq!(
+            best_suggestion_weighted("grap", &candidates, &frequency).as_deref(),
+            Some("grep")
+        );
+
+        // A closer match still beats a more frequent one.
+        assert_eq!(
+            best_suggestion_weighted("gerp", &candidates, &frequency).as_deref(),
+            Some("grp")
+        );
+
+        // And a prefix match beats any fuzzy one.
+        let candidates = names(&["cargo", "cat"]);
+        let frequency = HashMap::from([("cat".to_string(), 100)]);
+        assert_eq!(
+            best_suggestion_weighted("carg", &candidates, &frequency).as_deref(),
+            Some("cargo")
+        );
+    }
+}
@@ -6,10 +6,12 @@ use rustyline::completion::Pair;
 use rustyline::history::DefaultHistory;
 use rustyline::Editor;
 
+use crate::builtins::builtin_names;
 use crate::colors::ColorConfig;
 use crate::completions::CompletionSet;
 use crate::job_control::Job;
 use crate::parse::{parse_line_lenient, OPERATOR_TOKEN_MARKER};
+use crate::utils::is_valid_var_name;
 use crate::completion::LineHelper;
 
 pub fn update_completion_context(
@@ -22,9 +24,16 @@ pub fn update_completion_context(
     jobs: &[Job],
 ) {
     let commands = collect_commands(aliases, functions, abbreviations);
+    let mut alias_names: Vec<String> = aliases.keys().cloned().collect();
+    alias_names.sort();
     let vars = env::vars().map(|(k, _)| k).collect();
     let jobs = jobs.iter().map(|job| job.id.to_string()).collect();
+    let history: Vec<String> = editor.history().iter().cloned().collect();
     if let Some(helper) = editor.helper_mut() {
+        helper.history_args.update(&history);
+        if let Ok(mut last_arg) = helper.last_arg.lock() {
+            last_arg.reset(history);
+        }
         helper.update_context(
             commands,
             vars,
@@ -33,6 +42,7 @@ pub fn update_completion_context(
             completions.clone(),
             colors,
         );
+        helper.aliases = alias_names;
     }
 }
 
@@ -42,13 +52,7 @@ fn collect_commands(
     abbreviations: &HashMap<String, Vec<String>>,
 ) -> Vec<String> {
     let mut entries = Vec::new();
-    entries.extend(
-        [
-            "cd", "pwd", "jobs", "fg", "bg", "help", "exit", "set", "abbr", "complete",
-        ]
-        .iter()
-        .map(|s| s.to_string()),
-    );
+    entries.extend(builtin_names().map(str::to_string));
     entries.extend(aliases.keys().cloned());
     entries.extend(functions.keys().cloned());
     entries.extend(abbreviations.keys().cloned());
@@ -68,12 +72,23 @@ fn collect_commands(
     entries
 }
 
+// Extra word boundaries when COMP_WORDBREAKS is unset, so `--opt=value` and
+// `PATH=a:b` complete only the part after the separator.
+const DEFAULT_WORD_BREAKS: &str = "=:";
+
 pub(crate) fn current_token(line: &str, pos: usize) -> (usize, String) {
+    let breaks = env::var("COMP_WORDBREAKS").unwrap_or_else(|_| DEFAULT_WORD_BREAKS.to_string());
+    current_token_with(line, pos, &breaks)
+}
+
+/// The word ending at `pos`: it starts after whitespace, an operator, or any
+/// character in `breaks`.
+pub(crate) fn current_token_with(line: &str, pos: usize, breaks: &str) -> (usize, String) {
     let mut start = pos;
     let bytes = line.as_bytes();
     while start > 0 {
         let ch = bytes[start - 1] as char;
-        if ch.is_whitespace() || is_operator_char(ch) {
+        if ch.is_whitespace() || is_operator_char(ch) || breaks.contains(ch) {
             break;
         }
         start -= 1;
@@ -81,6 +96,26 @@ pub(crate) fn current_token(line: &str, pos: usize) -> (usize, String) {
     (start, line[start..pos].to_string())
 }
 
+/// For a `NAME=value` word in command position (possibly after other
+/// assignments), the offsets where the word and its value start.
+pub(crate) fn assignment_at(line: &str, pos: usize) -> Option<(usize, usize)> {
+    let (word_start, word) = current_token_with(line, pos, "");
+    let (name, _) = word.split_once('=')?;
+    if !is_valid_var_name(name) {
+        return None;
+    }
+    let command = line[..word_start]
+        .rsplit(is_operator_char)
+        .next()
+        .unwrap_or_default();
+    let after_assignments = command.split_whitespace().all(|earlier| {
+        earlier
+            .split_once('=')
+            .is_some_and(|(name, _)| is_valid_var_name(name))
+    });
+    after_assignments.then_some((word_start, word_start + name.len() + 1))
+}
+
 fn is_operator_char(ch: char) -> bool {
     matches!(ch, '|' | '&' | ';' | '(' | ')' | '{' | '}')
 }
@@ -132,7 +167,82 @@ pub(crate) fn command_for_position(line: &str, pos: usize) -> Option<String> {
     command
 }
 
+/// Existing names offered after `unalias` and `abbr -e`/`abbr --erase`.
+pub(crate) fn managed_name_candidates(
+    line: &str,
+    start: usize,
+    aliases: &[String],
+    abbreviations: &HashMap<String, Vec<String>>,
+) -> Vec<String> {
+    let Some(command) = command_for_position(line, start) else {
+        return Vec::new();
+    };
+    let previous = line[..start].split_whitespace().last();
+    match command.as_str() {
+        "unalias" => aliases.to_vec(),
+        "abbr" if matches!(previous, Some("-e" | "--erase")) => {
+            let mut names: Vec<String> = abbreviations.keys().cloned().collect();
+            names.sort();
+            names
+        }
+        _ => Vec::new(),
+    }
+}
+
 fn is_command_delimiter(token: &str) -> bool {
     let op = token.trim_start_matches(OPERATOR_TOKEN_MARKER);
     matches!(op, "|" | "||" | "&&" | ";" | "&")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assignments_are_found_in_command_position() {
+        assert_eq!(assignment_at("FOO=/tm", 7), Some((0, 4)));
+        assert_eq!(assignment_at("A=1 PATH=/bin:/us", 17), Some((4, 9)));
+        assert_eq!(assignment_at("ls; X=", 6), Some((4, 6)));
+        assert_eq!(assignment_at("echo FOO=/tm", 12), None);
+        assert_eq!(assignment_at("1X=/tm", 6), None);
+    }
+
+    #[test]
+    fn word_breaks_split_option_values() {
+        let line = "cmd --file=/tm";
+        assert_eq!(
+            current_token_with(line, line.len(), "=:"),
+            (11, "/tm".to_string())
+        );
+        let line = "export PATH=/bin:/us";
+        assert_eq!(
+            current_token_with(line, line.len(), "=:"),
+            (17, "/us".to_string())
+        );
+        assert_eq!(
+            current_token_with(line, line.len(), ""),
+            (7, "PATH=/bin:/us".to_string())
+        );
+    }
+
+    #[test]
+    fn abbr_erase_offers_abbreviation_names() {
+        let mut abbreviations = HashMap::new();
+        abbreviations.insert("gs".to_string(), vec!["git status".to_string()]);
+        abbreviations.insert("gc".to_string(), vec!["git commit".to_string()]);
+        let aliases = vec!["ll".to_string()];
+
+        let line = "abbr -e ";
+        assert_eq!(
+            managed_name_candidates(line, line.len(), &aliases, &abbreviations),
+            vec!["gc".to_string(), "gs".to_string()]
+        );
+        let line = "unalias ";
+        assert_eq!(
+            managed_name_candidates(line, line.len(), &aliases, &abbreviations),
+            aliases
+        );
+        let line = "abbr ";
+        assert!(managed_name_candidates(line, line.len(), &aliases, &abbreviations).is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+//! Follows rustyline's vi input mode so the prompt can show it. Rustyline
+//! only reports the mode to key handlers, and before the key is applied, so
+//! the tracker predicts the mode each key leaves behind.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use rustyline::{
+    Cmd, ConditionalEventHandler, Event, EventContext, InputMode, KeyCode, KeyEvent, Modifiers,
+    RepeatCount,
+};
+
+use crate::prompt::ViMode;
+
+/// Bound to every key in vi mode; records the mode and lets the key through.
+pub(crate) struct ViModeTracker {
+    pub(crate) normal: Arc<AtomicBool>,
+}
+
+impl ConditionalEventHandler for ViModeTracker {
+    fn handle(&self, evt: &Event, _: RepeatCount, _: bool, ctx: &EventContext<'_>) -> Option<Cmd> {
+        if let Some(key) = evt.get(0) {
+            let mode = next_vi_mode(ctx.input_mode(), *key);
+            self.normal.store(mode == ViMode::Normal, Ordering::Relaxed);
+        }
+        None
+    }
+}
+
+/// The mode after `key` is pressed in `mode`: Esc leaves insert mode, and
+/// the commands that start inserting text (`i`, `a`, `o`, `c`, `s`, `R` and
+/// their capitals) leave normal mode.
+pub(crate) fn next_vi_mode(mode: InputMode, key: KeyEvent) -> ViMode {
+    match (mode, key) {
+        (InputMode::Insert | InputMode::Replace, KeyEvent(KeyCode::Esc, _)) => ViMode::Normal,
+        (InputMode::Insert | InputMode::Replace, _) => ViMode::Insert,
+        (InputMode::Command, KeyEvent(KeyCode::Char(ch), Modifiers::NONE))
+            if "iIaAoOcCsSR".contains(ch) =>
+        {
+            ViMode::Insert
+        }
+        (InputMode::Command, _) => ViMode::Normal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_switch_between_insert_and_normal() {
+        let esc = KeyEvent(KeyCode::Esc, Modifiers::NONE);
+        let key = |ch| KeyEvent(KeyCode::Char(ch), Modifiers::NONE);
+        assert_eq!(next_vi_mode(InputMode::Insert, esc), ViMode::Normal);
+        assert_eq!(next_vi_mode(InputMode::Insert, key('x')), ViMode::Insert);
+        assert_eq!(next_vi_mode(InputMode::Command, key('A')), ViMode::Insert);
+        assert_eq!(next_vi_mode(InputMode::Command, key('w')), ViMode::Normal);
+        assert_eq!(next_vi_mode(InputMode::Replace, esc), ViMode::Normal);
+    }
+}
@@ -4,8 +4,8 @@ use std::fs;
 use std::io;
 use std::process::Command;
 
-use crate::completion::matching::best_suggestion;
-use crate::parse::parse_line;
+use crate::completion::matching::best_suggestion_weighted;
+use crate::parse::{parse_line, strip_markers, OPERATOR_TOKEN_MARKER};
 
 const BUILTIN_COMMANDS: &[&str] = &[
     "cd",
@@ -194,6 +194,101 @@ pub fn apply_completion_tokens(tokens: &[String], set: &mut CompletionSet) -> Re
     Ok(())
 }
 
+#[derive(Clone, Debug, Default)]
+pub struct BashImport {
+    pub imported: usize,
+    pub skipped: Vec<String>,
+}
+
+pub fn load_bash_completion_file(path: &str, set: &mut CompletionSet) -> io::Result<BashImport> {
+    let content = fs::read_to_string(path)?;
+    Ok(import_bash_completions(&content, set))
+}
+
+pub fn import_bash_completions(content: &str, set: &mut CompletionSet) -> BashImport {
+    // Only static `-W` word lists are imported; anything needing a bash
+    // interpreter (-F functions, -C commands, $(...) word lists) is skipped.
+    let mut result = BashImport::default();
+    let mut pending = String::new();
+    for raw in content.lines() {
+        if let Some(prefix) = raw.strip_suffix('\\') {
+            pending.push_str(prefix);
+            pending.push(' ');
+            continue;
+        }
+        pending.push_str(raw);
+        let line = std::mem::take(&mut pending);
+        let line = line.trim();
+        if !line.starts_with("complete ") {
+            continue;
+        }
+        let Ok(tokens) = parse_line(line) else {
+            result.skipped.push(format!("unparsable line: {line}"));
+            continue;
+        };
+        let tokens = tokens
+            .iter()
+            .map(|token| strip_markers(token))
+            .collect::<Vec<_>>();
+        match parse_bash_complete(&tokens) {
+            Ok((words, names)) => {
+                for name in names {
+                    set.add_static(&name, words.clone());
+                    result.imported += 1;
+                }
+            }
+            Err(reason) => result.skipped.push(reason),
+        }
+    }
+    result
+}
+
+fn parse_bash_complete(tokens: &[String]) -> Result<(Vec<String>, Vec<String>), String> {
+    let mut words: Option<String> = None;
+    let mut function: Option<String> = None;
+    let mut names = Vec::new();
+    let mut i = 1;
+    while i < tokens.len() {
+        let token = tokens[i].as_str();
+        match token {
+            "-W" => {
+                i += 1;
+                words = tokens.get(i).cloned();
+            }
+            "-F" | "-C" => {
+                i += 1;
+                function = Some(tokens.get(i).cloned().unwrap_or_default());
+            }
+            "-o" | "-A" | "-G" | "-X" | "-P" | "-S" => {
+                i += 1;
+            }
+            _ if token.starts_with('-') => {}
+            _ => names.push(token.to_string()),
+        }
+        i += 1;
+    }
+    let label = names.join(" ");
+    if names.is_empty() {
+        return Err("complete line without command names".to_string());
+    }
+    if let Some(function) = function {
+        return Err(format!(
+            "function-based completion '{function}' for '{label}' not supported"
+        ));
+    }
+    let Some(words) = words else {
+        return Err(format!("no -W word list for '{label}'"));
+    };
+    if words.contains('$') || words.contains('`') {
+        return Err(format!("dynamic word list for '{label}' not supported"));
+    }
+    let words = words
+        .split_whitespace()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+    Ok((words, names))
+}
+
 pub fn format_completion_lines(set: &CompletionSet) -> Vec<String> {
     let mut out = Vec::new();
     let mut entries: Vec<_> = set.entries.iter().collect();
@@ -261,12 +356,108 @@ pub fn completion_candidates(set: &CompletionSet, command: &str) -> Vec<String>
     out
 }
 
+/// Arguments kept per command by [`HistoryArguments`]; older ones drop off.
+pub const HISTORY_ARGUMENTS_PER_COMMAND: usize = 50;
+
+/// Non-flag arguments each command was given in history, so `ssh <tab>` can
+/// offer hosts used before. Values are kept newest first, without
+/// duplicates, and capped at `HISTORY_ARGUMENTS_PER_COMMAND`. Redirection
+/// targets and the value after a short option (`-p 2222`) are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct HistoryArguments {
+    by_command: HashMap<String, Vec<String>>,
+    // The newest entry read so far; the next update starts after it.
+    last_entry: Option<String>,
+}
+
+impl HistoryArguments {
+    /// Reads the entries of `history` (oldest first) added since the last
+    /// update. When the last entry read is gone, as after `history -c`,
+    /// everything is read again.
+    pub fn update(&mut self, history: &[String]) {
+        let start = match &self.last_entry {
+            None => 0,
+            Some(last) => match history.iter().rposition(|entry| entry == last) {
+                Some(idx) => idx + 1,
+                None => {
+                    self.by_command.clear();
+                    0
+                }
+            },
+        };
+        for entry in &history[start..] {
+            self.add_entry(entry);
+        }
+        self.last_entry = history.last().cloned();
+    }
+
+    pub fn for_command(&self, command: &str) -> &[String] {
+        self.by_command.get(command).map_or(&[], Vec::as_slice)
+    }
+
+    fn add_entry(&mut self, entry: &str) {
+        let Ok(tokens) = parse_line(entry) else {
+            return;
+        };
+        let mut found = Vec::new();
+        let mut command: Option<String> = None;
+        let mut skip_next = false;
+        for token in &tokens {
+            if let Some(operator) = token.strip_prefix(OPERATOR_TOKEN_MARKER) {
+                skip_next = operator.contains(['<', '>']);
+                if !skip_next {
+                    command = None;
+                }
+                continue;
+            }
+            if std::mem::take(&mut skip_next) {
+                continue;
+            }
+            let word = strip_markers(token);
+            let Some(name) = &command else {
+                command = Some(word);
+                continue;
+            };
+            if word.starts_with('-') {
+                // `-p 2222`: the word after a lone short option is its value.
+                skip_next = word.len() == 2 && word != "--";
+                continue;
+            }
+            if !word.is_empty() {
+                found.push((name.clone(), word));
+            }
+        }
+        // Front insertion reversed keeps each entry's arguments in order.
+        for (name, word) in found.into_iter().rev() {
+            let seen = self.by_command.entry(name).or_default();
+            seen.retain(|previous| *previous != word);
+            seen.insert(0, word);
+            seen.truncate(HISTORY_ARGUMENTS_PER_COMMAND);
+        }
+    }
+}
+
+/// How often each command name starts a history entry, for ranking
+/// "did you mean" suggestions.
+pub fn history_command_counts<'a>(
+    entries: impl IntoIterator<Item = &'a String>,
+) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for entry in entries {
+        if let Some(command) = entry.split_whitespace().next() {
+            *counts.entry(command.to_string()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
 pub fn suggest_command(
     name: &str,
     aliases: &HashMap<String, Vec<String>>,
     functions: &HashMap<String, Vec<String>>,
     abbreviations: &HashMap<String, Vec<String>>,
     completions: &CompletionSet,
+    frequency: &HashMap<String, usize>,
 ) -> Option<String> {
     let mut candidates = Vec::new();
     candidates.extend(BUILTIN_COMMANDS.iter().map(|s| s.to_string()));
@@ -287,7 +478,7 @@ pub fn suggest_command(
     }
     candidates.sort();
     candidates.dedup();
-    best_suggestion(name, &candidates)
+    best_suggestion_weighted(name, &candidates, frequency)
 }
 
 fn shell_quote(token: &str) -> String {
@@ -334,3 +525,77 @@ fn is_completion_flag(token: &str) -> bool {
         "-c" | "--command" | "-a" | "--arguments" | "-x" | "--dynamic" | "-r" | "--remove"
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bash_word_list_imports_static_items() {
+        let mut set = CompletionSet::default();
+        let result = import_bash_completions("complete -W 'a b c' cmd\n", &mut set);
+        assert_eq!(result.imported, 1);
+        assert!(result.skipped.is_empty());
+        assert_eq!(
+            set.entries.get("cmd").map(|spec| spec.static_items.clone()),
+            Some(vec!["a".to_string(), "b".to_string(), "c".to_string()])
+        );
+    }
+
+    #[test]
+    fn history_arguments_are_remembered_per_command() {
+        let history: Vec<String> = [
+            "ssh host1",
+            "ssh -p 2222 host2 > log.txt",
+            "git checkout main && ssh host1; ls 'my dir'",
+            "ssh",
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+        let mut arguments = HistoryArguments::default();
+        arguments.update(&history);
+        assert_eq!(arguments.for_command("ssh"), ["host1", "host2"]);
+        assert_eq!(arguments.for_command("git"), ["checkout", "main"]);
+        assert_eq!(arguments.for_command("ls"), ["my dir"]);
+        assert!(arguments.for_command("log.txt").is_empty());
+
+        let many: Vec<String> = (0..HISTORY_ARGUMENTS_PER_COMMAND + 10)
+            .map(|idx| format!("ping host{idx}"))
+            .collect();
+        let mut arguments = HistoryArguments::default();
+        arguments.update(&many);
+        let ping = arguments.for_command("ping");
+        assert_eq!(ping.len(), HISTORY_ARGUMENTS_PER_COMMAND);
+        assert_eq!(ping[0], format!("host{}", many.len() - 1));
+    }
+
+    #[test]
+    fn history_arguments_read_only_new_entries() {
+        let mut history = vec!["ssh host1".to_string(), "ssh host2".to_string()];
+        let mut arguments = HistoryArguments::default();
+        arguments.update(&history);
+        assert_eq!(arguments.for_command("ssh"), ["host2", "host1"]);
+
+        // A full history drops its oldest entry as a new one arrives.
+        history.remove(0);
+        history.push("ssh host1".to_string());
+        arguments.update(&history);
+        assert_eq!(arguments.for_command("ssh"), ["host1", "host2"]);
+        arguments.update(&history);
+        assert_eq!(arguments.for_command("ssh"), ["host1", "host2"]);
+
+        arguments.update(&["ssh host3".to_string()]);
+        assert_eq!(arguments.for_command("ssh"), ["host3"]);
+    }
+
+    #[test]
+    fn bash_function_completion_is_skipped() {
+        let mut set = CompletionSet::default();
+        let content = "_foo() { :; }\ncomplete -o default -F _foo foo\n";
+        let result = import_bash_completions(content, &mut set);
+        assert_eq!(result.imported, 0);
+        assert_eq!(result.skipped.len(), 1);
+        assert!(set.entries.is_empty());
+    }
+}
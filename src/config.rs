@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 mod parser;
 pub mod sandbox;
@@ -12,6 +12,63 @@ use crate::parse::OPERATOR_TOKEN_MARKER;
 use crate::prompt::{render_prompt_template, render_prompt_theme, PromptTheme};
 
 pub use parser::load_config;
+pub(crate) use parser::shell_quote;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StartupFiles {
+    pub rc: bool,
+    pub profiles: Vec<PathBuf>,
+    pub logout: Option<PathBuf>,
+}
+
+pub fn startup_files(home: Option<&Path>, login: bool, interactive: bool) -> StartupFiles {
+    // Non-login shells only read ~/.minishellrc (aliases, prompt, sandbox settings).
+    // Login shells source profiles before the first prompt and a logout file on exit;
+    // a non-interactive login shell skips the rc like `bash -l` skips bashrc.
+    let home = match home {
+        Some(home) if login => home,
+        _ => {
+            return StartupFiles {
+                rc: true,
+                ..StartupFiles::default()
+            }
+        }
+    };
+    StartupFiles {
+        rc: interactive,
+        profiles: vec![home.join(".profile"), home.join(".minishell_profile")],
+        logout: Some(home.join(".minishell_logout")),
+    }
+}
+
+/// Directory of drop-in fragments sourced after `~/.minishellrc`.
+pub fn conf_d_dir() -> Option<PathBuf> {
+    let base = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var("HOME").ok()?).join(".config"),
+    };
+    Some(base.join("better_shell").join("conf.d"))
+}
+
+/// Lists `*.sh`/`*.customsh` files in `dir`, sorted by file name.
+pub fn conf_d_fragments(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut fragments = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && matches!(
+                    path.extension().and_then(|ext| ext.to_str()),
+                    Some("sh") | Some("customsh")
+                )
+        })
+        .collect::<Vec<_>>();
+    fragments.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    fragments
+}
 
 pub fn build_prompt(
     interactive: bool,
@@ -111,3 +168,56 @@ pub fn format_abbreviation_line(name: &str, tokens: &[String]) -> String {
     out.push_str(&value);
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn startup_files_depend_on_login_and_interactive() {
+        let home = Path::new("/home/user");
+
+        let plain = startup_files(Some(home), false, true);
+        assert!(plain.rc);
+        assert!(plain.profiles.is_empty());
+        assert_eq!(plain.logout, None);
+        assert_eq!(startup_files(Some(home), false, false), plain);
+
+        let login = startup_files(Some(home), true, true);
+        assert!(login.rc);
+        assert_eq!(
+            login.profiles,
+            vec![home.join(".profile"), home.join(".minishell_profile")]
+        );
+        assert_eq!(login.logout, Some(home.join(".minishell_logout")));
+
+        let login_script = startup_files(Some(home), true, false);
+        assert!(!login_script.rc);
+        assert_eq!(login_script.profiles, login.profiles);
+
+        let no_home = startup_files(None, true, true);
+        assert!(no_home.profiles.is_empty());
+        assert_eq!(no_home.logout, None);
+    }
+
+    #[test]
+    fn conf_d_fragments_are_filtered_and_sorted() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "20-git.sh",
+            "10-path.customsh",
+            "README.md",
+            "05-env.sh",
+            "99-old.sh.bak",
+        ] {
+            fs::write(dir.path().join(name), "true\n").unwrap();
+        }
+        fs::create_dir(dir.path().join("30-dir.sh")).unwrap();
+        let names = conf_d_fragments(dir.path())
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["05-env.sh", "10-path.customsh", "20-git.sh"]);
+        assert!(conf_d_fragments(&dir.path().join("missing")).is_empty());
+    }
+}
@@ -4,7 +4,7 @@ use std::fs;
 use std::io;
 
 use crate::colors::{load_color_lines, ColorConfig};
-use crate::execution::{apply_sandbox_directive, SandboxConfig};
+use crate::execution::{apply_sandbox_directive, apply_sandbox_limit, SandboxConfig};
 use crate::parse::{parse_line, parse_sandbox_value};
 use crate::prompt::{parse_prompt_theme, PromptTheme};
 use crate::utils::is_valid_var_name;
@@ -87,6 +87,18 @@ pub fn load_config(
                 }
                 continue;
             }
+            if let Some(setting) = key.strip_prefix("sandbox.") {
+                match setting {
+                    "allow" => sandbox.allow = split_command_list(value),
+                    "deny" => sandbox.deny = split_command_list(value),
+                    limit => {
+                        if let Err(err) = apply_sandbox_limit(&mut sandbox.limits, limit, value) {
+                            eprintln!("config:{}: {err}", idx + 1);
+                        }
+                    }
+                }
+                continue;
+            }
             if let Err(err) = parse_assignment(line, idx + 1) {
                 eprintln!("config:{}: {err}", idx + 1);
             }
@@ -180,6 +192,15 @@ fn parse_abbreviation(
     Ok(())
 }
 
+// Accepts `a b c` or `a,b,c` so sandbox lists read naturally either way.
+fn split_command_list(value: &str) -> Vec<String> {
+    value
+        .split(|ch: char| ch == ',' || ch.is_whitespace())
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
 fn strip_quotes(input: &str) -> &str {
     let bytes = input.as_bytes();
     if bytes.len() >= 2 {
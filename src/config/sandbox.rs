@@ -1,6 +1,6 @@
 use std::env;
 
-use crate::execution::SandboxConfig;
+use crate::execution::{apply_sandbox_limit, SandboxConfig};
 use crate::parse::parse_line;
 
 pub(crate) fn apply_sandbox_env(sandbox: &mut SandboxConfig) {
@@ -25,4 +25,15 @@ pub(crate) fn apply_sandbox_env(sandbox: &mut SandboxConfig) {
             }
         }
     }
+    for (var, key) in [
+        ("MINISHELL_SANDBOX_CPU", "cpu"),
+        ("MINISHELL_SANDBOX_AS", "as"),
+        ("MINISHELL_SANDBOX_NOFILE", "nofile"),
+    ] {
+        if let Ok(value) = env::var(var) {
+            if let Err(err) = apply_sandbox_limit(&mut sandbox.limits, key, &value) {
+                eprintln!("config error: invalid {var}: {err}");
+            }
+        }
+    }
 }
@@ -1,7 +1,8 @@
 use std::fs;
 use std::io::{self, Cursor, Read, Write};
+use std::os::fd::OwnedFd;
 use std::os::unix::process::ExitStatusExt;
-use std::process::{Command, Stdio};
+use std::process::{Child, ChildStdout, Command, Stdio};
 use std::sync::{
     atomic::{AtomicI32, Ordering},
     Arc,
@@ -10,8 +11,9 @@ use std::sync::{
 use log::debug;
 
 use crate::job_control::{
-    set_process_group_explicit, wait_for_process_group, SignalMaskGuard, TerminalGuard,
-    TermiosGuard, WaitOutcome, WaitResult,
+    audit_job_event, set_process_group_explicit, signal_name, signal_status, status_signal,
+    wait_for_process_group, JobEvent, JobEventKind, SignalMaskGuard, TerminalGuard, TermiosGuard,
+    WaitOutcome, WaitResult,
 };
 use crate::parse::CommandSpec;
 
@@ -19,7 +21,10 @@ mod redirection;
 mod sandbox;
 mod spawning;
 
-pub use sandbox::{apply_sandbox_directive, sandbox_options_for_command, SandboxConfig};
+pub use sandbox::{
+    apply_sandbox_directive, apply_sandbox_limit, format_sandbox_status,
+    sandbox_options_for_command, SandboxConfig,
+};
 pub use spawning::{
     build_command, run_command_in_foreground, spawn_command_background, spawn_command_sandboxed,
     spawn_pipeline_background, spawn_pipeline_sandboxed, wrap_spawn_error,
@@ -27,8 +32,8 @@ pub use spawning::{
 
 use sandbox::apply_sandbox;
 use redirection::{
-    apply_input_redirection, apply_stderr_redirection, apply_stdout_redirection, heredoc_stdin,
-    input_redirection_count,
+    apply_input_redirection, apply_stderr_redirection, apply_stdout_redirection,
+    child_stdout_redirected, heredoc_stdin, input_redirection_count, open_redirect_target,
 };
 use spawning::build_pipeline_command;
 
@@ -96,6 +101,10 @@ pub fn run_pipeline_capture(
             child.id(),
             pgid.unwrap_or(child.id() as i32)
         );
+        audit_job_event(
+            JobEvent::new(JobEventKind::Spawn, pgid.unwrap_or(child.id() as i32))
+                .pid(child.id() as i32),
+        );
         if pgid.is_none() {
             let id = child.id() as i32;
             pgid = Some(id);
@@ -175,6 +184,10 @@ pub fn run_pipeline(
             child.id(),
             pgid.unwrap_or(child.id() as i32)
         );
+        audit_job_event(
+            JobEvent::new(JobEventKind::Spawn, pgid.unwrap_or(child.id() as i32))
+                .pid(child.id() as i32),
+        );
         if pgid.is_none() {
             // Block SIGINT/SIGCHLD until the process group is established.
             // Block SIGCHLD during process-group handoff to avoid races.
@@ -299,20 +312,20 @@ where
                 pipefail_status = result.status_code;
             }
             if last {
-                if cmd.stdout.is_some() {
+                if cmd.redirects_stdout() {
                     write_command_output(cmd, &result.output)?;
                     output.clear();
                 } else {
                     output = result.output;
                 }
-            } else if cmd.stdout.is_some() {
+            } else if cmd.redirects_stdout() {
                 write_command_output(cmd, &result.output)?;
                 input = None;
             } else {
                 input = Some(result.output);
             }
         } else {
-            let capture_output = cmd.stdout.is_none();
+            let capture_output = !child_stdout_redirected(cmd);
             let piped_input = if input_redirection_count(cmd) == 0 {
                 input.take()
             } else {
@@ -345,6 +358,79 @@ where
     })
 }
 
+/// Runs a pipeline of external commands with the last one's output going to
+/// `sink` as it is written, so a reader on the other end can stop early.
+/// Waits for every stage.
+pub fn run_pipeline_into(
+    pipeline: &[CommandSpec],
+    sink: OwnedFd,
+    trace: bool,
+    sandbox: &SandboxConfig,
+) -> io::Result<BuiltinPipeResult> {
+    let mut sink = Some(sink);
+    let mut prev_stdout = None;
+    let mut children: Vec<Child> = Vec::with_capacity(pipeline.len());
+    for (idx, cmd) in pipeline.iter().enumerate() {
+        let last = idx + 1 == pipeline.len();
+        let stdout = if last && !child_stdout_redirected(cmd) {
+            sink.take()
+        } else {
+            None
+        };
+        let spawned = spawn_stage(cmd, prev_stdout.take(), last, stdout, trace, sandbox);
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(err) => {
+                // Earlier stages see a closed pipe and finish on their own.
+                for mut child in children {
+                    let _ = child.wait();
+                }
+                return Err(err);
+            }
+        };
+        if trace {
+            let pid = child.id();
+            eprintln!("trace: spawn pipe pid {pid}");
+        }
+        prev_stdout = child.stdout.take();
+        children.push(child);
+    }
+    drop(sink);
+
+    let mut status_code = 0;
+    let mut pipefail_status = 0;
+    for mut child in children {
+        status_code = exit_status_code(child.wait()?);
+        if status_code != 0 {
+            pipefail_status = status_code;
+        }
+    }
+    Ok(BuiltinPipeResult {
+        status_code,
+        pipefail_status,
+    })
+}
+
+fn spawn_stage(
+    cmd: &CommandSpec,
+    prev_stdout: Option<ChildStdout>,
+    last: bool,
+    stdout: Option<OwnedFd>,
+    trace: bool,
+    sandbox: &SandboxConfig,
+) -> io::Result<Child> {
+    let mut command = build_pipeline_command(cmd, prev_stdout, last, false)?;
+    if let Some(stdout) = stdout {
+        command.stdout(Stdio::from(stdout));
+    }
+    if let Some(options) = sandbox_options_for_command(cmd, sandbox, trace) {
+        apply_sandbox(&mut command, &options)?;
+    }
+    command
+        .spawn()
+        .map_err(|err| wrap_spawn_error(&cmd.args[0], err))
+}
+
 fn run_external_capture(
     cmd: &CommandSpec,
     input: Option<&str>,
@@ -363,8 +449,8 @@ fn run_external_capture(
     }
     if capture_output {
         command.stdout(Stdio::piped());
-    } else if let Some(ref output) = cmd.stdout {
-        apply_stdout_redirection(&mut command, output)?;
+    } else {
+        apply_stdout_redirection(&mut command, cmd)?;
     }
     apply_stderr_redirection(&mut command, cmd)?;
 
@@ -401,11 +487,26 @@ pub fn status_from_error(err: &io::Error) -> i32 {
     }
 }
 
+/// The `set -o reportstatus` line for a failed command, or `None` on success.
+/// 127 and 126 mirror `status_from_error`; 128+n names the signal.
+pub(crate) fn describe_exit_status(command: &str, status: i32) -> Option<String> {
+    let reason = match status {
+        0 => return None,
+        127 => "not found".to_string(),
+        126 => "not executable".to_string(),
+        _ => match status_signal(status) {
+            Some(sig) => format!("killed by {}", signal_name(sig)),
+            None => return Some(format!("command '{command}' exited with status {status}")),
+        },
+    };
+    Some(format!("command '{command}' {reason} (status {status})"))
+}
+
 pub fn exit_status_code(status: std::process::ExitStatus) -> i32 {
     if let Some(code) = status.code() {
         code
     } else if let Some(sig) = status.signal() {
-        128 + sig
+        signal_status(sig)
     } else {
         1
     }
@@ -473,18 +574,43 @@ pub fn write_command_output(cmd: &CommandSpec, output: &str) -> io::Result<()> {
         return Ok(());
     }
     if let Some(ref redir) = cmd.stdout {
-        let mut opts = fs::OpenOptions::new();
-        opts.write(true).create(true);
-        if redir.append {
-            opts.append(true);
-        } else {
-            opts.truncate(true);
-        }
-        let mut file = opts.open(&redir.path)?;
+        let mut file = open_redirect_target(redir)?;
         file.write_all(output.as_bytes())?;
+    } else if cmd.stdout_to_stderr {
+        io::stderr().write_all(output.as_bytes())?;
     } else {
         let mut stdout = io::stdout();
         stdout.write_all(output.as_bytes())?;
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_statuses_map_to_reasons() {
+        assert_eq!(describe_exit_status("true", 0), None);
+        assert_eq!(
+            describe_exit_status("false", 1).unwrap(),
+            "command 'false' exited with status 1"
+        );
+        assert_eq!(
+            describe_exit_status("nosuch", 127).unwrap(),
+            "command 'nosuch' not found (status 127)"
+        );
+        assert_eq!(
+            describe_exit_status("./x", 126).unwrap(),
+            "command './x' not executable (status 126)"
+        );
+        assert_eq!(
+            describe_exit_status("sleep", 137).unwrap(),
+            "command 'sleep' killed by SIGKILL (status 137)"
+        );
+        assert_eq!(
+            describe_exit_status("sh", 200).unwrap(),
+            "command 'sh' exited with status 200"
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use std::fs;
 use std::io;
-use std::os::fd::{FromRawFd, IntoRawFd};
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
 use std::os::unix::process::CommandExt;
 use std::process::{ChildStdout, Command, Stdio};
 
@@ -35,10 +35,9 @@ pub(crate) fn apply_input_redirection(command: &mut Command, cmd: &CommandSpec)
     Ok(())
 }
 
-pub(crate) fn apply_stdout_redirection(
-    command: &mut Command,
-    output: &OutputRedirection,
-) -> io::Result<()> {
+/// Opens the target of `>` (truncating) or `>>` (appending), creating it
+/// if needed.
+pub(crate) fn open_redirect_target(output: &OutputRedirection) -> io::Result<fs::File> {
     let mut opts = fs::OpenOptions::new();
     opts.write(true).create(true);
     if output.append {
@@ -46,8 +45,34 @@ pub(crate) fn apply_stdout_redirection(
     } else {
         opts.truncate(true);
     }
-    let file = opts.open(&output.path)?;
-    command.stdout(Stdio::from(file));
+    opts.open(&output.path)
+}
+
+// `2>&1 >file`: the file only replaces stdout after stderr has copied it,
+// which apply_stderr_redirection arranges in the child.
+fn stdout_follows_stderr(cmd: &CommandSpec) -> bool {
+    cmd.stderr_to_stdout && cmd.stderr_before_stdout && cmd.stdout.is_some()
+}
+
+/// Like `CommandSpec::redirects_stdout`, but false for `2>&1 >file`, where
+/// the child starts out on the usual stdout and moves to the file itself.
+pub(crate) fn child_stdout_redirected(cmd: &CommandSpec) -> bool {
+    cmd.stdout_to_stderr || (cmd.stdout.is_some() && !stdout_follows_stderr(cmd))
+}
+
+pub(crate) fn apply_stdout_redirection(command: &mut Command, cmd: &CommandSpec) -> io::Result<()> {
+    if cmd.stdout_to_stderr {
+        let stderr = io::stderr().as_fd().try_clone_to_owned()?;
+        command.stdout(Stdio::from(stderr));
+        return Ok(());
+    }
+    if stdout_follows_stderr(cmd) {
+        return Ok(());
+    }
+    if let Some(ref output) = cmd.stdout {
+        let file = open_redirect_target(output)?;
+        command.stdout(Stdio::from(file));
+    }
     Ok(())
 }
 
@@ -61,22 +86,24 @@ pub(crate) fn apply_stderr_redirection(command: &mut Command, cmd: &CommandSpec)
     }
 
     if cmd.stderr_to_stdout {
-        set_pre_exec(command, || {
+        let later_stdout = match cmd.stdout {
+            Some(ref output) if stdout_follows_stderr(cmd) => {
+                Some(OwnedFd::from(open_redirect_target(output)?))
+            }
+            _ => None,
+        };
+        set_pre_exec(command, move || {
             dup2(1, 2).map_err(|err| io::Error::other(err.to_string()))?;
+            if let Some(ref fd) = later_stdout {
+                dup2(fd.as_raw_fd(), 1).map_err(|err| io::Error::other(err.to_string()))?;
+            }
             Ok(())
         });
         return Ok(());
     }
 
     if let Some(ref err) = cmd.stderr {
-        let mut opts = fs::OpenOptions::new();
-        opts.write(true).create(true);
-        if err.append {
-            opts.append(true);
-        } else {
-            opts.truncate(true);
-        }
-        let file = opts.open(&err.path)?;
+        let file = open_redirect_target(err)?;
         command.stderr(Stdio::from(file));
     }
     Ok(())
@@ -131,8 +158,8 @@ pub(crate) fn apply_pipeline_stdout(
         command.stdout(Stdio::piped());
         return Ok(());
     }
-    if let Some(ref output) = cmd.stdout {
-        apply_stdout_redirection(command, output)?;
+    if child_stdout_redirected(cmd) {
+        apply_stdout_redirection(command, cmd)?;
     } else if pipe_last_if_missing {
         command.stdout(Stdio::piped());
     }
@@ -221,7 +248,9 @@ mod tests {
         spec.heredoc = Some(HeredocSpec {
             delimiter: "EOF".to_string(),
             quoted: false,
+            strip_tabs: false,
             content: Some("line1\nline2\n".to_string()),
+            body: None,
         });
 
         match run_cat_with_spec(spec) {
@@ -256,10 +285,40 @@ mod tests {
         spec.heredoc = Some(HeredocSpec {
             delimiter: "EOF".to_string(),
             quoted: false,
+            strip_tabs: false,
             content: Some("data".to_string()),
+            body: None,
         });
         let mut command = Command::new("cat");
         let err = apply_input_redirection(&mut command, &spec).unwrap_err();
         assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
     }
+
+    #[test]
+    fn open_redirect_target_modes() {
+        use std::io::Write;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        let target = |append| OutputRedirection {
+            path: path.display().to_string(),
+            append,
+        };
+
+        open_redirect_target(&target(false))
+            .unwrap()
+            .write_all(b"first\n")
+            .unwrap();
+        open_redirect_target(&target(true))
+            .unwrap()
+            .write_all(b"second\n")
+            .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first\nsecond\n");
+
+        open_redirect_target(&target(false))
+            .unwrap()
+            .write_all(b"third\n")
+            .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "third\n");
+    }
 }
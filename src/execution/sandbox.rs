@@ -7,6 +7,8 @@ use crate::parse::{CommandSpec, SandboxDirective};
 use std::ffi::CString;
 #[cfg(feature = "sandbox")]
 use std::os::unix::ffi::OsStrExt;
+#[cfg(feature = "sandbox")]
+use std::os::unix::process::CommandExt;
 
 // Two backends: bubblewrap for stronger isolation, native for broad compatibility.
 #[derive(Debug, Clone, Copy)]
@@ -15,6 +17,20 @@ pub enum SandboxBackend {
     Native,
 }
 
+// Optional rlimits applied to sandboxed children just before exec.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SandboxLimits {
+    pub cpu_seconds: Option<u64>,
+    pub address_space: Option<u64>,
+    pub open_files: Option<u64>,
+}
+
+impl SandboxLimits {
+    pub fn is_empty(&self) -> bool {
+        self.cpu_seconds.is_none() && self.address_space.is_none() && self.open_files.is_none()
+    }
+}
+
 // Persistent config loaded from env/config files.
 #[derive(Debug, Clone)]
 pub struct SandboxConfig {
@@ -22,6 +38,11 @@ pub struct SandboxConfig {
     pub backend: SandboxBackend,
     pub bubblewrap_path: Option<String>,
     pub bubblewrap_args: Vec<String>,
+    pub limits: SandboxLimits,
+    // When non-empty, only these commands are sandboxed.
+    pub allow: Vec<String>,
+    // Commands that are never sandboxed, even with sandboxing on.
+    pub deny: Vec<String>,
 }
 
 impl Default for SandboxConfig {
@@ -31,7 +52,23 @@ impl Default for SandboxConfig {
             backend: SandboxBackend::Native,
             bubblewrap_path: None,
             bubblewrap_args: Vec::new(),
+            limits: SandboxLimits::default(),
+            allow: Vec::new(),
+            deny: Vec::new(),
+        }
+    }
+}
+
+impl SandboxConfig {
+    /// Applies the global allow/deny lists to a command name. Both lists
+    /// match either the name as typed or its basename.
+    pub fn policy_allows(&self, program: &str) -> bool {
+        let base = program.rsplit('/').next().unwrap_or(program);
+        let listed = |list: &[String]| list.iter().any(|item| item == program || item == base);
+        if listed(&self.deny) {
+            return false;
         }
+        self.allow.is_empty() || listed(&self.allow)
     }
 }
 
@@ -43,6 +80,7 @@ pub struct SandboxOptions {
     pub backend: SandboxBackend,
     pub bubblewrap_path: Option<String>,
     pub bubblewrap_args: Vec<String>,
+    pub limits: SandboxLimits,
 }
 
 impl Default for SandboxOptions {
@@ -52,10 +90,44 @@ impl Default for SandboxOptions {
             backend: SandboxBackend::Native,
             bubblewrap_path: None,
             bubblewrap_args: Vec::new(),
+            limits: SandboxLimits::default(),
         }
     }
 }
 
+/// Applies a `sandbox.cpu`, `sandbox.as` or `sandbox.nofile` setting.
+/// Address-space sizes accept K/M/G suffixes; an empty value clears the limit.
+pub fn apply_sandbox_limit(
+    limits: &mut SandboxLimits,
+    key: &str,
+    value: &str,
+) -> Result<(), String> {
+    let value = value.trim();
+    let parsed = if value.is_empty() {
+        None
+    } else {
+        let (digits, scale) = match value.char_indices().last() {
+            Some((idx, 'k' | 'K')) => (&value[..idx], 1u64 << 10),
+            Some((idx, 'm' | 'M')) => (&value[..idx], 1u64 << 20),
+            Some((idx, 'g' | 'G')) => (&value[..idx], 1u64 << 30),
+            _ => (value, 1),
+        };
+        let number = digits
+            .parse::<u64>()
+            .ok()
+            .and_then(|number| number.checked_mul(scale))
+            .ok_or_else(|| format!("invalid sandbox limit '{value}' for {key}"))?;
+        Some(number)
+    };
+    match key {
+        "cpu" => limits.cpu_seconds = parsed,
+        "as" | "memory" => limits.address_space = parsed,
+        "nofile" => limits.open_files = parsed,
+        _ => return Err(format!("unknown sandbox limit '{key}'")),
+    }
+    Ok(())
+}
+
 pub fn apply_sandbox_directive(sandbox: &mut SandboxConfig, directive: SandboxDirective) {
     match directive {
         SandboxDirective::Enable => sandbox.enabled = true,
@@ -71,13 +143,60 @@ pub fn apply_sandbox_directive(sandbox: &mut SandboxConfig, directive: SandboxDi
     }
 }
 
+pub fn format_sandbox_status(sandbox: &SandboxConfig) -> String {
+    let backend = match sandbox.backend {
+        SandboxBackend::Bubblewrap => "bubblewrap",
+        SandboxBackend::Native => "native",
+    };
+    let path = sandbox
+        .bubblewrap_path
+        .clone()
+        .unwrap_or_else(|| "bwrap (from PATH)".to_string());
+    let args = if sandbox.bubblewrap_args.is_empty() {
+        "(none)".to_string()
+    } else {
+        sandbox.bubblewrap_args.join(" ")
+    };
+    let limits = &sandbox.limits;
+    let limits = if limits.is_empty() {
+        "(none)".to_string()
+    } else {
+        let mut parts = Vec::new();
+        if let Some(cpu) = limits.cpu_seconds {
+            parts.push(format!("cpu={cpu}s"));
+        }
+        if let Some(bytes) = limits.address_space {
+            parts.push(format!("as={bytes}"));
+        }
+        if let Some(files) = limits.open_files {
+            parts.push(format!("nofile={files}"));
+        }
+        parts.join(" ")
+    };
+    let list = |items: &[String]| {
+        if items.is_empty() {
+            "(none)".to_string()
+        } else {
+            items.join(" ")
+        }
+    };
+    format!(
+        "enabled: {}\nbackend: {backend}\nbwrap path: {path}\nbwrap args: {args}\nlimits: {limits}\n\
+allow: {}\ndeny: {}\n",
+        if sandbox.enabled { "yes" } else { "no" },
+        list(&sandbox.allow),
+        list(&sandbox.deny)
+    )
+}
+
 pub fn sandbox_options_for_command(
     cmd: &CommandSpec,
     sandbox: &SandboxConfig,
     trace: bool,
 ) -> Option<SandboxOptions> {
     // Allow per-command sandbox overrides without mutating global config.
-    let mut enabled = sandbox.enabled;
+    let program = cmd.args.first().map(String::as_str).unwrap_or("");
+    let mut enabled = sandbox.enabled && sandbox.policy_allows(program);
     let mut backend = sandbox.backend;
     if let Some(directive) = cmd.sandbox {
         match directive {
@@ -101,18 +220,25 @@ pub fn sandbox_options_for_command(
         backend,
         bubblewrap_path: sandbox.bubblewrap_path.clone(),
         bubblewrap_args: sandbox.bubblewrap_args.clone(),
+        limits: sandbox.limits,
     })
 }
 
 pub(crate) fn apply_sandbox(command: &mut Command, options: &SandboxOptions) -> io::Result<()> {
     #[cfg(feature = "sandbox")]
     {
+        // Limits are set in the child before exec so they also cover bwrap's payload.
+        if !options.limits.is_empty() {
+            let limits = options.limits;
+            set_pre_exec(command, move || apply_rlimits(&limits));
+        }
         match options.backend {
             SandboxBackend::Bubblewrap => {
                 let program = command.get_program().to_os_string();
                 let args: Vec<_> = command.get_args().map(|arg| arg.to_os_string()).collect();
                 let bwrap_path = options
                     .bubblewrap_path
+                    .clone()
                     .unwrap_or_else(|| "bwrap".to_string());
                 let bwrap_path_os = std::ffi::OsString::from(bwrap_path);
                 let mut bwrap_args = options
@@ -159,6 +285,28 @@ where
     }
 }
 
+#[cfg(feature = "sandbox")]
+fn apply_rlimits(limits: &SandboxLimits) -> io::Result<()> {
+    let entries = [
+        (libc::RLIMIT_CPU, limits.cpu_seconds),
+        (libc::RLIMIT_AS, limits.address_space),
+        (libc::RLIMIT_NOFILE, limits.open_files),
+    ];
+    for (resource, value) in entries {
+        let Some(value) = value else {
+            continue;
+        };
+        let limit = libc::rlimit {
+            rlim_cur: value as libc::rlim_t,
+            rlim_max: value as libc::rlim_t,
+        };
+        if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
 #[cfg(feature = "sandbox")]
 fn execvp_os(program: &std::ffi::OsStr, args: &[std::ffi::OsString]) -> io::Result<()> {
     let prog_c = CString::new(program.as_bytes())
@@ -187,6 +335,50 @@ fn native_sandbox_exec(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parse::parse_sandbox_value;
+
+    #[test]
+    fn status_reports_backend_and_bwrap_settings() {
+        let config = SandboxConfig::default();
+        assert_eq!(
+            format_sandbox_status(&config),
+            "enabled: no\nbackend: native\nbwrap path: bwrap (from PATH)\nbwrap args: (none)\n\
+limits: (none)\nallow: (none)\ndeny: (none)\n"
+        );
+        let config = SandboxConfig {
+            enabled: true,
+            backend: SandboxBackend::Bubblewrap,
+            bubblewrap_path: Some("/usr/bin/bwrap".to_string()),
+            bubblewrap_args: vec!["--unshare-net".to_string(), "--die-with-parent".to_string()],
+            limits: SandboxLimits {
+                cpu_seconds: Some(5),
+                address_space: None,
+                open_files: Some(64),
+            },
+            allow: vec!["curl".to_string(), "wget".to_string()],
+            deny: Vec::new(),
+        };
+        assert_eq!(
+            format_sandbox_status(&config),
+            "enabled: yes\nbackend: bubblewrap\nbwrap path: /usr/bin/bwrap\n\
+bwrap args: --unshare-net --die-with-parent\nlimits: cpu=5s nofile=64\n\
+allow: curl wget\ndeny: (none)\n"
+        );
+    }
+
+    #[test]
+    fn runtime_toggle_updates_config() {
+        let mut config = SandboxConfig::default();
+        apply_sandbox_directive(&mut config, parse_sandbox_value("bwrap").unwrap());
+        assert!(config.enabled);
+        assert!(matches!(config.backend, SandboxBackend::Bubblewrap));
+        apply_sandbox_directive(&mut config, parse_sandbox_value("off").unwrap());
+        assert!(!config.enabled);
+        assert!(matches!(config.backend, SandboxBackend::Bubblewrap));
+        apply_sandbox_directive(&mut config, parse_sandbox_value("on").unwrap());
+        assert!(config.enabled);
+        assert!(parse_sandbox_value("maybe").is_err());
+    }
 
     #[test]
     fn native_backend_is_explicitly_unsupported() {
@@ -195,9 +387,76 @@ mod tests {
             backend: SandboxBackend::Native,
             bubblewrap_path: None,
             bubblewrap_args: Vec::new(),
+            limits: SandboxLimits::default(),
         };
         let mut command = Command::new("true");
         let err = apply_sandbox(&mut command, &options).unwrap_err();
         assert_eq!(err.kind(), io::ErrorKind::Unsupported);
     }
+
+    #[test]
+    fn limits_flow_from_config_into_options() {
+        let mut config = SandboxConfig {
+            enabled: true,
+            ..SandboxConfig::default()
+        };
+        apply_sandbox_limit(&mut config.limits, "cpu", "10").unwrap();
+        apply_sandbox_limit(&mut config.limits, "as", "512M").unwrap();
+        apply_sandbox_limit(&mut config.limits, "nofile", "128").unwrap();
+        let mut cmd = CommandSpec::new();
+        cmd.args = vec!["make".to_string()];
+
+        let options = sandbox_options_for_command(&cmd, &config, false).unwrap();
+        assert_eq!(
+            options.limits,
+            SandboxLimits {
+                cpu_seconds: Some(10),
+                address_space: Some(512 << 20),
+                open_files: Some(128),
+            }
+        );
+
+        apply_sandbox_limit(&mut config.limits, "cpu", "").unwrap();
+        let options = sandbox_options_for_command(&cmd, &config, false).unwrap();
+        assert_eq!(options.limits.cpu_seconds, None);
+        assert!(apply_sandbox_limit(&mut config.limits, "cpu", "ten").is_err());
+        assert!(apply_sandbox_limit(&mut config.limits, "stack", "1").is_err());
+
+        cmd.sandbox = Some(SandboxDirective::Disable);
+        assert!(sandbox_options_for_command(&cmd, &config, false).is_none());
+    }
+
+    #[test]
+    fn allow_and_deny_lists_pick_sandboxed_commands() {
+        let command = |name: &str| {
+            let mut cmd = CommandSpec::new();
+            cmd.args = vec![name.to_string(), "-v".to_string()];
+            cmd
+        };
+        let mut config = SandboxConfig {
+            enabled: true,
+            deny: vec!["ls".to_string()],
+            ..SandboxConfig::default()
+        };
+        assert!(sandbox_options_for_command(&command("ls"), &config, false).is_none());
+        assert!(sandbox_options_for_command(&command("/bin/ls"), &config, false).is_none());
+        assert!(sandbox_options_for_command(&command("curl"), &config, false).is_some());
+
+        config.deny.clear();
+        config.allow = vec!["curl".to_string()];
+        assert!(sandbox_options_for_command(&command("curl"), &config, false).is_some());
+        assert!(sandbox_options_for_command(&command("make"), &config, false).is_none());
+
+        // Per-command directives win over both lists.
+        let mut make = command("make");
+        make.sandbox = Some(SandboxDirective::Enable);
+        assert!(sandbox_options_for_command(&make, &config, false).is_some());
+        let mut curl = command("curl");
+        curl.sandbox = Some(SandboxDirective::Disable);
+        assert!(sandbox_options_for_command(&curl, &config, false).is_none());
+
+        // The lists only narrow sandboxing; they never turn it on globally.
+        config.enabled = false;
+        assert!(sandbox_options_for_command(&command("curl"), &config, false).is_none());
+    }
 }
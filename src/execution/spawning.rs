@@ -8,8 +8,8 @@ use std::sync::{
 use log::debug;
 
 use crate::job_control::{
-    set_process_group, set_process_group_explicit, wait_for_process_group, SignalMaskGuard,
-    TerminalGuard, TermiosGuard,
+    audit_job_event, set_process_group, set_process_group_explicit, wait_for_process_group,
+    JobEvent, JobEventKind, SignalMaskGuard, TerminalGuard, TermiosGuard,
 };
 use crate::parse::CommandSpec;
 
@@ -25,9 +25,7 @@ pub fn build_command(cmd: &CommandSpec) -> io::Result<Command> {
     command.args(&cmd.args[1..]);
 
     apply_input_redirection(&mut command, cmd)?;
-    if let Some(ref output) = cmd.stdout {
-        apply_stdout_redirection(&mut command, output)?;
-    }
+    apply_stdout_redirection(&mut command, cmd)?;
     apply_stderr_redirection(&mut command, cmd)?;
     apply_fd_closures(&mut command, cmd)?;
 
@@ -78,6 +76,7 @@ pub fn run_command_in_foreground(
         child.id(),
         child.id()
     );
+    audit_job_event(JobEvent::new(JobEventKind::Spawn, child.id() as i32).pid(child.id() as i32));
     let pgid = child.id() as i32;
     fg_pgid.store(pgid, Ordering::SeqCst);
     let _termios_guard = TermiosGuard::new();
@@ -119,6 +118,7 @@ pub fn spawn_command_background(
         child.id(),
         child.id()
     );
+    audit_job_event(JobEvent::new(JobEventKind::Spawn, child.id() as i32).pid(child.id() as i32));
     job_pgid.store(child.id() as i32, Ordering::SeqCst);
     Ok((job_pgid.load(Ordering::SeqCst), child.id() as i32))
 }
@@ -143,6 +143,7 @@ pub fn spawn_command_sandboxed(
         child.id(),
         child.id()
     );
+    audit_job_event(JobEvent::new(JobEventKind::Spawn, child.id() as i32).pid(child.id() as i32));
     job_pgid.store(child.id() as i32, Ordering::SeqCst);
     Ok((job_pgid.load(Ordering::SeqCst), child.id() as i32))
 }
@@ -182,6 +183,10 @@ pub fn spawn_pipeline_background(
             child.id(),
             pgid.unwrap_or(child.id() as i32)
         );
+        audit_job_event(
+            JobEvent::new(JobEventKind::Spawn, pgid.unwrap_or(child.id() as i32))
+                .pid(child.id() as i32),
+        );
         if pgid.is_none() {
             pgid = Some(child.id() as i32);
         }
@@ -228,6 +233,10 @@ pub fn spawn_pipeline_sandboxed(
             child.id(),
             pgid.unwrap_or(child.id() as i32)
         );
+        audit_job_event(
+            JobEvent::new(JobEventKind::Spawn, pgid.unwrap_or(child.id() as i32))
+                .pid(child.id() as i32),
+        );
         if pgid.is_none() {
             pgid = Some(child.id() as i32);
         }
@@ -1,5 +1,6 @@
 //! Expansion runs in two phases: parameter/command substitution, then globbing.
 //! This ordering avoids accidental globbing inside variable values.
+use crate::arithmetic::eval_arithmetic_with;
 use crate::error::{ErrorKind, ShellError};
 use crate::parse::{
     parse_command_substitution, parse_command_substitution_lenient, strip_markers, ESCAPE_MARKER,
@@ -11,10 +12,15 @@ use crate::utils::is_valid_var_name;
 mod glob;
 
 #[allow(unused_imports)]
-pub use glob::{expand_globs, expand_globs_with, GlobOptions};
+pub use glob::{expand_globs, expand_globs_with, glob_pattern, GlobOptions};
+
+// Internal to expansion: separates the words of `"$@"`, and marks a `"$@"`
+// that had no positional parameters to expand to.
+const FIELD_BREAK: char = '\x1a';
+const NO_FIELDS: char = '\x19';
 
 type LookupVar<'a> = Box<dyn Fn(&str) -> Option<String> + 'a>;
-type CommandSubst<'a> = Box<dyn Fn(&str) -> Result<String, String> + 'a>;
+pub type CommandSubst<'a> = Box<dyn Fn(&str) -> Result<String, String> + 'a>;
 
 pub struct ExpansionContext<'a> {
     pub lookup_var: LookupVar<'a>,
@@ -36,23 +42,74 @@ pub fn expand_tokens(
         .as_ref()("IFS")
         .unwrap_or_else(|| " \t\n".to_string());
     let ifs_chars: Vec<char> = ifs.chars().collect();
+    let mut heredoc_delimiter = false;
+    let mut redirect_target = false;
     for token in tokens {
-        if token.starts_with(OPERATOR_TOKEN_MARKER) {
+        // The delimiter word is never expanded and keeps its quote markers so
+        // the parser can tell `<<'EOF'` from `<<EOF`.
+        if token.starts_with(OPERATOR_TOKEN_MARKER) || heredoc_delimiter {
+            heredoc_delimiter = is_heredoc_operator(&token);
+            redirect_target = is_file_redirect_operator(&token);
             expanded.push(token);
             continue;
         }
+        let words_before = expanded.len();
         for brace_token in expand_braces(&token) {
-            let value = expand_token(&brace_token, ctx)?;
+            let value = expand_word(&brace_token, ctx)?;
+            if value.contains([FIELD_BREAK, NO_FIELDS]) {
+                expanded.extend(split_positional_words(&value, &ifs_chars));
+                continue;
+            }
             let fields = split_ifs_token(&value, &ifs_chars);
             if fields.is_empty() {
+                // Unquoted empty expansions vanish; `""` or `"$EMPTY"` stays one empty arg.
+                if is_quoted_word(&brace_token) {
+                    expanded.push(String::new());
+                }
                 continue;
             }
             expanded.extend(fields);
         }
+        if std::mem::take(&mut redirect_target) && expanded.len() != words_before + 1 {
+            return Err(ambiguous_redirect(&strip_markers(&token)));
+        }
     }
     Ok(expanded)
 }
 
+/// The error for a redirection target that does not expand to exactly one
+/// word, e.g. `> $EMPTY` or `> *.log` matching several files.
+pub(crate) fn ambiguous_redirect(target: &str) -> String {
+    format!("{target}: ambiguous redirect")
+}
+
+/// Ambiguous redirects fail the command (status 1) rather than reading as
+/// syntax errors.
+#[allow(dead_code)]
+pub(crate) fn is_ambiguous_redirect(msg: &str) -> bool {
+    msg.ends_with(": ambiguous redirect")
+}
+
+pub(crate) fn is_heredoc_operator(token: &str) -> bool {
+    matches!(
+        token.strip_prefix(OPERATOR_TOKEN_MARKER),
+        Some("<<" | "0<<" | "<<-" | "0<<-")
+    )
+}
+
+/// Operators whose next word names a file to open, as opposed to a heredoc
+/// delimiter or here-string.
+pub(crate) fn is_file_redirect_operator(token: &str) -> bool {
+    matches!(
+        token.strip_prefix(OPERATOR_TOKEN_MARKER),
+        Some("<" | ">" | ">>" | "&>" | "&>>" | "0<" | "1>" | "1>>" | "2>" | "2>>")
+    )
+}
+
+fn is_quoted_word(token: &str) -> bool {
+    token.is_empty() || token.contains(NOGLOB_MARKER) || token.contains(ESCAPE_MARKER)
+}
+
 fn split_ifs_token(token: &str, ifs: &[char]) -> Vec<String> {
     if ifs.is_empty() {
         return vec![token.to_string()];
@@ -89,23 +146,66 @@ fn split_ifs_token(token: &str, ifs: &[char]) -> Vec<String> {
 }
 
 pub fn expand_token(token: &str, ctx: &ExpansionContext<'_>) -> Result<String, String> {
+    // Outside a command's word list, `"$@"` is one word like `"$*"`.
+    let word = expand_word(token, ctx)?;
+    Ok(word.replace(NO_FIELDS, "").replace(FIELD_BREAK, " "))
+}
+
+/// Splits a word that contained `"$@"` into one word per positional
+/// parameter, with any text around it attached to the first and last.
+/// An empty parameter still makes an (empty) word, but `"$@"` with no
+/// parameters makes none.
+fn split_positional_words(value: &str, ifs: &[char]) -> Vec<String> {
+    let value = value.replace(NO_FIELDS, "");
+    let mut words = Vec::new();
+    if value.is_empty() {
+        return words;
+    }
+    for piece in value.split(FIELD_BREAK) {
+        let fields = split_ifs_token(piece, ifs);
+        if fields.is_empty() {
+            words.push(String::new());
+        }
+        words.extend(fields);
+    }
+    words
+}
+
+fn expand_word(token: &str, ctx: &ExpansionContext<'_>) -> Result<String, String> {
     let mut out = String::new();
     let mut chars = token.chars().peekable();
-    // Tilde expansion only applies at the start of a token.
+    // Tilde expansion applies at the start of a token and, in assignment
+    // words, right after the first `=` and after each `:`.
     let mut at_start = true;
+    let assignment = is_assignment_word(token);
+    let mut seen_eq = false;
 
     while let Some(ch) = chars.next() {
         if ch == ESCAPE_MARKER {
             if let Some(next) = chars.next() {
-                out.push(next);
+                push_escaped(&mut out, next, ctx);
                 at_start = false;
             }
             continue;
         }
         if ch == NOGLOB_MARKER {
             // Double-quoted segments mark bytes as non-globbable.
-            if let Some(next) = chars.next() {
-                if next == '$' {
+            if let Some(mut next) = chars.next() {
+                // `"\t"` and `$'...'` text carry both markers; the escaped
+                // character is taken literally.
+                let escaped = next == ESCAPE_MARKER;
+                if escaped {
+                    let Some(literal) = chars.next() else {
+                        continue;
+                    };
+                    next = literal;
+                }
+                if next == '$' && !escaped {
+                    if let Some(value) = quoted_special_parameter(&mut chars, ctx) {
+                        out.push_str(&value);
+                        at_start = false;
+                        continue;
+                    }
                     let expanded = match expand_dollar(&mut chars, ctx)? {
                         Some(value) => value,
                         None => "$".to_string(),
@@ -123,7 +223,7 @@ pub fn expand_token(token: &str, ctx: &ExpansionContext<'_>) -> Result<String, S
 
         if at_start && ch == '~' {
             let next = chars.peek().copied();
-            if next.is_none() || next == Some('/') {
+            if next.is_none() || next == Some('/') || (assignment && next == Some(':')) {
                 if let Some(home) = (ctx.lookup_var)("HOME") {
                     out.push_str(&home);
                 } else {
@@ -143,12 +243,204 @@ pub fn expand_token(token: &str, ctx: &ExpansionContext<'_>) -> Result<String, S
         }
 
         out.push(ch);
-        at_start = false;
+        at_start = assignment && (ch == ':' || (ch == '=' && !seen_eq));
+        seen_eq |= ch == '=';
     }
 
     Ok(out)
 }
 
+/// Expands the body of an unquoted heredoc: parameters, `$(...)` and
+/// backticks, but no splitting, globbing or quote removal. Only `\$`,
+/// `` \` ``, `\\` and backslash-newline are escapes; any other backslash
+/// is kept as written.
+pub fn expand_heredoc(body: &str, ctx: &ExpansionContext<'_>) -> Result<String, String> {
+    let mut out = String::with_capacity(body.len());
+    let mut chars = body.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.peek().copied() {
+                Some(next @ ('$' | '`' | '\\')) => {
+                    chars.next();
+                    out.push(next);
+                }
+                Some('\n') => {
+                    chars.next();
+                }
+                _ => out.push('\\'),
+            },
+            '$' => match expand_dollar(&mut chars, ctx)? {
+                Some(value) => out.push_str(&value),
+                None => out.push('$'),
+            },
+            '`' => {
+                let mut inner = String::new();
+                let mut closed = false;
+                while let Some(next) = chars.next() {
+                    match next {
+                        '`' => {
+                            closed = true;
+                            break;
+                        }
+                        '\\' if matches!(chars.peek(), Some('`' | '$' | '\\')) => {
+                            inner.extend(chars.next());
+                        }
+                        _ => inner.push(next),
+                    }
+                }
+                if closed {
+                    out.push_str(&(ctx.command_subst)(&inner)?);
+                } else {
+                    out.push('`');
+                    out.push_str(&inner);
+                }
+            }
+            _ => out.push(ch),
+        }
+    }
+    Ok(out)
+}
+
+// An escaped blank or glob character stays marked, like a double-quoted
+// one, so splitting and globbing leave it alone.
+fn push_escaped(out: &mut String, ch: char, ctx: &ExpansionContext<'_>) {
+    if ch.is_whitespace() || matches!(ch, '*' | '?' | '[' | ']') || is_ifs_char(ch, ctx) {
+        out.push(NOGLOB_MARKER);
+    }
+    out.push(ch);
+}
+
+fn is_ifs_char(ch: char, ctx: &ExpansionContext<'_>) -> bool {
+    (ctx.lookup_var)("IFS").is_some_and(|ifs| ifs.contains(ch))
+}
+
+// `NAME=...` with an unquoted, valid name before the first `=`.
+fn is_assignment_word(token: &str) -> bool {
+    token
+        .split_once('=')
+        .is_some_and(|(name, _)| is_valid_var_name(name))
+}
+
+// What follows `$((`: an arithmetic body closed by `))`, or a body whose
+// first unmatched `)` stands alone, which makes the whole thing a command
+// substitution starting with a subshell, as in `$((cd /tmp; ls) | wc -l)`.
+enum DoubleParen {
+    Arithmetic(String),
+    Subshell(String),
+    Unterminated(String),
+}
+
+fn scan_double_paren<I>(chars: &mut std::iter::Peekable<I>) -> DoubleParen
+where
+    I: Iterator<Item = char>,
+{
+    let mut body = String::new();
+    let mut depth = 0usize;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                // In double quotes the tokenizer has already marked the
+                // second `)` as quoted text.
+                chars.next_if_eq(&NOGLOB_MARKER);
+                if chars.next_if_eq(&')').is_some() {
+                    return DoubleParen::Arithmetic(body);
+                }
+                return DoubleParen::Subshell(body);
+            }
+            ')' => depth -= 1,
+            _ => {}
+        }
+        body.push(ch);
+    }
+    DoubleParen::Unterminated(body)
+}
+
+fn expand_double_paren<I>(
+    chars: &mut std::iter::Peekable<I>,
+    ctx: &ExpansionContext<'_>,
+) -> Result<String, String>
+where
+    I: Iterator<Item = char>,
+{
+    match scan_double_paren(chars) {
+        DoubleParen::Arithmetic(body) => expand_arithmetic(&body, ctx),
+        DoubleParen::Subshell(body) => {
+            let rest = if ctx.strict {
+                parse_command_substitution(chars)?
+            } else {
+                let (rest, closed) = parse_command_substitution_lenient(chars)?;
+                if !closed {
+                    return Ok(format!("$(({body}){rest}"));
+                }
+                rest
+            };
+            (ctx.command_subst)(&format!("({body}){rest}"))
+        }
+        DoubleParen::Unterminated(_) if ctx.strict => Err(ShellError::new(
+            ErrorKind::Parse,
+            "Unterminated arithmetic expansion $((...))",
+        )
+        .with_context("Missing closing `))`")
+        .to_string()),
+        DoubleParen::Unterminated(body) => Ok(format!("$(({body}")),
+    }
+}
+
+/// `$((expr))`: parameters and substitutions in `expr` are expanded first,
+/// then bare names are read through `ctx.lookup_var`.
+fn expand_arithmetic(body: &str, ctx: &ExpansionContext<'_>) -> Result<String, String> {
+    let mut expr = String::with_capacity(body.len());
+    let mut chars = body.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '$' {
+            expr.push(ch);
+            continue;
+        }
+        match expand_dollar(&mut chars, ctx)? {
+            Some(value) => expr.push_str(&value),
+            None => expr.push('$'),
+        }
+    }
+    eval_arithmetic_with(&expr, &|name| (ctx.lookup_var)(name))
+        .map(|value| value.to_string())
+        .map_err(|err| format!("arithmetic expansion: {err}"))
+}
+
+/// A special or positional parameter such as `$?` or `$1` inside double
+/// quotes, where the character after the `$` carries its own quote marker.
+/// `"$@"` keeps each positional parameter a word of its own: they are
+/// joined with `FIELD_BREAK`, or stand in as `NO_FIELDS` when there are
+/// none, for `expand_tokens` to split on.
+fn quoted_special_parameter(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    ctx: &ExpansionContext<'_>,
+) -> Option<String> {
+    let mut ahead = chars.clone();
+    if ahead.next() != Some(NOGLOB_MARKER) {
+        return None;
+    }
+    let name = ahead.next().filter(|ch| is_special_parameter(*ch))?;
+    *chars = ahead;
+    if name == '@' {
+        if ctx.positional.is_empty() {
+            return Some(NO_FIELDS.to_string());
+        }
+        let words: Vec<String> = ctx
+            .positional
+            .iter()
+            .map(|arg| enforce_no_glob(arg))
+            .collect();
+        return Some(words.join(&FIELD_BREAK.to_string()));
+    }
+    let value = (ctx.lookup_var)(&name.to_string()).unwrap_or_default();
+    Some(enforce_no_glob(&value))
+}
+
+fn is_special_parameter(ch: char) -> bool {
+    matches!(ch, '?' | '$' | '!' | '#' | '@' | '*') || ch.is_ascii_digit()
+}
+
 fn expand_dollar<I>(
     chars: &mut std::iter::Peekable<I>,
     ctx: &ExpansionContext<'_>,
@@ -159,6 +451,10 @@ where
     match chars.peek().copied() {
         Some('(') => {
             chars.next();
+            if chars.peek() == Some(&'(') {
+                chars.next();
+                return expand_double_paren(chars, ctx).map(Some);
+            }
             if ctx.strict {
                 let inner = parse_command_substitution(chars)?;
                 let output = (ctx.command_subst)(&inner)?;
@@ -176,6 +472,8 @@ where
             chars.next();
             let mut inner = String::new();
             let mut found = false;
+            // Nested `${...}` in a `${name:-word}` word.
+            let mut depth = 0usize;
             while let Some(ch) = chars.next() {
                 if ch == ESCAPE_MARKER {
                     if let Some(next) = chars.next() {
@@ -191,9 +489,14 @@ where
                     }
                     continue;
                 }
-                if ch == '}' {
-                    found = true;
-                    break;
+                if ch == '{' && inner.ends_with('$') {
+                    depth += 1;
+                } else if ch == '}' {
+                    if depth == 0 {
+                        found = true;
+                        break;
+                    }
+                    depth -= 1;
                 }
                 inner.push(ch);
             }
@@ -210,8 +513,11 @@ where
             }
             let param = parse_parameter(&inner)?;
             let name = strip_markers(param.name());
-            let needs_validation = !matches!(param, Parameter::PrefixVars { .. });
-            if needs_validation && !is_valid_var_name(&name) {
+            let needs_validation = !matches!(
+                param,
+                Parameter::PrefixVars { .. } | Parameter::PositionalCount
+            );
+            if needs_validation && !is_valid_var_name(&name) && name != "?" {
                 if ctx.strict {
                     return Err(ShellError::new(
                         ErrorKind::Expansion,
@@ -223,25 +529,48 @@ where
                 return Ok(Some(format!("${{{inner}}}")));
             }
             match param {
-                Parameter::Default { fallback, .. } => {
-                    let value = (ctx.lookup_var)(&name).unwrap_or_default();
-                    if !value.is_empty() {
-                        return Ok(Some(value));
-                    }
-                    if let Some(fallback) = fallback {
-                        return Ok(Some(expand_token(&fallback, ctx)?));
+                Parameter::Default {
+                    op,
+                    word,
+                    null_is_unset,
+                    ..
+                } => {
+                    let value = (ctx.lookup_var)(&name)
+                        .filter(|value| !(null_is_unset && value.is_empty()));
+                    match (op, value) {
+                        (DefaultOp::Alternate, None) => Ok(Some(String::new())),
+                        (DefaultOp::Alternate, Some(_)) => expand_token(&word, ctx).map(Some),
+                        (_, Some(value)) => Ok(Some(value)),
+                        (DefaultOp::Use, None) => expand_token(&word, ctx).map(Some),
+                        (DefaultOp::Assign, None) => {
+                            let value = strip_markers(&expand_token(&word, ctx)?);
+                            std::env::set_var(&name, &value);
+                            Ok(Some(value))
+                        }
+                        (DefaultOp::Error, None) => {
+                            let message = strip_markers(&expand_token(&word, ctx)?);
+                            Err(if message.is_empty() {
+                                format!("{name}: parameter null or not set")
+                            } else {
+                                format!("{name}: {message}")
+                            })
+                        }
                     }
-                    Ok(Some(String::new()))
                 }
-                Parameter::Pattern { op, pattern, .. } => {
+                Parameter::Pattern {
+                    op,
+                    pattern,
+                    longest,
+                    ..
+                } => {
                     let value = (ctx.lookup_var)(&name).unwrap_or_default();
                     let pattern = strip_markers(&pattern);
                     if pattern.is_empty() {
                         return Ok(Some(value));
                     }
                     let stripped = match op {
-                        ParamOp::Prefix => remove_prefix_pattern(&value, &pattern)?,
-                        ParamOp::Suffix => remove_suffix_pattern(&value, &pattern)?,
+                        ParamOp::Prefix => remove_prefix_pattern(&value, &pattern, longest)?,
+                        ParamOp::Suffix => remove_suffix_pattern(&value, &pattern, longest)?,
                     };
                     Ok(Some(stripped))
                 }
@@ -276,6 +605,8 @@ where
                     Ok(Some(String::new()))
                 }
                 Parameter::Assoc { key, length, .. } => {
+                    // Subscripts may be quoted (`m["a b"]`) or use `$var`.
+                    let key = strip_markers(&expand_token(&key, ctx)?);
                     if let Some(map) = (ctx.lookup_assoc)(&name) {
                         if let Some(value) = expand_assoc_ref(Some(&key), length, ctx, &map) {
                             return Ok(Some(value));
@@ -300,6 +631,11 @@ where
                     keys.sort();
                     Ok(Some(keys.join(&sep.to_string())))
                 }
+                Parameter::PositionalCount => Ok(Some(ctx.positional.len().to_string())),
+                Parameter::Indirect { .. } => {
+                    let target = (ctx.lookup_var)(&name).unwrap_or_default();
+                    expand_indirect(&name, &target, ctx).map(Some)
+                }
                 Parameter::Transform { op, .. } => {
                     let value = (ctx.lookup_var)(&name).unwrap_or_default();
                     Ok(Some(transform_value(&value, op)))
@@ -321,6 +657,10 @@ where
                 }
             }
         }
+        Some(ch) if is_special_parameter(ch) => {
+            chars.next();
+            Ok(Some((ctx.lookup_var)(&ch.to_string()).unwrap_or_default()))
+        }
         Some(ch) if is_var_start(ch) => {
             let mut name = String::new();
             name.push(ch);
@@ -345,10 +685,30 @@ enum ParamOp {
     Suffix,
 }
 
+// `${name-word}`, `${name=word}`, `${name+word}` and `${name?word}`.
+enum DefaultOp {
+    Use,
+    Assign,
+    Alternate,
+    Error,
+}
+
 enum Parameter {
     Simple { name: String, length: bool },
-    Default { name: String, fallback: Option<String> },
-    Pattern { name: String, op: ParamOp, pattern: String },
+    // With a `:` (`null_is_unset`), an empty value counts as unset.
+    Default {
+        name: String,
+        op: DefaultOp,
+        word: String,
+        null_is_unset: bool,
+    },
+    // `#`/`%` remove the shortest match, `##`/`%%` the longest.
+    Pattern {
+        name: String,
+        op: ParamOp,
+        pattern: String,
+        longest: bool,
+    },
     Subst {
         name: String,
         pattern: String,
@@ -358,6 +718,9 @@ enum Parameter {
     Assoc { name: String, key: String, length: bool },
     AssocKeys { name: String },
     PrefixVars { prefix: String },
+    // `${#}`, `${#@}` and `${#*}`.
+    PositionalCount,
+    Indirect { name: String },
     Transform { name: String, op: TransformOp },
     Substring {
         name: String,
@@ -376,24 +739,47 @@ impl Parameter {
             Parameter::Array { name, .. } => name,
             Parameter::Assoc { name, .. } => name,
             Parameter::AssocKeys { name, .. } => name,
+            Parameter::Indirect { name } => name,
             Parameter::Transform { name, .. } => name,
             Parameter::Substring { name, .. } => name,
-            Parameter::PrefixVars { .. } => "",
+            Parameter::PrefixVars { .. } | Parameter::PositionalCount => "",
         }
     }
 }
 
+fn parse_default(input: &str) -> Option<Parameter> {
+    let (name, rest) = input.split_at(input.find(|ch: char| !is_var_char(ch))?);
+    if name.is_empty() {
+        return None;
+    }
+    let (null_is_unset, rest) = match rest.strip_prefix(':') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let mut chars = rest.chars();
+    let op = match chars.next()? {
+        '-' => DefaultOp::Use,
+        '=' => DefaultOp::Assign,
+        '+' => DefaultOp::Alternate,
+        '?' => DefaultOp::Error,
+        _ => return None,
+    };
+    Some(Parameter::Default {
+        name: name.to_string(),
+        op,
+        word: chars.as_str().to_string(),
+        null_is_unset,
+    })
+}
+
 fn parse_parameter(input: &str) -> Result<Parameter, String> {
-    if let Some((name, fallback)) = input.split_once(":-") {
-        return Ok(Parameter::Default {
-            name: name.to_string(),
-            fallback: Some(fallback.to_string()),
-        });
+    if let Some(param) = parse_default(input) {
+        return Ok(param);
     }
 
     if let Some(inner) = input.strip_prefix('!') {
-        if inner.ends_with('*') && !inner.contains('[') {
-            let prefix = inner.trim_end_matches('*').to_string();
+        if (inner.ends_with('*') || inner.ends_with('@')) && !inner.contains('[') {
+            let prefix = inner[..inner.len() - 1].to_string();
             return Ok(Parameter::PrefixVars { prefix });
         }
         if let Some((name, idx)) = parse_array_ref(inner) {
@@ -401,6 +787,11 @@ fn parse_parameter(input: &str) -> Result<Parameter, String> {
                 return Ok(Parameter::AssocKeys { name });
             }
         }
+        if is_valid_var_name(&strip_markers(inner)) {
+            return Ok(Parameter::Indirect {
+                name: inner.to_string(),
+            });
+        }
     }
 
     let (length, inner) = if let Some(rest) = input.strip_prefix('#') {
@@ -408,6 +799,9 @@ fn parse_parameter(input: &str) -> Result<Parameter, String> {
     } else {
         (false, input)
     };
+    if length && matches!(inner, "" | "@" | "*") {
+        return Ok(Parameter::PositionalCount);
+    }
 
     if let Some((name, index)) = parse_array_ref(inner) {
         if let Some(ref key) = index {
@@ -442,20 +836,17 @@ fn parse_parameter(input: &str) -> Result<Parameter, String> {
         });
     }
 
-    if let Some((name, pattern)) = inner.split_once('#') {
-        return Ok(Parameter::Pattern {
-            name: name.to_string(),
-            op: ParamOp::Prefix,
-            pattern: pattern.to_string(),
-        });
-    }
-
-    if let Some((name, pattern)) = inner.split_once('%') {
-        return Ok(Parameter::Pattern {
-            name: name.to_string(),
-            op: ParamOp::Suffix,
-            pattern: pattern.to_string(),
-        });
+    for (marker, op) in [('#', ParamOp::Prefix), ('%', ParamOp::Suffix)] {
+        if let Some((name, pattern)) = inner.split_once(marker) {
+            let longest = pattern.starts_with(marker);
+            let pattern = if longest { &pattern[1..] } else { pattern };
+            return Ok(Parameter::Pattern {
+                name: name.to_string(),
+                op,
+                pattern: pattern.to_string(),
+                longest,
+            });
+        }
     }
 
     if let Some((name, op)) = parse_transform(inner) {
@@ -488,7 +879,8 @@ fn parse_subst(input: &str) -> Option<(String, String, String)> {
     let name = parts.next()?.to_string();
     let pattern = parts.next()?.to_string();
     let replacement = parts.next().unwrap_or("").to_string();
-    if name.is_empty() {
+    // `${path#*/}` is a prefix removal whose pattern has a slash.
+    if !is_valid_var_name(&strip_markers(&name)) {
         return None;
     }
     Some((name, pattern, replacement))
@@ -593,7 +985,10 @@ fn expand_assoc_ref(
             }
             let ifs = (ctx.lookup_var)("IFS").unwrap_or_else(|| " \t\n".to_string());
             let sep = ifs.chars().next().unwrap_or(' ');
-            let values: Vec<String> = map.values().cloned().collect();
+            let values: Vec<&str> = sorted_keys(map)
+                .into_iter()
+                .map(|key| map[key].as_str())
+                .collect();
             Some(values.join(&sep.to_string()))
         }
         Some(key) => {
@@ -606,11 +1001,46 @@ fn expand_assoc_ref(
     }
 }
 
-fn expand_assoc_keys(ctx: &ExpansionContext<'_>, map: &std::collections::HashMap<String, String>) -> String {
+fn expand_assoc_keys(
+    ctx: &ExpansionContext<'_>,
+    map: &std::collections::HashMap<String, String>,
+) -> String {
     let ifs = (ctx.lookup_var)("IFS").unwrap_or_else(|| " \t\n".to_string());
     let sep = ifs.chars().next().unwrap_or(' ');
-    let keys: Vec<String> = map.keys().cloned().collect();
-    keys.join(&sep.to_string())
+    sorted_keys(map).join(&sep.to_string())
+}
+
+// `${!name}`: `name` holds the name of another parameter (a variable, a
+// positional such as `1`, or an array element such as `arr[2]`) whose value
+// is substituted. An unset or empty `name` expands to nothing.
+fn expand_indirect(name: &str, target: &str, ctx: &ExpansionContext<'_>) -> Result<String, String> {
+    if target.is_empty() {
+        return Ok(String::new());
+    }
+    let is_special =
+        matches!(target, "#" | "@" | "*") || target.chars().all(|ch| ch.is_ascii_digit());
+    if is_special || is_valid_var_name(target) {
+        return Ok((ctx.lookup_var)(target).unwrap_or_default());
+    }
+    if parse_array_ref(target).is_some_and(|(array, _)| is_valid_var_name(&array)) {
+        return expand_token(&format!("${{{target}}}"), ctx);
+    }
+    if ctx.strict {
+        return Err(ShellError::new(
+            ErrorKind::Expansion,
+            format!("{name}: invalid indirect expansion"),
+        )
+        .with_context(format!("'{target}' is not a variable name"))
+        .to_string());
+    }
+    Ok(String::new())
+}
+
+// Keys in a stable order so `${!m[@]}` and `${m[@]}` line up run to run.
+fn sorted_keys(map: &std::collections::HashMap<String, String>) -> Vec<&str> {
+    let mut keys: Vec<&str> = map.keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    keys
 }
 
 fn transform_value(value: &str, op: TransformOp) -> String {
@@ -684,14 +1114,19 @@ fn replace_first_pattern(value: &str, pattern: &str, replacement: &str) -> Resul
     Ok(value.to_string())
 }
 
-fn remove_prefix_pattern(value: &str, pattern: &str) -> Result<String, String> {
+fn remove_prefix_pattern(value: &str, pattern: &str, longest: bool) -> Result<String, String> {
     let matcher = Pattern::new(pattern).map_err(|err| format!("invalid pattern: {err}"))?;
     let indices: Vec<usize> = value
         .char_indices()
         .map(|(idx, _)| idx)
         .chain(std::iter::once(value.len()))
         .collect();
-    for &end in &indices {
+    let ends: Box<dyn Iterator<Item = &usize>> = if longest {
+        Box::new(indices.iter().rev())
+    } else {
+        Box::new(indices.iter())
+    };
+    for &end in ends {
         let prefix = &value[..end];
         if matcher.matches(prefix) {
             return Ok(value[end..].to_string());
@@ -700,14 +1135,19 @@ fn remove_prefix_pattern(value: &str, pattern: &str) -> Result<String, String> {
     Ok(value.to_string())
 }
 
-fn remove_suffix_pattern(value: &str, pattern: &str) -> Result<String, String> {
+fn remove_suffix_pattern(value: &str, pattern: &str, longest: bool) -> Result<String, String> {
     let matcher = Pattern::new(pattern).map_err(|err| format!("invalid pattern: {err}"))?;
     let indices: Vec<usize> = value
         .char_indices()
         .map(|(idx, _)| idx)
         .chain(std::iter::once(value.len()))
         .collect();
-    for &start in indices.iter().rev() {
+    let starts: Box<dyn Iterator<Item = &usize>> = if longest {
+        Box::new(indices.iter())
+    } else {
+        Box::new(indices.iter().rev())
+    };
+    for &start in starts {
         let suffix = &value[start..];
         if matcher.matches(suffix) {
             return Ok(value[..start].to_string());
@@ -758,10 +1198,25 @@ fn find_expandable_brace(token: &str) -> Option<(usize, usize, Vec<String>)> {
     let mut iter = token.char_indices().peekable();
     let mut depth = 0usize;
     let mut start = None;
+    // Inside `${...}`, whose commas belong to the parameter's word.
+    let mut param_depth = 0usize;
+    let mut prev = None;
 
     while let Some((idx, ch)) = iter.next() {
         if ch == ESCAPE_MARKER || ch == NOGLOB_MARKER {
             iter.next();
+            prev = None;
+            continue;
+        }
+        let after_dollar = prev.replace(ch) == Some('$');
+        if ch == '{' && after_dollar {
+            param_depth += 1;
+            continue;
+        }
+        if param_depth > 0 {
+            if ch == '}' {
+                param_depth -= 1;
+            }
             continue;
         }
         if ch == '{' {
@@ -983,6 +1438,90 @@ mod tests {
         }
     }
 
+    #[test]
+    fn heredoc_body_expands_with_limited_escapes() {
+        let ctx = ExpansionContext {
+            lookup_var: Box::new(|name| (name == "WHO").then(|| "world".to_string())),
+            lookup_array: Box::new(|_| None),
+            lookup_assoc: Box::new(|_| None),
+            command_subst: Box::new(|inner| Ok(format!("<{inner}>"))),
+            positional: &[],
+            strict: true,
+        };
+        assert_eq!(
+            expand_heredoc("hi $WHO, \\$WHO costs \\$5\n", &ctx).unwrap(),
+            "hi world, $WHO costs $5\n"
+        );
+        assert_eq!(
+            expand_heredoc("a\\\\b \\n \\\"q\\\" 'x' \"$WHO\"\n", &ctx).unwrap(),
+            "a\\b \\n \\\"q\\\" 'x' \"world\"\n"
+        );
+        assert_eq!(
+            expand_heredoc("`date` \\`raw\\` $(id)\njoin\\\ned\n", &ctx).unwrap(),
+            "<date> `raw` <id>\njoined\n"
+        );
+    }
+
+    #[test]
+    fn arithmetic_expansion_follows_precedence() {
+        let ctx = ExpansionContext {
+            lookup_var: Box::new(|name| match name {
+                "N" => Some("6".to_string()),
+                "WORD" => Some("abc".to_string()),
+                _ => None,
+            }),
+            lookup_array: Box::new(|_| None),
+            lookup_assoc: Box::new(|_| None),
+            command_subst: Box::new(|inner| Ok(format!("<{inner}>"))),
+            positional: &[],
+            strict: true,
+        };
+        let expand = |token: &str| expand_token(token, &ctx);
+        assert_eq!(expand("$((2 + 3 * 4))").unwrap(), "14");
+        assert_eq!(expand("$(((2 + 3) * 4))").unwrap(), "20");
+        assert_eq!(expand("$((2 ** 3 ** 2 - 17 % 5))").unwrap(), "510");
+        assert_eq!(expand("$((N / 4))-$((N >= 6))$((N < 6))").unwrap(), "1-10");
+        assert_eq!(expand("$((N + $N + UNSET + WORD))").unwrap(), "12");
+        assert_eq!(
+            expand("$((1 / (N - 6)))").unwrap_err(),
+            "arithmetic expansion: division by zero"
+        );
+        assert!(expand("$((1 + 2)").is_err());
+        // A lone `)` makes it a command substitution of a subshell.
+        assert_eq!(expand("$((cd /; ls) | wc)").unwrap(), "<(cd /; ls) | wc>");
+    }
+
+    #[test]
+    fn redirect_targets_must_expand_to_one_word() {
+        let ctx = ExpansionContext {
+            lookup_var: Box::new(|name| match name {
+                "OUT" => Some("out.txt".to_string()),
+                "TWO" => Some("a b".to_string()),
+                _ => None,
+            }),
+            lookup_array: Box::new(|_| None),
+            lookup_assoc: Box::new(|_| None),
+            command_subst: Box::new(|_| Ok(String::new())),
+            positional: &[],
+            strict: true,
+        };
+        let expand = |line: &str| expand_tokens(crate::parse::parse_line(line).unwrap(), &ctx);
+        let expanded = expand("echo $TWO > $OUT").unwrap();
+        assert_eq!(expanded[1..3], ["a", "b"]);
+        assert_eq!(expanded[4], "out.txt");
+        assert_eq!(
+            expand("echo hi > $TWO").unwrap_err(),
+            "$TWO: ambiguous redirect"
+        );
+        assert_eq!(
+            expand("cat < $MISSING").unwrap_err(),
+            "$MISSING: ambiguous redirect"
+        );
+        assert!(is_ambiguous_redirect(
+            &expand("echo 2>> {x,y}").unwrap_err()
+        ));
+    }
+
     fn ctx_with_array(name: &'static str, values: Vec<String>) -> ExpansionContext<'static> {
         ExpansionContext {
             lookup_var: Box::new(|_| None),
@@ -1024,6 +1563,55 @@ mod tests {
         }
     }
 
+    #[test]
+    fn assoc_subscripts_accept_quoted_special_keys() {
+        let ctx = ctx_with_assoc(
+            "m",
+            vec![("a b", "spaced"), ("k.1", "dotted"), ("x", "plain")],
+        );
+        let tokens = crate::parse::parse_line(r#"echo ${m["a b"]} ${m[k.1]} ${m['x']}"#).unwrap();
+        let expanded = expand_tokens(tokens, &ctx).unwrap();
+        assert_eq!(expanded[1..], ["spaced", "dotted", "plain"]);
+        assert_eq!(expand_token("${!m[@]}", &ctx).unwrap(), "a b k.1 x");
+        assert_eq!(
+            expand_token("${m[@]}", &ctx).unwrap(),
+            "spaced dotted plain"
+        );
+    }
+
+    #[test]
+    fn empty_quoted_argument_survives_full_chain() {
+        let ctx = ctx_no_subst();
+        let line = "printf '%s|%s' \"ab\"\"cd\" \"\" '' $CS_TEST_UNSET_EMPTY *.none";
+        let tokens = crate::parse::parse_line(line).unwrap();
+        let expanded = expand_globs(expand_tokens(tokens, &ctx).unwrap()).unwrap();
+        let (pipeline, _) = crate::parse::split_pipeline(expanded).unwrap();
+        assert_eq!(
+            pipeline[0].args,
+            vec!["printf", "%s|%s", "abcd", "", "", "*.none"]
+        );
+    }
+
+    #[test]
+    fn tilde_expands_in_assignment_values() {
+        let ctx = ExpansionContext {
+            lookup_var: Box::new(|name| (name == "HOME").then(|| "/home/me".to_string())),
+            ..ctx_no_subst()
+        };
+        assert_eq!(
+            expand_token("X=~/a:~/b", &ctx).unwrap(),
+            "X=/home/me/a:/home/me/b"
+        );
+        assert_eq!(
+            expand_token("PATH=~:/bin", &ctx).unwrap(),
+            "PATH=/home/me:/bin"
+        );
+        assert_eq!(expand_token("~/a:~/b", &ctx).unwrap(), "/home/me/a:~/b");
+        assert_eq!(expand_token("--opt=~/x", &ctx).unwrap(), "--opt=~/x");
+        assert_eq!(expand_token("X=a=~/b", &ctx).unwrap(), "X=a=~/b");
+        assert_eq!(expand_token("X=~user", &ctx).unwrap(), "X=~user");
+    }
+
     #[test]
     fn expand_parameter_defaulting() {
         let ctx = ctx_no_subst();
@@ -1038,6 +1626,38 @@ mod tests {
         });
     }
 
+    #[test]
+    fn default_operators_tell_unset_from_empty() {
+        let ctx = ctx_no_subst();
+        let (unset, empty) = ("CS_TEST_DEFAULT_UNSET", "CS_TEST_DEFAULT_EMPTY");
+        env::remove_var(unset);
+        with_env_var(empty, "", || {
+            let expand = |token: String| expand_token(&token, &ctx);
+            assert_eq!(expand(format!("${{{unset}-d}}")).unwrap(), "d");
+            assert_eq!(expand(format!("${{{empty}-d}}")).unwrap(), "");
+            assert_eq!(expand(format!("${{{empty}:-d}}")).unwrap(), "d");
+            assert_eq!(expand(format!("${{{unset}+alt}}")).unwrap(), "");
+            assert_eq!(expand(format!("${{{empty}+alt}}")).unwrap(), "alt");
+            assert_eq!(expand(format!("${{{empty}:+alt}}")).unwrap(), "");
+            assert_eq!(expand(format!("${{{empty}?}}")).unwrap(), "");
+            assert_eq!(
+                expand(format!("${{{empty}:?}}")).unwrap_err(),
+                format!("{empty}: parameter null or not set")
+            );
+            assert_eq!(
+                expand(format!("${{{unset}?need $HOME}}")).unwrap_err(),
+                format!("{unset}: need {}", env::var("HOME").unwrap())
+            );
+
+            assert_eq!(expand(format!("${{{empty}=kept}}")).unwrap(), "");
+            let nested = format!("${{{empty}:=${{{unset}:-x}}y}}");
+            assert_eq!(expand(nested).unwrap(), "xy");
+            assert_eq!(env::var(empty).unwrap(), "xy");
+            assert_eq!(expand(format!("${{{empty}:=z}}")).unwrap(), "xy");
+        });
+        assert_eq!(env::var_os(unset), None);
+    }
+
     #[test]
     fn expand_parameter_prefix_pattern() {
         let ctx = ctx_no_subst();
@@ -1058,6 +1678,76 @@ mod tests {
         });
     }
 
+    #[test]
+    fn expand_parameter_longest_patterns_and_lengths() {
+        let positional = ["a".to_string(), "b c".to_string(), String::new()];
+        let ctx = ExpansionContext {
+            lookup_var: Box::new(|name| {
+                (name == "FILE").then(|| "/srv/www/index.tar.gz".to_string())
+            }),
+            lookup_array: Box::new(|_| None),
+            lookup_assoc: Box::new(|_| None),
+            command_subst: Box::new(|_| Ok(String::new())),
+            positional: &positional,
+            strict: true,
+        };
+        let expand = |token: &str| expand_token(token, &ctx).unwrap();
+        assert_eq!(expand("${FILE#*/}"), "srv/www/index.tar.gz");
+        assert_eq!(expand("${FILE##*/}"), "index.tar.gz");
+        assert_eq!(expand("${FILE%.*}"), "/srv/www/index.tar");
+        assert_eq!(expand("${FILE%%.*}"), "/srv/www/index");
+        assert_eq!(expand("${FILE%.txt}"), "/srv/www/index.tar.gz");
+        assert_eq!(expand("${#FILE}"), "21");
+        assert_eq!(expand("${#UNSET}"), "0");
+        assert_eq!(expand("${#@}-${#*}-${#}"), "3-3-3");
+    }
+
+    fn positional_ctx(positional: &[String]) -> ExpansionContext<'_> {
+        ExpansionContext {
+            lookup_var: Box::new(move |name| match name {
+                "@" | "*" => Some(positional.join(" ")),
+                _ => positional
+                    .get(name.parse::<usize>().ok()?.checked_sub(1)?)
+                    .cloned(),
+            }),
+            lookup_array: Box::new(|_| None),
+            lookup_assoc: Box::new(|_| None),
+            command_subst: Box::new(|_| Ok(String::new())),
+            positional,
+            strict: true,
+        }
+    }
+
+    fn expand_words(line: &str, ctx: &ExpansionContext<'_>) -> Vec<String> {
+        let tokens = crate::parse::parse_line(line).unwrap();
+        let words = expand_tokens(tokens, ctx).unwrap();
+        words.iter().map(|word| strip_markers(word)).collect()
+    }
+
+    #[test]
+    fn quoted_at_keeps_one_word_per_parameter() {
+        let positional = ["a".to_string(), "b c".to_string(), String::new()];
+        let ctx = positional_ctx(&positional);
+        assert_eq!(expand_words(r#"echo "$@""#, &ctx), ["echo", "a", "b c", ""]);
+        assert_eq!(expand_words(r#"echo "$*""#, &ctx), ["echo", "a b c "]);
+        assert_eq!(expand_words("echo $@", &ctx), ["echo", "a", "b", "c"]);
+        assert_eq!(expand_words("echo $*", &ctx), ["echo", "a", "b", "c"]);
+        assert_eq!(
+            expand_words(r#"echo x"$@"y"#, &ctx),
+            ["echo", "xa", "b c", "y"]
+        );
+        assert_eq!(expand_words(r#"echo "$2""#, &ctx), ["echo", "b c"]);
+        assert_eq!(
+            expand_token(r#""$@""#, &ctx).unwrap(),
+            expand_token(r#""$*""#, &ctx).unwrap()
+        );
+
+        let ctx = positional_ctx(&[]);
+        assert_eq!(expand_words(r#"echo "$@""#, &ctx), ["echo"]);
+        assert_eq!(expand_words(r#"echo x"$@""#, &ctx), ["echo", "x"]);
+        assert_eq!(expand_words(r#"echo "$*""#, &ctx), ["echo", ""]);
+    }
+
     #[test]
     fn expand_array_index() {
         let ctx = ctx_with_array("arr", vec!["a".into(), "b".into(), "c".into()]);
@@ -1120,6 +1810,46 @@ mod tests {
         });
     }
 
+    #[test]
+    fn expand_indirect_references() {
+        let arr = vec!["zero".to_string(), "one".to_string()];
+        let ctx = ExpansionContext {
+            lookup_var: Box::new(|name| match name {
+                "REF" => Some("TARGET".to_string()),
+                "TARGET" => Some("hello".to_string()),
+                "POS" => Some("1".to_string()),
+                "1" => Some("first".to_string()),
+                "ELEM" => Some("arr[1]".to_string()),
+                "BAD" => Some("not a name".to_string()),
+                _ => None,
+            }),
+            lookup_array: Box::new(move |name| (name == "arr").then(|| arr.clone())),
+            lookup_assoc: Box::new(|_| None),
+            command_subst: Box::new(|_| Ok(String::new())),
+            positional: &[],
+            strict: true,
+        };
+        assert_eq!(expand_token("${!REF}", &ctx).unwrap(), "hello");
+        assert_eq!(expand_token("${!POS}", &ctx).unwrap(), "first");
+        assert_eq!(expand_token("${!ELEM}", &ctx).unwrap(), "one");
+        assert_eq!(expand_token("${!UNSET}", &ctx).unwrap(), "");
+        let err = expand_token("${!BAD}", &ctx).unwrap_err();
+        assert!(err.contains("BAD: invalid indirect expansion"), "{err}");
+    }
+
+    #[test]
+    fn expand_prefix_vars_with_at() {
+        let ctx = ctx_no_subst();
+        with_env_var("CS_AT_PREFIX_A", "1", || {
+            with_env_var("CS_AT_PREFIX_B", "2", || {
+                assert_eq!(
+                    expand_token("${!CS_AT_PREFIX_@}", &ctx).unwrap(),
+                    "CS_AT_PREFIX_A CS_AT_PREFIX_B"
+                );
+            });
+        });
+    }
+
     #[test]
     fn expand_parameter_subst_first_match() {
         let ctx = ctx_no_subst();
@@ -1137,6 +1867,40 @@ mod tests {
         assert_eq!(expand_token(&token, &ctx).unwrap(), "foo|bar");
     }
 
+    #[test]
+    fn escaped_blanks_and_globs_stay_one_literal_word() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a b"), "").unwrap();
+        std::fs::write(dir.path().join("x"), "").unwrap();
+        let root = dir.path().display();
+        let ctx = ExpansionContext {
+            lookup_var: Box::new(|name| (name == "IFS").then(|| ":".to_string())),
+            ..ctx_no_subst()
+        };
+        let line = format!(r"printf {root}/a\ b {root}/\* {root}/\? a\:b \[x]");
+        let tokens = crate::parse::parse_line(&line).unwrap();
+        let expanded = expand_globs(expand_tokens(tokens, &ctx).unwrap()).unwrap();
+        assert_eq!(
+            expanded,
+            vec![
+                "printf".to_string(),
+                format!("{root}/a b"),
+                format!("{root}/*"),
+                format!("{root}/?"),
+                "a:b".to_string(),
+                "[x]".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn escaped_subscript_assignment_stays_one_word() {
+        let ctx = ctx_no_subst();
+        let tokens = crate::parse::parse_line(r"m[x\ y]=1 printf").unwrap();
+        let expanded = expand_globs(expand_tokens(tokens, &ctx).unwrap()).unwrap();
+        assert_eq!(expanded, vec!["m[x y]=1", "printf"]);
+    }
+
     #[test]
     fn ifs_splits_unquoted_fields() {
         let ctx = ctx_no_subst();
@@ -1180,6 +1944,19 @@ mod tests {
         assert_eq!(expanded, vec!["1", "2", "3"]);
     }
 
+    #[test]
+    fn brace_expansion_cross_products_and_nesting() {
+        let ctx = ctx_no_subst();
+        let expand = |token: &str| expand_tokens(vec![token.to_string()], &ctx).unwrap();
+        assert_eq!(expand("{a,b}{1,2}"), ["a1", "a2", "b1", "b2"]);
+        assert_eq!(expand("{a,b{1,2}}"), ["a", "b1", "b2"]);
+        assert_eq!(expand("file.{txt,md}"), ["file.txt", "file.md"]);
+        assert_eq!(expand("x{}"), ["x{}"]);
+        assert_eq!(expand("{a}"), ["{a}"]);
+        assert_eq!(expand("{a,b"), ["{a,b"]);
+        assert_eq!(expand("${CS_TEST_BRACE_UNSET:-a,b}"), ["a,b"]);
+    }
+
     #[test]
     fn brace_expansion_alpha_range() {
         let ctx = ctx_no_subst();
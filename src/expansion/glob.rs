@@ -5,6 +5,8 @@ use std::path::{Path, PathBuf};
 
 use crate::parse::{strip_markers, ESCAPE_MARKER, NOGLOB_MARKER, OPERATOR_TOKEN_MARKER};
 
+use super::{ambiguous_redirect, is_file_redirect_operator, is_heredoc_operator};
+
 #[derive(Copy, Clone, Debug)]
 pub struct GlobOptions {
     pub extglob: bool,
@@ -31,11 +33,42 @@ pub fn expand_globs(tokens: Vec<String>) -> Result<Vec<String>, String> {
 
 pub fn expand_globs_with(tokens: Vec<String>, options: GlobOptions) -> Result<Vec<String>, String> {
     let mut expanded = Vec::new();
+    let mut heredoc_delimiter = false;
+    let mut redirect_target = false;
+    let mut command_pos = true;
+    // Set by a leading `noglob` (and cleared by `glob`) until the end of
+    // that command.
+    let mut noglob = false;
     for token in tokens {
-        if token.starts_with(OPERATOR_TOKEN_MARKER) {
+        // Empty arguments pass through untouched; there is nothing to match.
+        // Heredoc delimiters keep their quote markers for the parser.
+        if token.is_empty() || token.starts_with(OPERATOR_TOKEN_MARKER) || heredoc_delimiter {
+            heredoc_delimiter = is_heredoc_operator(&token);
+            redirect_target = is_file_redirect_operator(&token);
+            command_pos = is_command_delimiter(&token);
+            noglob &= !command_pos;
             expanded.push(token);
             continue;
         }
+        // `noglob cmd args...` (fish/zsh style) passes the words literally;
+        // `glob cmd args...` expands them, even inside a `noglob`.
+        if command_pos && matches!(token.as_str(), "noglob" | "glob") {
+            noglob = token == "noglob";
+            continue;
+        }
+        if noglob {
+            command_pos = false;
+            expanded.push(strip_markers(&token));
+            continue;
+        }
+        let redirect_target = std::mem::take(&mut redirect_target);
+        // `m[key]=value` before the command is an element assignment, not a
+        // bracket pattern.
+        if command_pos && is_subscript_assignment(&token) {
+            expanded.push(strip_markers(&token));
+            continue;
+        }
+        command_pos = false;
         let (pattern, has_glob) = glob_pattern(&token);
         if has_glob {
             let mut matches = Vec::new();
@@ -63,7 +96,12 @@ pub fn expand_globs_with(tokens: Vec<String>, options: GlobOptions) -> Result<Ve
                 }
                 if !options.nullglob {
                     expanded.push(strip_markers(&token));
+                } else if redirect_target {
+                    return Err(ambiguous_redirect(&strip_markers(&token)));
                 }
+            } else if redirect_target && matches.len() > 1 {
+                // A redirection opens one file; bash refuses rather than guess.
+                return Err(ambiguous_redirect(&strip_markers(&token)));
             } else {
                 matches.sort();
                 expanded.extend(matches);
@@ -84,6 +122,20 @@ pub fn expand_globs_with(tokens: Vec<String>, options: GlobOptions) -> Result<Ve
     Ok(expanded)
 }
 
+fn is_command_delimiter(token: &str) -> bool {
+    let op = token.trim_start_matches(OPERATOR_TOKEN_MARKER);
+    matches!(op, "|" | "||" | "&&" | ";" | "&")
+}
+
+fn is_subscript_assignment(token: &str) -> bool {
+    let Some((left, _)) = token.split_once('=') else {
+        return false;
+    };
+    left.strip_suffix(']')
+        .and_then(|left| left.split_once('['))
+        .is_some_and(|(name, key)| !key.is_empty() && crate::utils::is_valid_var_name(name))
+}
+
 pub fn glob_pattern(token: &str) -> (String, bool) {
     let mut pattern = String::new();
     let mut has_glob = false;
@@ -378,6 +430,75 @@ mod tests {
     use proptest::prelude::*;
     use tempfile::tempdir;
 
+    #[test]
+    fn subscript_assignments_are_not_globbed() {
+        let escaped = format!("m[a{ESCAPE_MARKER} c]=w");
+        let expanded = expand_globs(vec![escaped, "echo".to_string()]).unwrap();
+        assert_eq!(expanded, vec!["m[a c]=w", "echo"]);
+        assert!(is_subscript_assignment("m[k.1]=v"));
+        assert!(!is_subscript_assignment("1m[k]=v"));
+        assert!(!is_subscript_assignment("m[]=v"));
+    }
+
+    #[test]
+    fn redirect_targets_must_glob_to_one_file() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("a.log"), "").unwrap();
+        let redirect = format!("{OPERATOR_TOKEN_MARKER}>");
+        let single = format!("{}/a.*", dir.path().display());
+        let expanded = expand_globs(vec![redirect.clone(), single]).unwrap();
+        assert_eq!(expanded[1], dir.path().join("a.log").display().to_string());
+
+        std::fs::write(dir.path().join("b.log"), "").unwrap();
+        let many = format!("{}/*.log", dir.path().display());
+        assert_eq!(
+            expand_globs(vec![redirect, many.clone()]).unwrap_err(),
+            format!("{many}: ambiguous redirect")
+        );
+        // The same glob as an ordinary argument still expands to both files.
+        assert_eq!(expand_globs(vec![many]).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn noglob_prefix_keeps_one_command_literal() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "").unwrap();
+        let pattern = format!("{}/*.rs", dir.path().display());
+        let tokens = vec![
+            "noglob".to_string(),
+            "echo".to_string(),
+            pattern.clone(),
+            format!("{OPERATOR_TOKEN_MARKER};"),
+            "echo".to_string(),
+            pattern.clone(),
+        ];
+        let expanded = expand_globs(tokens).unwrap();
+        assert_eq!(expanded[..2], ["echo".to_string(), pattern]);
+        assert_eq!(expanded[4], dir.path().join("a.rs").display().to_string());
+        // Only a leading `noglob` is a prefix.
+        let words = vec!["echo".to_string(), "noglob".to_string()];
+        assert_eq!(expand_globs(words.clone()).unwrap(), words);
+    }
+
+    #[test]
+    fn glob_prefix_expands_even_after_noglob() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "").unwrap();
+        let pattern = format!("{}/*.rs", dir.path().display());
+        let file = dir.path().join("a.rs").display().to_string();
+        let words = |prefixes: &[&str]| {
+            let mut words: Vec<String> = prefixes.iter().map(|word| word.to_string()).collect();
+            words.extend(["echo".to_string(), pattern.clone()]);
+            expand_globs(words).unwrap()
+        };
+        assert_eq!(words(&["glob"]), ["echo".to_string(), file.clone()]);
+        assert_eq!(words(&["noglob", "glob"]), ["echo".to_string(), file]);
+        assert_eq!(
+            words(&["glob", "noglob"]),
+            ["echo".to_string(), pattern.clone()]
+        );
+    }
+
     #[test]
     fn expand_globs_matches_and_sorts() {
         let dir = tempdir().unwrap();
@@ -1,17 +1,21 @@
 use std::env;
+use std::fs;
 use std::io;
 use std::sync::{
     atomic::AtomicI32,
     Arc,
 };
 
-use crate::builtins::{execute_builtin_substitution_capture, is_builtin_enabled_map};
+use crate::builtins::{
+    execute_builtin_substitution_capture, is_builtin_enabled_map, without_bypass_prefix,
+};
 use crate::execution::{builtin_pipe_capture, run_pipeline_capture, SandboxConfig};
-use crate::expansion::{expand_globs_with, expand_tokens, ExpansionContext};
+use crate::expansion::{expand_globs_with, expand_tokens, CommandSubst, ExpansionContext};
 use crate::expansion::GlobOptions;
 use crate::io_helpers::normalize_command_output;
 use crate::parse::{
-    parse_line, parse_line_lenient, split_pipeline, split_sequence, SeqOp, SeqSegment,
+    parse_line, parse_line_lenient, split_pipeline, split_sequence, strip_markers, CommandSpec,
+    SeqOp, SeqSegment, OPERATOR_TOKEN_MARKER,
 };
 use crate::process_subst::{apply_process_subst, FdGuard, ProcessSubstResult};
 
@@ -24,6 +28,7 @@ fn execute_command_substitution(
     assoc_arrays: std::collections::HashMap<String, std::collections::HashMap<String, String>>,
     builtin_enabled: std::collections::HashMap<String, bool>,
     glob_options: GlobOptions,
+    specials: SpecialParams,
     strict: bool,
 ) -> Result<String, String> {
     let tokens = if strict {
@@ -43,8 +48,12 @@ fn execute_command_substitution(
         builtin_enabled.clone(),
         glob_options,
         &[],
+        specials,
         strict,
     );
+    if let Some(target) = file_read_target(&tokens) {
+        return read_file_substitution(target, &ctx, glob_options);
+    }
     let (segments, _fd_guard) = expand_and_split_tokens(
         tokens,
         &ctx,
@@ -55,6 +64,7 @@ fn execute_command_substitution(
         assoc_arrays.clone(),
         builtin_enabled.clone(),
         glob_options,
+        specials,
         strict,
     )?;
     if segments.is_empty() {
@@ -66,11 +76,38 @@ fn execute_command_substitution(
         trace,
         &sandbox,
         builtin_enabled,
-        "background jobs not allowed in command substitution",
-        "command substitution failed",
+        specials.physical,
+        "command substitution",
     )
 }
 
+/// The redirection target when a substitution is nothing but `< file`.
+fn file_read_target(tokens: &[String]) -> Option<&String> {
+    match tokens {
+        [operator, target]
+            if operator.strip_prefix(OPERATOR_TOKEN_MARKER) == Some("<")
+                && !target.starts_with(OPERATOR_TOKEN_MARKER) =>
+        {
+            Some(target)
+        }
+        _ => None,
+    }
+}
+
+// `$(< file)` reads the file itself instead of running `cat`, so it is
+// cheaper and works without `cat` on PATH.
+fn read_file_substitution(
+    target: &str,
+    ctx: &ExpansionContext<'_>,
+    glob_options: GlobOptions,
+) -> Result<String, String> {
+    let words = expand_globs_with(expand_tokens(vec![target.to_string()], ctx)?, glob_options)?;
+    let [path] = words.as_slice() else {
+        return Err(format!("{}: ambiguous redirect", strip_markers(target)));
+    };
+    fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))
+}
+
 pub(crate) fn execute_tokens_capture(
     tokens: Vec<String>,
     fg_pgid: Arc<AtomicI32>,
@@ -80,6 +117,7 @@ pub(crate) fn execute_tokens_capture(
     assoc_arrays: std::collections::HashMap<String, std::collections::HashMap<String, String>>,
     builtin_enabled: std::collections::HashMap<String, bool>,
     glob_options: GlobOptions,
+    specials: SpecialParams,
     strict: bool,
 ) -> Result<String, String> {
     // Capture mode forbids background jobs to keep substitutions deterministic.
@@ -92,6 +130,7 @@ pub(crate) fn execute_tokens_capture(
         builtin_enabled.clone(),
         glob_options,
         &[],
+        specials,
         strict,
     );
     let (segments, _fd_guard) = expand_and_split_tokens(
@@ -104,6 +143,7 @@ pub(crate) fn execute_tokens_capture(
         assoc_arrays.clone(),
         builtin_enabled.clone(),
         glob_options,
+        specials,
         strict,
     )?;
     if segments.is_empty() {
@@ -115,9 +155,10 @@ pub(crate) fn execute_tokens_capture(
         trace,
         &sandbox,
         builtin_enabled,
-        "background jobs not allowed in prompt function",
-        "prompt function failed",
+        specials.physical,
+        "prompt function",
     )
+    .map(normalize_command_output)
 }
 
 fn expand_and_split_tokens(
@@ -130,6 +171,7 @@ fn expand_and_split_tokens(
     assoc_arrays: std::collections::HashMap<String, std::collections::HashMap<String, String>>,
     builtin_enabled: std::collections::HashMap<String, bool>,
     glob_options: GlobOptions,
+    specials: SpecialParams,
     strict: bool,
 ) -> Result<(Vec<SeqSegment>, FdGuard), String> {
     let expanded = expand_tokens(tokens, ctx)?;
@@ -145,6 +187,7 @@ fn expand_and_split_tokens(
         assoc_arrays,
         builtin_enabled,
         glob_options,
+        specials,
         strict,
     )
     .map_err(|err| err.to_string())?;
@@ -163,8 +206,10 @@ fn execute_segments_capture(
     trace: bool,
     sandbox: &SandboxConfig,
     builtin_enabled: std::collections::HashMap<String, bool>,
-    background_error: &str,
-    failure_context: &str,
+    physical: bool,
+    // What is running, for error messages: "command substitution" or
+    // "prompt function".
+    context: &str,
 ) -> Result<String, String> {
     let mut output = String::new();
     let mut last_status = 0;
@@ -180,14 +225,15 @@ fn execute_segments_capture(
         }
         let (pipeline, background) = split_pipeline(segment.tokens)?;
         if background {
-            return Err(background_error.to_string());
+            return Err(format!("background jobs not allowed in {context}"));
         }
+        let pipeline: Vec<CommandSpec> = pipeline.into_iter().map(without_bypass_prefix).collect();
         let has_builtin = pipeline
             .iter()
             .any(|cmd| is_builtin_enabled_map(&builtin_enabled, cmd.args.first().map(String::as_str)));
         if has_builtin {
             if pipeline.len() == 1 {
-                let result = execute_builtin_substitution_capture(&pipeline[0], None)?;
+                let result = execute_builtin_substitution_capture(&pipeline[0], None, physical)?;
                 output.push_str(&result.output);
                 last_status = result.status_code;
                 continue;
@@ -196,24 +242,43 @@ fn execute_segments_capture(
                 &pipeline,
                 |cmd| is_builtin_enabled_map(&builtin_enabled, cmd.args.first().map(String::as_str)),
                 |cmd, stdin| {
-                    execute_builtin_substitution_capture(cmd, stdin)
+                    execute_builtin_substitution_capture(cmd, stdin, physical)
                         .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
                 },
                 trace,
                 sandbox,
             )
-            .map_err(|err| format!("{failure_context}: {err}"))?;
+            .map_err(|err| format!("{context} failed: {err}"))?;
             output.push_str(&result.output);
             last_status = result.status_code;
             continue;
         }
         let result = run_pipeline_capture(pipeline.as_slice(), fg_pgid, trace, sandbox)
-            .map_err(|err| format!("{failure_context}: {err}"))?;
+            .map_err(|err| format!("{context} failed: {err}"))?;
         output.push_str(&result.output);
         last_status = result.status_code;
     }
 
-    Ok(normalize_command_output(output))
+    Ok(output)
+}
+
+/// Wraps a runner that returns the raw stdout of `$(inner)` into the
+/// `command_subst` hook, trimming trailing newlines the way substitution does.
+/// Tests pass a canned runner here instead of spawning processes.
+pub(crate) fn command_subst_hook<'a, F>(run: F) -> CommandSubst<'a>
+where
+    F: Fn(&str) -> Result<String, String> + 'a,
+{
+    Box::new(move |inner| run(inner).map(normalize_command_output))
+}
+
+/// The shell state behind `$?` and `$!`, copied when a context is built,
+/// along with `set -o physical` for a `pwd` run inside a substitution.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct SpecialParams {
+    pub(crate) last_status: i32,
+    pub(crate) last_background_pid: Option<i32>,
+    pub(crate) physical: bool,
 }
 
 pub(crate) fn build_expansion_context<'a>(
@@ -225,6 +290,7 @@ pub(crate) fn build_expansion_context<'a>(
     builtin_enabled: std::collections::HashMap<String, bool>,
     glob_options: GlobOptions,
     positional: &'a [String],
+    specials: SpecialParams,
     strict: bool,
 ) -> ExpansionContext<'a> {
     let arrays_for_lookup = arrays.clone();
@@ -242,15 +308,20 @@ pub(crate) fn build_expansion_context<'a>(
             }
             match name {
                 "#" => Some(positional.len().to_string()),
+                "?" => Some(specials.last_status.to_string()),
+                "$" => Some(std::process::id().to_string()),
+                "!" => specials.last_background_pid.map(|pid| pid.to_string()),
                 "*" => Some(positional.join(" ")),
-                "@" => Some(positional.join(" ")), // for now, same as *
+                // Unquoted, `$@` splits like `$*`; `"$@"` is handled by
+                // expand_tokens, which keeps one word per parameter.
+                "@" => Some(positional.join(" ")),
                 _ => env::var(name).ok(),
             }
         }),
         lookup_array: Box::new(move |name| arrays_for_lookup.get(name).cloned()),
         lookup_assoc: Box::new(move |name| assoc_for_lookup.get(name).cloned()),
         // Boxed closure allows swapping implementations in tests or future shells.
-        command_subst: Box::new(move |inner| {
+        command_subst: command_subst_hook(move |inner| {
             execute_command_substitution(
                 inner,
                 &fg_pgid,
@@ -260,6 +331,7 @@ pub(crate) fn build_expansion_context<'a>(
                 assoc_for_subst.clone(),
                 builtins_for_subst.clone(),
                 glob_options,
+                specials,
                 strict,
             )
         }),
@@ -267,3 +339,77 @@ pub(crate) fn build_expansion_context<'a>(
         strict,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expansion::{expand_globs, glob_pattern};
+    use crate::parse::strip_markers;
+
+    // Stands in for running `inner`: `echo` joins its (already expanded)
+    // arguments, `lines` prints several lines, anything else prints nothing.
+    fn canned_run(inner: &str) -> Result<String, String> {
+        let args = expand_tokens(parse_line(inner)?, &canned_ctx())?;
+        let args: Vec<String> = args.iter().map(|arg| strip_markers(arg)).collect();
+        match args.first().map(String::as_str) {
+            Some("echo") => Ok(format!("{}\n", args[1..].join(" "))),
+            Some("lines") => Ok("first\r\nsecond\n\n".to_string()),
+            Some("star") => Ok("*\n".to_string()),
+            _ => Ok(String::new()),
+        }
+    }
+
+    fn canned_ctx() -> ExpansionContext<'static> {
+        ExpansionContext {
+            lookup_var: Box::new(|name| (name == "WHO").then(|| "world".to_string())),
+            lookup_array: Box::new(|_| None),
+            lookup_assoc: Box::new(|_| None),
+            command_subst: command_subst_hook(canned_run),
+            positional: &[],
+            strict: true,
+        }
+    }
+
+    fn expand_line(line: &str) -> Vec<String> {
+        let tokens = expand_tokens(parse_line(line).unwrap(), &canned_ctx()).unwrap();
+        tokens.iter().map(|token| strip_markers(token)).collect()
+    }
+
+    #[test]
+    fn injected_substitution_expands_without_processes() {
+        assert_eq!(
+            expand_line("echo $(echo hi $WHO)"),
+            vec!["echo", "hi", "world"]
+        );
+        assert_eq!(
+            expand_line("echo \"$(echo hi $WHO)\""),
+            vec!["echo", "hi world"]
+        );
+        assert_eq!(expand_line("echo x$(missing)y"), vec!["echo", "xy"]);
+    }
+
+    #[test]
+    fn injected_substitution_nests_and_trims_lines() {
+        assert_eq!(
+            expand_line("echo \"$(echo a $(echo b $(echo c)))\""),
+            vec!["echo", "a b c"]
+        );
+        assert_eq!(
+            expand_line("echo $(lines)"),
+            vec!["echo", "first", "second"]
+        );
+        assert_eq!(
+            expand_line("echo \"$(lines)\""),
+            vec!["echo", "first second"]
+        );
+    }
+
+    #[test]
+    fn quoted_substitution_output_is_not_globbed() {
+        let ctx = canned_ctx();
+        let quoted = expand_tokens(parse_line("echo \"$(star)\"").unwrap(), &ctx).unwrap();
+        assert_eq!(expand_globs(quoted).unwrap(), vec!["echo", "*"]);
+        let bare = expand_tokens(parse_line("echo $(star)").unwrap(), &ctx).unwrap();
+        assert!(glob_pattern(&bare[1]).1);
+    }
+}
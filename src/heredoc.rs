@@ -5,11 +5,17 @@ use crate::completion::LineHelper;
 use crate::io_helpers::read_heredoc;
 use crate::parse::CommandSpec;
 
-pub fn fill_heredocs(
+/// Reads each pending heredoc body; `expand` runs on bodies whose delimiter
+/// was not quoted.
+pub fn fill_heredocs<F>(
     pipeline: &mut [CommandSpec],
     interactive: bool,
     editor: &mut Editor<LineHelper, DefaultHistory>,
-) -> Result<(), String> {
+    expand: F,
+) -> Result<(), String>
+where
+    F: Fn(&str) -> Result<String, String>,
+{
     let mut editor = if interactive { Some(editor) } else { None };
     for cmd in pipeline.iter_mut() {
         let Some(ref mut heredoc) = cmd.heredoc else {
@@ -18,7 +24,20 @@ pub fn fill_heredocs(
         if heredoc.content.is_some() {
             continue;
         }
-        let content = read_heredoc(editor.as_deref_mut(), interactive, &heredoc.delimiter)?;
+        let content = match heredoc.body.clone() {
+            Some(body) => body,
+            None => read_heredoc(
+                editor.as_deref_mut(),
+                interactive,
+                &heredoc.delimiter,
+                heredoc.strip_tabs,
+            )?,
+        };
+        let content = if heredoc.quoted {
+            content
+        } else {
+            expand(&content)?
+        };
         heredoc.content = Some(content);
     }
     Ok(())
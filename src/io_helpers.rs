@@ -1,4 +1,4 @@
-use std::io;
+use std::io::{self, BufRead};
 
 use rustyline::error::ReadlineError;
 use rustyline::history::DefaultHistory;
@@ -19,21 +19,74 @@ pub fn read_input_line(
             Err(ReadlineError::Eof) => return Ok(None),
             Err(err) => return Err(io::Error::other(err)),
         };
+        reject_nul(&line)?;
         Ok(Some(line))
     } else {
-        let mut line = String::new();
-        let bytes = io::stdin().read_line(&mut line)?;
+        let mut buf = Vec::new();
+        let bytes = io::stdin().lock().read_until(b'\n', &mut buf)?;
         if bytes == 0 {
             return Ok(None);
         }
-        Ok(Some(line))
+        decode_input_line(buf).map(Some)
+    }
+}
+
+// Consecutive EOFs swallowed by `set -o ignoreeof` when IGNOREEOF is unset
+// or not a number, matching bash.
+const DEFAULT_IGNOREEOF: u32 = 10;
+
+/// How many consecutive EOFs it takes to leave the shell, or `None` when the
+/// first one exits. An IGNOREEOF variable wins over `set -o ignoreeof`.
+pub fn ignoreeof_limit(option: bool, var: Option<&str>) -> Option<u32> {
+    match var {
+        Some(value) => Some(value.trim().parse().unwrap_or(DEFAULT_IGNOREEOF)),
+        None => option.then_some(DEFAULT_IGNOREEOF),
+    }
+}
+
+/// Decides whether the `consecutive`-th EOF in a row ends the shell.
+/// Scripts and piped input always stop at the end of their input.
+pub fn eof_should_exit(interactive: bool, limit: Option<u32>, consecutive: u32) -> bool {
+    !interactive || limit.is_none_or(|limit| consecutive >= limit)
+}
+
+pub fn decode_input_line(bytes: Vec<u8>) -> io::Result<String> {
+    // Reject instead of converting lossily: replacement characters parse oddly
+    // and arguments containing NUL could never reach execvp anyway.
+    let line = String::from_utf8(bytes).map_err(|err| {
+        let pos = err.utf8_error().valid_up_to();
+        invalid_input(
+            ShellError::new(ErrorKind::Parse, "input is not valid UTF-8")
+                .with_position(pos)
+                .with_context(format!("invalid byte at offset {pos}")),
+        )
+    })?;
+    reject_nul(&line)?;
+    Ok(line)
+}
+
+fn reject_nul(line: &str) -> io::Result<()> {
+    if let Some(pos) = line.find('\0') {
+        return Err(invalid_input(
+            ShellError::new(ErrorKind::Parse, "input contains a NUL byte")
+                .with_position(pos)
+                .with_context(format!("NUL byte at offset {pos}")),
+        ));
     }
+    Ok(())
 }
 
+fn invalid_input(err: ShellError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+/// Reads heredoc lines up to `delimiter`. With `strip_tabs` (`<<-`), leading
+/// tabs are removed from every line first, so the delimiter may be indented.
 pub fn read_heredoc(
     mut editor: Option<&mut Editor<LineHelper, DefaultHistory>>,
     interactive: bool,
     delimiter: &str,
+    strip_tabs: bool,
 ) -> Result<String, String> {
     // Heredoc content is collected after parsing to allow interactive input.
     let mut content = String::new();
@@ -49,10 +102,11 @@ pub fn read_heredoc(
             };
             match editor.readline("> ") {
                 Ok(line) => {
+                    let line = heredoc_line(&line, strip_tabs);
                     if line == delimiter {
                         break;
                     }
-                    content.push_str(&line);
+                    content.push_str(line);
                     content.push('\n');
                 }
                 Err(ReadlineError::Eof) => {
@@ -97,7 +151,7 @@ pub fn read_heredoc(
                 .with_context("Heredoc was not terminated with expected delimiter")
                 .to_string());
             }
-            let trimmed = line.trim_end_matches(&['\n', '\r'][..]);
+            let trimmed = heredoc_line(line.trim_end_matches(&['\n', '\r'][..]), strip_tabs);
             if trimmed == delimiter {
                 break;
             }
@@ -108,8 +162,35 @@ pub fn read_heredoc(
     Ok(content)
 }
 
+fn heredoc_line(line: &str, strip_tabs: bool) -> &str {
+    if strip_tabs {
+        line.trim_start_matches('\t')
+    } else {
+        line
+    }
+}
+
 pub fn normalize_command_output(output: String) -> String {
     let trimmed = output.trim_end_matches(&['\n', '\r'][..]);
     let normalized = trimmed.replace('\n', " ");
     normalized.replace('\r', "")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignoreeof_counts_consecutive_eofs() {
+        assert_eq!(ignoreeof_limit(false, None), None);
+        assert_eq!(ignoreeof_limit(true, None), Some(10));
+        assert_eq!(ignoreeof_limit(false, Some("3")), Some(3));
+        assert_eq!(ignoreeof_limit(true, Some("lots")), Some(10));
+
+        assert!(eof_should_exit(true, None, 1));
+        assert!(!eof_should_exit(true, Some(3), 1));
+        assert!(!eof_should_exit(true, Some(3), 2));
+        assert!(eof_should_exit(true, Some(3), 3));
+        assert!(eof_should_exit(false, Some(3), 1));
+    }
+}
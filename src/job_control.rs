@@ -1,21 +1,30 @@
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
 use std::io;
 use std::os::fd::AsFd;
 use std::os::unix::process::CommandExt;
 use std::process::Command;
 use std::sync::{
-    atomic::{AtomicI32, Ordering},
+    atomic::{AtomicBool, AtomicI32, Ordering},
     Arc,
 };
+use std::thread;
+use std::time::Duration;
 
 use log::{debug, warn};
 use nix::sys::signal::{
     kill, sigaction, sigprocmask, SaFlags, SigAction, SigHandler, SigSet, SigmaskHow, Signal,
 };
-use nix::sys::termios::{tcgetattr, tcsetattr, SetArg, Termios};
+use nix::sys::termios::{tcgetattr, tcgetsid, tcsetattr, SetArg, Termios};
 use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
-use nix::unistd::{setpgid, tcsetpgrp, Pid};
+use nix::unistd::{getsid, isatty, setpgid, tcsetpgrp, Pid};
+
+mod audit;
+mod notify;
+
+pub use audit::{audit_job_event, JobEvent, JobEventKind};
+pub use notify::{watch_jobs_while, JobNotices};
 
 pub fn set_process_group(command: &mut Command, fg_pgid: &Arc<AtomicI32>) {
     let fg_pgid = Arc::clone(fg_pgid);
@@ -60,7 +69,42 @@ fn reset_ignored_signals() -> io::Result<()> {
     Ok(())
 }
 
+// Cleared at startup when stdin is not the controlling terminal of the
+// shell's session; foreground handoff is then skipped entirely.
+static MANAGE_TERMINAL: AtomicBool = AtomicBool::new(true);
+
+/// Whether foreground jobs should be handed the terminal. Only a stdin tty
+/// that controls the shell's own session counts: a tty on stderr alone, or a
+/// tty left over from another session, cannot be passed to `tcsetpgrp`.
+pub fn should_manage_terminal(
+    stdin_is_tty: bool,
+    tty_session: Option<i32>,
+    own_session: Option<i32>,
+) -> bool {
+    stdin_is_tty && tty_session.is_some() && tty_session == own_session
+}
+
+/// Probes stdin once, after the session is set up, and records the decision.
+pub fn init_terminal_management() -> bool {
+    let stdin = std::io::stdin();
+    let fd = stdin.as_fd();
+    let stdin_is_tty = isatty(libc::STDIN_FILENO).unwrap_or(false);
+    let tty_session = tcgetsid(fd).ok().map(Pid::as_raw);
+    let own_session = getsid(None).ok().map(Pid::as_raw);
+    let manage = should_manage_terminal(stdin_is_tty, tty_session, own_session);
+    MANAGE_TERMINAL.store(manage, Ordering::SeqCst);
+    debug!("tty event=init manage={manage}");
+    manage
+}
+
+fn manages_terminal() -> bool {
+    MANAGE_TERMINAL.load(Ordering::SeqCst)
+}
+
 pub fn set_terminal_foreground(pgid: i32) -> io::Result<()> {
+    if !manages_terminal() {
+        return Ok(());
+    }
     let stdin = std::io::stdin();
     let fd = stdin.as_fd();
     match tcsetpgrp(fd, Pid::from_raw(pgid)) {
@@ -101,10 +145,12 @@ pub struct TermiosGuard {
 impl TermiosGuard {
     pub fn new() -> Self {
         Self {
-            saved: {
+            saved: if manages_terminal() {
                 let stdin = std::io::stdin();
                 let fd = stdin.as_fd();
                 tcgetattr(fd).ok()
+            } else {
+                None
             },
         }
     }
@@ -154,11 +200,15 @@ impl Drop for TerminalGuard {
 
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub enum JobStatus {
-    // Stopped jobs must be resumed explicitly via fg/bg.
+    // Stopped jobs must be resumed explicitly via fg/bg. The status code
+    // (128 + the stop signal) is kept for `jobs`.
     Running,
-    Stopped,
+    Stopped(i32),
 }
 
+// How often `wait -n` checks the job table for a finished job.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 pub struct Job {
     pub id: usize,
     // Job id is user-facing; a job can map to multiple process IDs.
@@ -167,6 +217,8 @@ pub struct Job {
     pub count: usize,
     pub command: String,
     pub status: JobStatus,
+    // Bumped on every state change; ranks the %+ and %- jobs.
+    pub recency: u64,
 }
 
 pub struct Coprocess {
@@ -180,8 +232,8 @@ pub struct Coprocess {
 }
 
 pub enum JobPoll {
-    Done,
-    Stopped,
+    Done(i32),
+    Stopped(i32),
     Running,
     NoChange,
 }
@@ -197,6 +249,39 @@ pub struct WaitResult {
     pub pipefail_status: Option<i32>,
 }
 
+/// The exit status a process killed or stopped by `sig` reports.
+pub fn signal_status(sig: i32) -> i32 {
+    128 + sig
+}
+
+/// The signal behind a `128 + n` status, if `n` is a known signal.
+pub fn status_signal(code: i32) -> Option<i32> {
+    if code <= 128 {
+        return None;
+    }
+    Signal::try_from(code - 128).ok().map(|sig| sig as i32)
+}
+
+/// `SIGTSTP`-style name for a signal number, or `"SIG?"` when unknown.
+pub fn signal_name(sig: i32) -> &'static str {
+    Signal::try_from(sig).map_or("SIG?", Signal::as_str)
+}
+
+/// How `jobs` and job notices describe a status: `Done`, `Exit 3`,
+/// `Stopped (SIGTSTP)` or `Terminated (SIGINT)`.
+pub fn status_to_description(code: i32) -> String {
+    match status_signal(code) {
+        Some(sig) if is_stop_signal(sig) => format!("Stopped ({})", signal_name(sig)),
+        Some(sig) => format!("Terminated ({})", signal_name(sig)),
+        None if code == 0 => "Done".to_string(),
+        None => format!("Exit {code}"),
+    }
+}
+
+fn is_stop_signal(sig: i32) -> bool {
+    [libc::SIGTSTP, libc::SIGSTOP, libc::SIGTTIN, libc::SIGTTOU].contains(&sig)
+}
+
 pub struct BringJobResult {
     #[allow(dead_code)]
     pub outcome: WaitOutcome,
@@ -222,60 +307,125 @@ pub fn add_job_with_status(
         count,
         command: command.trim_end_matches('&').trim().to_string(),
         status,
+        recency: 0,
     });
+    touch_job(jobs, id);
     id
 }
 
+/// Marks job `id` as the most recently changed one.
+pub fn touch_job(jobs: &mut [Job], id: usize) {
+    let next = jobs.iter().map(|job| job.recency).max().unwrap_or(0) + 1;
+    if let Some(job) = jobs.iter_mut().find(|job| job.id == id) {
+        job.recency = next;
+    }
+}
+
+/// Ids of the current (`%+`) and previous (`%-`) jobs. As in bash, stopped
+/// jobs outrank running ones, then the most recently changed job wins.
+pub fn current_and_previous(jobs: &[Job]) -> (Option<usize>, Option<usize>) {
+    let mut ranked: Vec<&Job> = jobs.iter().collect();
+    ranked.sort_by_key(|job| {
+        let stopped = matches!(job.status, JobStatus::Stopped(_));
+        std::cmp::Reverse((stopped, job.recency))
+    });
+    (
+        ranked.first().map(|job| job.id),
+        ranked.get(1).map(|job| job.id),
+    )
+}
+
 pub fn list_jobs(jobs: &[Job], output: &mut String) {
     use std::fmt::Write;
 
     if jobs.is_empty() {
         return;
     }
+    let (current, previous) = current_and_previous(jobs);
     for job in jobs {
         let status = match job.status {
-            JobStatus::Running => "Running",
-            JobStatus::Stopped => "Stopped",
+            JobStatus::Running => "Running".to_string(),
+            JobStatus::Stopped(code) => status_to_description(code),
+        };
+        let mark = if Some(job.id) == current {
+            '+'
+        } else if Some(job.id) == previous {
+            '-'
+        } else {
+            ' '
         };
-        let _ = writeln!(output, "[{}] {status} {}", job.id, job.command);
+        let _ = writeln!(output, "[{}]{mark} {status} {}", job.id, job.command);
     }
 }
 
-pub fn parse_job_id(arg: Option<&String>) -> io::Result<Option<usize>> {
-    if let Some(value) = arg {
-        let trimmed = value.strip_prefix('%').unwrap_or(value);
-        trimmed
-            .parse::<usize>()
-            .map(Some)
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "job id must be a number"))
-    } else {
-        Ok(None)
-    }
+#[derive(Debug, PartialEq, Eq)]
+pub enum JobError {
+    NotFound(String),
+    Ambiguous(String),
 }
 
-pub fn take_job(jobs: &mut Vec<Job>, id: Option<usize>) -> Option<Job> {
-    if jobs.is_empty() {
-        return None;
-    }
-    match id {
-        Some(id) => {
-            let index = jobs.iter().position(|job| job.id == id)?;
-            Some(jobs.remove(index))
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JobError::NotFound(spec) => write!(f, "{spec}: no such job"),
+            JobError::Ambiguous(spec) => write!(f, "{spec}: ambiguous job spec"),
         }
-        None => jobs.pop(),
     }
 }
 
-pub fn find_job(jobs: &mut [Job], id: Option<usize>) -> Option<&mut Job> {
-    if jobs.is_empty() {
-        return None;
-    }
-    match id {
-        Some(id) => jobs.iter_mut().find(|job| job.id == id),
-        None => jobs.last_mut(),
+/// Looks up a job spec the way bash does: `%N` by id, `%+`/`%%`/`%` and
+/// `%-` for the current and previous jobs, `%name` by command prefix and
+/// `%?text` by substring. A bare number is a process ID, falling back to
+/// a job id so `fg 2` keeps working.
+pub fn resolve_job<'a>(jobs: &'a [Job], spec: &str) -> Result<&'a Job, JobError> {
+    let (current, previous) = current_and_previous(jobs);
+    let by_id = |id: Option<usize>, name: &str| {
+        id.and_then(|id| jobs.iter().find(|job| job.id == id))
+            .ok_or_else(|| JobError::NotFound(name.to_string()))
+    };
+    let Some(rest) = spec.strip_prefix('%') else {
+        return match spec {
+            "+" => by_id(current, "current"),
+            "-" => by_id(previous, "previous"),
+            _ => match spec.parse::<usize>() {
+                Ok(number) => jobs
+                    .iter()
+                    .find(|job| job.pgid as usize == number || job.last_pid as usize == number)
+                    .map_or_else(|| by_id(Some(number), spec), Ok),
+                Err(_) => Err(JobError::NotFound(spec.to_string())),
+            },
+        };
+    };
+    match rest {
+        "" | "+" | "%" => by_id(current, "current"),
+        "-" => by_id(previous, "previous"),
+        _ => {
+            if let Ok(id) = rest.parse::<usize>() {
+                return by_id(Some(id), spec);
+            }
+            let wanted = |job: &&Job| match rest.strip_prefix('?') {
+                Some(text) => job.command.contains(text),
+                None => job.command.starts_with(rest),
+            };
+            let mut matches: Vec<&Job> = jobs.iter().filter(wanted).collect();
+            match matches.len() {
+                0 => Err(JobError::NotFound(spec.to_string())),
+                1 => Ok(matches.remove(0)),
+                _ => Err(JobError::Ambiguous(spec.to_string())),
+            }
+        }
     }
 }
 
+pub fn take_job(jobs: &mut Vec<Job>, id: usize) -> Option<Job> {
+    let index = jobs.iter().position(|job| job.id == id)?;
+    Some(jobs.remove(index))
+}
+
+pub fn find_job(jobs: &mut [Job], id: usize) -> Option<&mut Job> {
+    jobs.iter_mut().find(|job| job.id == id)
+}
+
 pub fn bring_job_foreground(
     mut job: Job,
     fg_pgid: &Arc<AtomicI32>,
@@ -298,7 +448,8 @@ pub fn bring_job_foreground(
             stopped_job: None,
         }),
         WaitOutcome::Stopped => {
-            job.status = JobStatus::Stopped;
+            let code = outcome.status_code.unwrap_or(signal_status(libc::SIGTSTP));
+            job.status = JobStatus::Stopped(code);
             Ok(BringJobResult {
                 outcome: WaitOutcome::Stopped,
                 status_code: outcome.status_code,
@@ -310,6 +461,7 @@ pub fn bring_job_foreground(
 
 pub fn continue_job(pgid: i32) -> io::Result<()> {
     debug!("job event=cont pgid={}", pgid);
+    audit_job_event(JobEvent::new(JobEventKind::Continue, pgid));
     kill(Pid::from_raw(-pgid), Signal::SIGCONT).map_err(|err| io::Error::other(err.to_string()))
 }
 
@@ -337,6 +489,7 @@ pub fn wait_for_process_group(
                 }
                 exited += 1;
                 if expected_count > 0 && exited >= expected_count {
+                    audit_job_event(JobEvent::new(JobEventKind::Done, pgid).status(status_code));
                     return Ok(WaitResult {
                         outcome: WaitOutcome::Exited,
                         status_code: Some(status_code.unwrap_or(0)),
@@ -350,11 +503,12 @@ pub fn wait_for_process_group(
                     pgid, pid, sig as i32
                 );
                 if pid.as_raw() == last_pid {
-                    status_code = Some(128 + sig as i32);
+                    status_code = Some(signal_status(sig as i32));
                 }
-                pipefail_status = Some(128 + sig as i32);
+                pipefail_status = Some(signal_status(sig as i32));
                 exited += 1;
                 if expected_count > 0 && exited >= expected_count {
+                    audit_job_event(JobEvent::new(JobEventKind::Done, pgid).status(status_code));
                     return Ok(WaitResult {
                         outcome: WaitOutcome::Exited,
                         status_code: Some(status_code.unwrap_or(0)),
@@ -363,12 +517,13 @@ pub fn wait_for_process_group(
                 }
                 continue;
             }
-            Ok(WaitStatus::Stopped(_, _)) => {
+            Ok(WaitStatus::Stopped(_, sig)) => {
                 debug!("job event=stopped pgid={}", pgid);
+                audit_job_event(JobEvent::new(JobEventKind::Stop, pgid));
                 let _ = kill(Pid::from_raw(-pgid), Signal::SIGTSTP);
                 return Ok(WaitResult {
                     outcome: WaitOutcome::Stopped,
-                    status_code: None,
+                    status_code: Some(signal_status(sig as i32)),
                     pipefail_status: None,
                 });
             }
@@ -382,6 +537,7 @@ pub fn wait_for_process_group(
             }
         }
     }
+    audit_job_event(JobEvent::new(JobEventKind::Done, pgid).status(status_code));
     Ok(WaitResult {
         outcome: WaitOutcome::Exited,
         status_code: Some(status_code.unwrap_or(0)),
@@ -389,52 +545,118 @@ pub fn wait_for_process_group(
     })
 }
 
-pub fn reap_jobs(jobs: &mut Vec<Job>) {
+pub fn reap_jobs(jobs: &mut Vec<Job>, notices: &mut JobNotices) {
     // Reaping runs outside the signal handler to keep handlers async-safe.
     let mut index = 0;
     while index < jobs.len() {
-        let pgid = jobs[index].pgid;
-        match poll_job_status(pgid) {
-            JobPoll::Done => {
-                let job = jobs.remove(index);
-                debug!("job event=reap done pgid={} id={}", job.pgid, job.id);
-                println!("[{}] Done {}", job.id, job.command);
+        match poll_job(jobs, index, notices) {
+            Some((job, code)) => {
+                let status = status_to_description(code);
+                notices.push(format!("[{}] {status} {}", job.id, job.command));
             }
-            JobPoll::Stopped => {
-                if jobs[index].status != JobStatus::Stopped {
-                    jobs[index].status = JobStatus::Stopped;
-                    debug!(
-                        "job event=reap stopped pgid={} id={}",
-                        jobs[index].pgid, jobs[index].id
-                    );
-                    println!("[{}] Stopped {}", jobs[index].id, jobs[index].command);
-                }
-                index += 1;
+            None => index += 1,
+        }
+    }
+}
+
+/// What `wait_for_next_job` saw first.
+pub enum NextJob {
+    /// A job finished and left the table, with its exit status.
+    Done(i32),
+    /// A running job stopped and stays in the table, with 128+signal.
+    Stopped(i32),
+}
+
+/// Blocks until one job finishes or stops, for `wait`, and returns what
+/// happened; it is not announced like a reaped job. Jobs that resume
+/// meanwhile are updated as `reap_jobs` would. `None` once no running job
+/// is left (stopped jobs would never finish) or `interrupted` is set.
+pub fn wait_for_next_job(
+    jobs: &mut Vec<Job>,
+    notices: &mut JobNotices,
+    interrupted: &AtomicBool,
+) -> Option<NextJob> {
+    loop {
+        let mut index = 0;
+        while index < jobs.len() {
+            let was_stopped = matches!(jobs[index].status, JobStatus::Stopped(_));
+            if let Some((_, code)) = poll_job(jobs, index, notices) {
+                return Some(NextJob::Done(code));
             }
-            JobPoll::Running => {
-                if jobs[index].status != JobStatus::Running {
-                    jobs[index].status = JobStatus::Running;
-                    debug!(
-                        "job event=reap running pgid={} id={}",
-                        jobs[index].pgid, jobs[index].id
-                    );
-                    println!("[{}] Running {}", jobs[index].id, jobs[index].command);
+            if let JobStatus::Stopped(code) = jobs[index].status {
+                if !was_stopped {
+                    return Some(NextJob::Stopped(code));
                 }
-                index += 1;
             }
-            JobPoll::NoChange => {
-                index += 1;
+            index += 1;
+        }
+        let running = jobs.iter().any(|job| job.status == JobStatus::Running);
+        if !running || interrupted.load(Ordering::SeqCst) {
+            return None;
+        }
+        thread::sleep(WAIT_POLL_INTERVAL);
+    }
+}
+
+/// Checks job `index` once without blocking. A finished job is removed and
+/// returned with its exit status; a stopped or resumed one is updated in
+/// place with a notice.
+fn poll_job(jobs: &mut Vec<Job>, index: usize, notices: &mut JobNotices) -> Option<(Job, i32)> {
+    match poll_job_status(jobs[index].pgid) {
+        JobPoll::Done(code) => {
+            let job = jobs.remove(index);
+            debug!("job event=reap done pgid={} id={}", job.pgid, job.id);
+            audit_job_event(JobEvent::new(JobEventKind::Done, job.pgid));
+            return Some((job, code));
+        }
+        JobPoll::Stopped(code) => {
+            if !matches!(jobs[index].status, JobStatus::Stopped(_)) {
+                jobs[index].status = JobStatus::Stopped(code);
+                debug!(
+                    "job event=reap stopped pgid={} id={}",
+                    jobs[index].pgid, jobs[index].id
+                );
+                audit_job_event(JobEvent::new(JobEventKind::Stop, jobs[index].pgid));
+                notices.push(format!(
+                    "[{}] {} {}",
+                    jobs[index].id,
+                    status_to_description(code),
+                    jobs[index].command
+                ));
+                let id = jobs[index].id;
+                touch_job(jobs, id);
             }
         }
+        JobPoll::Running => {
+            if jobs[index].status != JobStatus::Running {
+                jobs[index].status = JobStatus::Running;
+                debug!(
+                    "job event=reap running pgid={} id={}",
+                    jobs[index].pgid, jobs[index].id
+                );
+                audit_job_event(JobEvent::new(JobEventKind::Continue, jobs[index].pgid));
+                notices.push(format!(
+                    "[{}] Running {}",
+                    jobs[index].id, jobs[index].command
+                ));
+                let id = jobs[index].id;
+                touch_job(jobs, id);
+            }
+        }
+        JobPoll::NoChange => {}
     }
+    None
 }
 
-pub fn reap_coprocs(coprocs: &mut HashMap<String, Coprocess>) -> Vec<String> {
+pub fn reap_coprocs(
+    coprocs: &mut HashMap<String, Coprocess>,
+    notices: &mut JobNotices,
+) -> Vec<String> {
     let mut removed = Vec::new();
     let names: Vec<String> = coprocs.keys().cloned().collect();
     for name in names {
         let done = if let Some(proc) = coprocs.get(&name) {
-            matches!(poll_job_status(proc.pgid), JobPoll::Done)
+            matches!(poll_job_status(proc.pgid), JobPoll::Done(_))
         } else {
             false
         };
@@ -442,7 +664,7 @@ pub fn reap_coprocs(coprocs: &mut HashMap<String, Coprocess>) -> Vec<String> {
             if let Some(proc) = coprocs.remove(&name) {
                 let _ = fs::remove_file(&proc.in_path);
                 let _ = fs::remove_file(&proc.out_path);
-                println!("[coproc {name}] Done {}", proc.command);
+                notices.push(format!("[coproc {name}] Done {}", proc.command));
             }
             std::env::remove_var(format!("{name}_PID"));
             removed.push(name);
@@ -458,14 +680,19 @@ fn poll_job_status(pgid: i32) -> JobPoll {
             Pid::from_raw(-pgid),
             Some(WaitPidFlag::WNOHANG | WaitPidFlag::WUNTRACED | WaitPidFlag::WCONTINUED),
         ) {
-            Ok(WaitStatus::Exited(_, _)) | Ok(WaitStatus::Signaled(_, _, _)) => {
+            Ok(WaitStatus::Exited(_, code)) => {
+                debug!("job event=poll done pgid={}", pgid);
+                outcome = JobPoll::Done(code);
+                continue;
+            }
+            Ok(WaitStatus::Signaled(_, sig, _)) => {
                 debug!("job event=poll done pgid={}", pgid);
-                outcome = JobPoll::Done;
+                outcome = JobPoll::Done(signal_status(sig as i32));
                 continue;
             }
-            Ok(WaitStatus::Stopped(_, _)) => {
+            Ok(WaitStatus::Stopped(_, sig)) => {
                 debug!("job event=poll stopped pgid={}", pgid);
-                outcome = JobPoll::Stopped;
+                outcome = JobPoll::Stopped(signal_status(sig as i32));
                 break;
             }
             Ok(WaitStatus::Continued(_)) => {
@@ -478,7 +705,7 @@ fn poll_job_status(pgid: i32) -> JobPoll {
             Err(nix::errno::Errno::EINTR) => continue,
             Err(nix::errno::Errno::ECHILD) => {
                 if matches!(outcome, JobPoll::NoChange) {
-                    outcome = JobPoll::Done;
+                    outcome = JobPoll::Done(0);
                 }
                 break;
             }
@@ -560,4 +787,135 @@ mod tests {
         let _ = kill(Pid::from_raw(-leader), Signal::SIGTERM);
         reap_process_group(leader);
     }
+
+    #[test]
+    fn signals_map_to_names_and_statuses_to_descriptions() {
+        assert_eq!(signal_name(libc::SIGINT), "SIGINT");
+        assert_eq!(signal_name(libc::SIGKILL), "SIGKILL");
+        assert_eq!(signal_name(libc::SIGTSTP), "SIGTSTP");
+        assert_eq!(signal_name(0), "SIG?");
+        assert_eq!(signal_status(libc::SIGTSTP), 128 + libc::SIGTSTP);
+        assert_eq!(status_signal(130), Some(libc::SIGINT));
+        assert_eq!(status_signal(128), None);
+        assert_eq!(status_signal(255), None);
+
+        assert_eq!(status_to_description(0), "Done");
+        assert_eq!(status_to_description(3), "Exit 3");
+        assert_eq!(
+            status_to_description(signal_status(libc::SIGTSTP)),
+            "Stopped (SIGTSTP)"
+        );
+        assert_eq!(
+            status_to_description(signal_status(libc::SIGSTOP)),
+            "Stopped (SIGSTOP)"
+        );
+        assert_eq!(status_to_description(130), "Terminated (SIGINT)");
+        assert_eq!(status_to_description(255), "Exit 255");
+    }
+
+    #[test]
+    fn wait_for_process_group_reports_the_stop_signal() {
+        let pid = spawn_in_own_pgid("sh", &["-c", "kill -STOP $$; sleep 1"]).unwrap();
+        let result = wait_for_process_group(pid, 1, pid).unwrap();
+        assert!(matches!(result.outcome, WaitOutcome::Stopped));
+        // The group is re-stopped with SIGTSTP, but the first stop is reported.
+        assert_eq!(result.status_code, Some(signal_status(libc::SIGSTOP)));
+        continue_job(pid).unwrap();
+        let _ = kill(Pid::from_raw(-pid), Signal::SIGTERM);
+        reap_process_group(pid);
+    }
+
+    #[test]
+    fn resolve_job_handles_every_spec_form() {
+        let mut jobs = Vec::new();
+        let mut next = 1;
+        let running = JobStatus::Running;
+        add_job_with_status(&mut jobs, &mut next, 100, 101, 2, "yes | cat &", running);
+        add_job_with_status(&mut jobs, &mut next, 200, 200, 1, "sleep 20 &", running);
+        add_job_with_status(&mut jobs, &mut next, 300, 300, 1, "vim notes.txt", running);
+        let id = |spec| resolve_job(&jobs, spec).map(|job| job.id);
+
+        assert_eq!(id("%2"), Ok(2));
+        assert_eq!(id("%+"), Ok(3));
+        assert_eq!(id("%"), Ok(3));
+        assert_eq!(id("+"), Ok(3));
+        assert_eq!(id("%-"), Ok(2));
+        assert_eq!(id("%vim"), Ok(3));
+        assert_eq!(id("%?cat"), Ok(1));
+        assert_eq!(id("%?notes"), Ok(3));
+        assert_eq!(id("200"), Ok(2));
+        assert_eq!(id("101"), Ok(1));
+        assert_eq!(id("1"), Ok(1));
+    }
+
+    #[test]
+    fn resolve_job_reports_missing_and_ambiguous_specs() {
+        let mut jobs = Vec::new();
+        let mut next = 1;
+        assert_eq!(
+            resolve_job(&jobs, "%+").map(|job| job.id),
+            Err(JobError::NotFound("current".to_string()))
+        );
+        assert_eq!(
+            resolve_job(&jobs, "%-").map(|job| job.id),
+            Err(JobError::NotFound("previous".to_string()))
+        );
+        let running = JobStatus::Running;
+        add_job_with_status(&mut jobs, &mut next, 100, 100, 1, "sleep 10 &", running);
+        add_job_with_status(&mut jobs, &mut next, 200, 200, 1, "sleep 20 &", running);
+        let err = |spec| resolve_job(&jobs, spec).map(|job| job.id).unwrap_err();
+
+        assert_eq!(err("%7"), JobError::NotFound("%7".to_string()));
+        assert_eq!(err("%make"), JobError::NotFound("%make".to_string()));
+        assert_eq!(err("%?vim"), JobError::NotFound("%?vim".to_string()));
+        assert_eq!(err("abc"), JobError::NotFound("abc".to_string()));
+        assert_eq!(err("%sleep"), JobError::Ambiguous("%sleep".to_string()));
+        assert_eq!(err("%?sleep"), JobError::Ambiguous("%?sleep".to_string()));
+        assert_eq!(err("%7").to_string(), "%7: no such job");
+        assert_eq!(err("%sleep").to_string(), "%sleep: ambiguous job spec");
+    }
+
+    #[test]
+    fn terminal_is_managed_only_for_own_controlling_tty() {
+        assert!(should_manage_terminal(true, Some(42), Some(42)));
+        // stdin is a pipe (stderr may still be a tty).
+        assert!(!should_manage_terminal(false, None, Some(42)));
+        // stdin is a tty, but it controls another session.
+        assert!(!should_manage_terminal(true, Some(7), Some(42)));
+        // stdin is a tty with no session attached, e.g. after setsid.
+        assert!(!should_manage_terminal(true, None, Some(42)));
+        assert!(!should_manage_terminal(true, None, None));
+    }
+
+    #[test]
+    fn current_and_previous_follow_job_changes() {
+        let mut jobs = Vec::new();
+        let mut next = 1;
+        let running = JobStatus::Running;
+        add_job_with_status(&mut jobs, &mut next, 10, 10, 1, "sleep 10 &", running);
+        add_job_with_status(&mut jobs, &mut next, 20, 20, 1, "sleep 20 &", running);
+        add_job_with_status(&mut jobs, &mut next, 30, 30, 1, "sleep 30 &", running);
+        assert_eq!(current_and_previous(&jobs), (Some(3), Some(2)));
+
+        // A stopped job outranks newer running ones.
+        jobs[0].status = JobStatus::Stopped(signal_status(libc::SIGTSTP));
+        touch_job(&mut jobs, 1);
+        assert_eq!(current_and_previous(&jobs), (Some(1), Some(3)));
+
+        let mut output = String::new();
+        list_jobs(&jobs, &mut output);
+        assert_eq!(
+            output,
+            "[1]+ Stopped (SIGTSTP) sleep 10\n[2]  Running sleep 20\n[3]- Running sleep 30\n"
+        );
+
+        jobs.retain(|job| job.id != 1);
+        assert_eq!(current_and_previous(&jobs), (Some(3), Some(2)));
+        assert_eq!(resolve_job(&jobs, "%-").map(|job| job.id), Ok(2));
+        assert_eq!(resolve_job(&jobs, "%%").map(|job| job.id), Ok(3));
+        assert_eq!(resolve_job(&jobs, "%+").map(|job| job.id), Ok(3));
+
+        jobs.retain(|job| job.id != 3);
+        assert_eq!(current_and_previous(&jobs), (Some(2), None));
+    }
 }
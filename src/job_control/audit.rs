@@ -0,0 +1,111 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::warn;
+
+// Path of the optional job lifecycle log; unset disables auditing.
+pub const JOB_LOG_ENV: &str = "MINISHELL_JOB_LOG";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobEventKind {
+    Spawn,
+    Stop,
+    Continue,
+    Done,
+}
+
+impl JobEventKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            JobEventKind::Spawn => "spawn",
+            JobEventKind::Stop => "stop",
+            JobEventKind::Continue => "continue",
+            JobEventKind::Done => "done",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct JobEvent {
+    pub kind: JobEventKind,
+    pub pgid: i32,
+    pub pid: Option<i32>,
+    pub status: Option<i32>,
+}
+
+impl JobEvent {
+    pub fn new(kind: JobEventKind, pgid: i32) -> Self {
+        Self {
+            kind,
+            pgid,
+            pid: None,
+            status: None,
+        }
+    }
+
+    pub fn pid(mut self, pid: i32) -> Self {
+        self.pid = Some(pid);
+        self
+    }
+
+    pub fn status(mut self, status: Option<i32>) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+/// One `key=value` line per event so the log can be grepped or split on spaces.
+pub fn format_job_event(event: &JobEvent, timestamp_ms: u128) -> String {
+    let mut line = format!(
+        "ts={timestamp_ms} event={} pgid={}",
+        event.kind.as_str(),
+        event.pgid
+    );
+    if let Some(pid) = event.pid {
+        line.push_str(&format!(" pid={pid}"));
+    }
+    if let Some(status) = event.status {
+        line.push_str(&format!(" status={status}"));
+    }
+    line
+}
+
+pub fn audit_job_event(event: JobEvent) {
+    let Some(path) = env::var_os(JOB_LOG_ENV) else {
+        return;
+    };
+    let timestamp_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or(0);
+    let line = format_job_event(&event, timestamp_ms);
+    let result = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{line}"));
+    if let Err(err) = result {
+        warn!("job event=audit error={}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_event_as_key_value_line() {
+        let event = JobEvent::new(JobEventKind::Spawn, 4242).pid(4243);
+        assert_eq!(
+            format_job_event(&event, 1_700_000_000_123),
+            "ts=1700000000123 event=spawn pgid=4242 pid=4243"
+        );
+        let event = JobEvent::new(JobEventKind::Done, 4242).status(Some(130));
+        assert_eq!(
+            format_job_event(&event, 5),
+            "ts=5 event=done pgid=4242 status=130"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use rustyline::ExternalPrinter;
+
+use super::{reap_jobs, Job};
+
+// How often the watcher checks for SIGCHLD while a line is being edited.
+const WATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// `[n] Done` style lines gathered while reaping, so they can be written in
+/// one piece instead of interleaving with whatever is on the terminal.
+#[derive(Default)]
+pub struct JobNotices {
+    lines: Vec<String>,
+}
+
+impl JobNotices {
+    pub fn push(&mut self, line: String) {
+        self.lines.push(line);
+    }
+
+    /// Everything collected so far as a single block, one notice per line,
+    /// leaving the buffer empty.
+    pub fn take(&mut self) -> Option<String> {
+        if self.lines.is_empty() {
+            return None;
+        }
+        let mut block = self.lines.drain(..).collect::<Vec<_>>().join("\n");
+        block.push('\n');
+        Some(block)
+    }
+}
+
+/// Runs `read` (the line editor) while a helper thread reaps background jobs
+/// as SIGCHLD arrives. Their notices go through `printer`, which prints
+/// above the prompt and redraws the line being edited.
+pub fn watch_jobs_while<T>(
+    jobs: &mut Vec<Job>,
+    sigchld: &AtomicBool,
+    printer: &mut (dyn ExternalPrinter + Send),
+    read: impl FnOnce() -> T,
+) -> T {
+    let done = AtomicBool::new(false);
+    let (result, reaped) = thread::scope(|scope| {
+        let watcher = scope.spawn(|| {
+            let mut reaped = false;
+            let mut notices = JobNotices::default();
+            while !done.load(Ordering::SeqCst) {
+                if sigchld.swap(false, Ordering::SeqCst) {
+                    reaped = true;
+                    reap_jobs(jobs, &mut notices);
+                    if let Some(block) = notices.take() {
+                        let _ = printer.print(block);
+                    }
+                }
+                thread::park_timeout(WATCH_INTERVAL);
+            }
+            reaped
+        });
+        let result = read();
+        done.store(true, Ordering::SeqCst);
+        watcher.thread().unpark();
+        (result, watcher.join().unwrap_or(false))
+    });
+    // Coprocesses are still reaped by the main loop, which keys off the flag.
+    if reaped {
+        sigchld.store(true, Ordering::SeqCst);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    use crate::job_control::{set_process_group_explicit, JobStatus};
+
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl ExternalPrinter for Recorder {
+        fn print(&mut self, msg: String) -> rustyline::Result<()> {
+            self.0.lock().unwrap().push(msg);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn notices_are_emitted_as_one_block() {
+        let mut notices = JobNotices::default();
+        assert_eq!(notices.take(), None);
+        notices.push("[1] Done sleep 1".to_string());
+        notices.push("[2] Stopped vim".to_string());
+        assert_eq!(
+            notices.take().as_deref(),
+            Some("[1] Done sleep 1\n[2] Stopped vim\n")
+        );
+        assert_eq!(notices.take(), None);
+    }
+
+    #[test]
+    fn finished_jobs_are_reported_while_reading() {
+        let mut cmd = Command::new("true");
+        set_process_group_explicit(&mut cmd, 0);
+        let pid = cmd.spawn().unwrap().id() as i32;
+        let mut jobs = vec![Job {
+            id: 1,
+            pgid: pid,
+            last_pid: pid,
+            count: 1,
+            command: "true &".to_string(),
+            status: JobStatus::Running,
+            recency: 0,
+        }];
+        let printed = Arc::new(Mutex::new(Vec::new()));
+        let mut printer = Recorder(Arc::clone(&printed));
+        let sigchld = AtomicBool::new(true);
+
+        let seen = watch_jobs_while(&mut jobs, &sigchld, &mut printer, || {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while printed.lock().unwrap().is_empty() && Instant::now() < deadline {
+                // Stands in for the SIGCHLD handler, in case the first poll
+                // ran before the child exited.
+                sigchld.store(true, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(10));
+            }
+            printed.lock().unwrap().clone()
+        });
+        assert_eq!(seen, vec!["[1] Done true &\n".to_string()]);
+        assert!(jobs.is_empty());
+        assert!(sigchld.load(Ordering::SeqCst));
+    }
+}
@@ -3,6 +3,8 @@
 //! This crate exposes a minimal API so fuzz targets and unit tests can link
 //! only parsing and expansion logic without pulling in interactive deps.
 
+#[cfg(feature = "expansion")]
+mod arithmetic;
 #[cfg(feature = "expansion")]
 mod expansion;
 mod error;
@@ -11,7 +13,8 @@ mod utils;
 
 pub use parse::{CommandSpec, SeqOp, SeqSegment};
 
-/// Tokenize a shell command line into raw tokens.
+/// Tokenize a shell command line into raw tokens, using the same tokenizer
+/// the shell itself runs.
 pub fn parse_tokens(input: &str) -> Result<Vec<String>, String> {
     parse::parse_line(input)
 }
@@ -36,7 +39,9 @@ pub fn fuzz_parse_bytes(data: &[u8]) {
 }
 
 #[cfg(feature = "expansion")]
-pub use expansion::{expand_token, expand_tokens, ExpansionContext};
+pub use expansion::{
+    expand_globs, expand_heredoc, expand_token, expand_tokens, glob_pattern, ExpansionContext,
+};
 
 /// Fuzz helper for parser+expansion targets.
 #[cfg(feature = "expansion")]
@@ -56,3 +61,61 @@ pub fn fuzz_expand_bytes(data: &[u8]) {
         }
     }
 }
+
+/// Fuzz helper for the whole parse -> expand -> glob -> pipeline chain.
+///
+/// Nothing is executed: command substitution expands to a fixed string and
+/// every variable has a value, so `${...}` modifiers do real work. Glob
+/// patterns are rooted in `glob_root` and absolute or `..` patterns are kept
+/// literal, so matching never looks outside that directory.
+#[cfg(feature = "expansion")]
+pub fn fuzz_pipeline_bytes(data: &[u8], glob_root: &std::path::Path) {
+    let input = String::from_utf8_lossy(data);
+    let ctx = ExpansionContext {
+        lookup_var: Box::new(|name| Some(format!("{name}.val ue"))),
+        lookup_array: Box::new(|_| Some(vec!["a".to_string(), "b c".to_string()])),
+        lookup_assoc: Box::new(|_| None),
+        command_subst: Box::new(|_| Ok("subst".to_string())),
+        positional: &[],
+        strict: true,
+    };
+    let Ok(tokens) = parse::parse_line(&input) else {
+        return;
+    };
+    let Ok(tokens) = expansion::expand_tokens(tokens, &ctx) else {
+        return;
+    };
+    let tokens = confine_globs(tokens, glob_root);
+    let Ok(tokens) = expansion::expand_globs(tokens) else {
+        return;
+    };
+    if let Ok(segments) = parse::split_sequence(tokens) {
+        for segment in segments {
+            let _ = parse::split_pipeline(segment.tokens);
+        }
+    }
+}
+
+#[cfg(feature = "expansion")]
+fn confine_globs(tokens: Vec<String>, root: &std::path::Path) -> Vec<String> {
+    let literal = |text: &str| -> String {
+        text.chars()
+            .flat_map(|ch| [parse::NOGLOB_MARKER, ch])
+            .collect()
+    };
+    let mut after_heredoc = false;
+    let mut confined = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        let skip = token.starts_with(parse::OPERATOR_TOKEN_MARKER) || after_heredoc;
+        after_heredoc = expansion::is_heredoc_operator(&token);
+        let (pattern, has_glob) = expansion::glob_pattern(&token);
+        if skip || !has_glob {
+            confined.push(token);
+        } else if pattern.starts_with('/') || pattern.contains("..") {
+            confined.push(literal(&pattern));
+        } else {
+            confined.push(format!("{}/{token}", literal(&root.display().to_string())));
+        }
+    }
+    confined
+}
@@ -0,0 +1,121 @@
+use std::env;
+use std::io::Write;
+
+use log::Level;
+
+pub fn init_logging() {
+    let env = env_logger::Env::default().filter_or("MINISHELL_LOG", "info");
+    let mut builder = env_logger::Builder::from_env(env);
+    if env::var("MINISHELL_LOG_FORMAT").is_ok_and(|value| value.eq_ignore_ascii_case("json")) {
+        builder.format(|buf, record| {
+            let timestamp = buf.timestamp_millis().to_string();
+            let message = record.args().to_string();
+            writeln!(
+                buf,
+                "{}",
+                format_json_record(&timestamp, record.level(), record.target(), &message)
+            )
+        });
+    } else {
+        builder.format_timestamp_millis();
+    }
+    let _ = builder.try_init();
+}
+
+/// Renders one log record as a single-line JSON object. `key=value` pairs in
+/// the message (as used by the `job event=...` logs) are lifted into `fields`.
+pub fn format_json_record(timestamp: &str, level: Level, target: &str, message: &str) -> String {
+    let mut out = String::from("{");
+    push_json_pair(&mut out, "timestamp", timestamp);
+    out.push(',');
+    push_json_pair(&mut out, "level", level.as_str());
+    out.push(',');
+    push_json_pair(&mut out, "target", target);
+    out.push(',');
+    push_json_pair(&mut out, "message", message);
+    let fields = message_fields(message);
+    if !fields.is_empty() {
+        out.push_str(",\"fields\":{");
+        for (idx, (key, value)) in fields.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            push_json_pair(&mut out, key, value);
+        }
+        out.push('}');
+    }
+    out.push('}');
+    out
+}
+
+fn message_fields(message: &str) -> Vec<(&str, &str)> {
+    let mut fields: Vec<(&str, &str)> = Vec::new();
+    for word in message.split_whitespace() {
+        let Some((key, value)) = word.split_once('=') else {
+            continue;
+        };
+        let valid_key = !key.is_empty()
+            && key
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-');
+        if valid_key && !fields.iter().any(|(existing, _)| *existing == key) {
+            fields.push((key, value));
+        }
+    }
+    fields
+}
+
+pub(crate) fn push_json_pair(out: &mut String, key: &str, value: &str) {
+    push_json_string(out, key);
+    out.push(':');
+    push_json_string(out, value);
+}
+
+fn push_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_record_includes_message_fields() {
+        let line = format_json_record(
+            "2024-05-01T12:00:00.123Z",
+            Level::Debug,
+            "better_shell::job_control",
+            "job event=wait pgid=42 expected_count=2 last_pid=43",
+        );
+        assert_eq!(
+            line,
+            "{\"timestamp\":\"2024-05-01T12:00:00.123Z\",\"level\":\"DEBUG\",\
+\"target\":\"better_shell::job_control\",\
+\"message\":\"job event=wait pgid=42 expected_count=2 last_pid=43\",\
+\"fields\":{\"event\":\"wait\",\"pgid\":\"42\",\"expected_count\":\"2\",\"last_pid\":\"43\"}}"
+        );
+    }
+
+    #[test]
+    fn json_record_escapes_special_characters() {
+        let line = format_json_record("t", Level::Warn, "shell", "bad \"quote\"\\\n\u{1}");
+        assert_eq!(
+            line,
+            "{\"timestamp\":\"t\",\"level\":\"WARN\",\"target\":\"shell\",\
+\"message\":\"bad \\\"quote\\\"\\\\\\n\\u0001\"}"
+        );
+        assert!(!line.contains('\n'));
+    }
+}
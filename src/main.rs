@@ -1,7 +1,8 @@
 use nix::unistd::isatty;
-use signal_hook::consts::signal::SIGCHLD;
+use signal_hook::consts::signal::{SIGCHLD, SIGINT};
 use signal_hook::flag;
 use std::env;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 mod builtins;
@@ -17,17 +18,23 @@ mod arithmetic;
 mod heredoc;
 mod io_helpers;
 mod job_control;
+mod logging;
 mod parse;
 mod process_subst;
 mod prompt;
 mod repl;
+mod safeglob;
 mod signals;
+mod timing;
 mod utils;
 
 pub(crate) use expansion_runner::build_expansion_context;
-pub(crate) use repl::{execute_segment, run_return_trap, trace_tokens, ShellState};
+pub(crate) use repl::{execute_segment, expand_segment, run_return_trap, trace_tokens, ShellState};
 
-use repl::{init_state, run_once};
+use config::startup_files;
+use job_control::init_terminal_management;
+use logging::init_logging;
+use repl::{init_state, run_once, run_profile_files};
 use signals::{init_session, install_signal_handlers};
 
 use parse::{parse_sandbox_value, SandboxDirective};
@@ -35,10 +42,17 @@ use parse::{parse_sandbox_value, SandboxDirective};
 fn main() {
     init_logging();
     let mut trace = false;
+    let mut posix = false;
     let mut sandbox_override: Option<SandboxDirective> = None;
+    // Like other shells, a leading '-' in argv[0] marks a login shell.
+    let mut login = env::args().next().is_some_and(|arg0| arg0.starts_with('-'));
     for arg in env::args().skip(1) {
         if arg == "-x" {
             trace = true;
+        } else if arg == "-l" || arg == "--login" {
+            login = true;
+        } else if arg == "--posix" {
+            posix = true;
         } else if arg == "--sandbox" {
             sandbox_override = Some(SandboxDirective::Enable);
         } else if arg == "--no-sandbox" {
@@ -65,17 +79,26 @@ fn main() {
             return;
         }
     };
-    let mut state = match init_state(trace, interactive, shell_pgid, sandbox_override) {
+    init_terminal_management();
+    let home = env::var("HOME").ok().map(PathBuf::from);
+    let startup = startup_files(home.as_deref(), login, interactive);
+    let mut state = match init_state(trace, interactive, shell_pgid, sandbox_override, &startup) {
         Ok(state) => state,
         Err(err) => {
             eprintln!("error: {err}");
             return;
         }
     };
+    state.posix = posix;
     if let Err(err) = flag::register(SIGCHLD, Arc::clone(&state.sigchld_flag)) {
         eprintln!("error: {err}");
         return;
     }
+    if let Err(err) = flag::register(SIGINT, Arc::clone(&state.interrupt_flag)) {
+        eprintln!("error: {err}");
+        return;
+    }
+    run_profile_files(&mut state, &startup.profiles);
 
     loop {
         if let Err(err) = run_once(&mut state) {
@@ -83,10 +106,3 @@ fn main() {
         }
     }
 }
-
-fn init_logging() {
-    let env = env_logger::Env::default().filter_or("MINISHELL_LOG", "info");
-    let _ = env_logger::Builder::from_env(env)
-        .format_timestamp_millis()
-        .try_init();
-}
@@ -3,20 +3,23 @@
 //! - `OPERATOR_TOKEN_MARKER` prefixes operator tokens so they survive expansion unchanged.
 //! - `NOGLOB_MARKER` tags characters that must not be globbed (from quotes/escapes).
 //! - `ESCAPE_MARKER` records escaped literals so they stay literal through expansion.
+//! - `HEREDOC_BODY_MARKER` appends a heredoc body read ahead of time to its delimiter word.
 use crate::error::{ErrorKind, ShellError};
 
 pub const OPERATOR_TOKEN_MARKER: char = '\x1e';
 pub const NOGLOB_MARKER: char = '\x1d';
 pub const ESCAPE_MARKER: char = '\x1f';
+pub const HEREDOC_BODY_MARKER: char = '\x1c';
 
 mod command_parser;
+pub(crate) mod escapes;
 mod redirection_parser;
 mod tokenizer;
 
 #[allow(unused_imports)]
 pub use command_parser::{
-    SeqOp, SeqSegment, split_pipeline, split_pipeline_lenient, split_sequence,
-    split_sequence_lenient,
+    drop_line_break_separators, SeqOp, SeqSegment, split_pipeline, split_pipeline_lenient,
+    split_sequence, split_sequence_lenient,
 };
 #[allow(unused_imports)]
 pub use tokenizer::{
@@ -37,9 +40,13 @@ pub struct OutputRedirection {
 #[derive(Debug, Clone)]
 pub struct HeredocSpec {
     pub delimiter: String,
-    #[allow(dead_code)]
+    // Any quoting in the delimiter word keeps the body literal.
     pub quoted: bool,
+    // Set by `<<-`: leading tabs come off each body line and the delimiter.
+    pub strip_tabs: bool,
     pub content: Option<String>,
+    // Raw body already read with a multi-line compound; expanded on each run.
+    pub body: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -59,6 +66,11 @@ pub struct CommandSpec {
     pub stdout: Option<OutputRedirection>,
     pub stderr: Option<OutputRedirection>,
     pub stderr_to_stdout: bool,
+    // `2>&1 >file`: stderr copied stdout before the file replaced it, so it
+    // keeps the original stdout. Only meaningful with `stderr_to_stdout`.
+    pub stderr_before_stdout: bool,
+    // `>&2`: stdout goes to the shell's stderr.
+    pub stdout_to_stderr: bool,
     pub stderr_close: bool,
     pub close_fds: Vec<i32>,
     pub sandbox: Option<SandboxDirective>,
@@ -74,11 +86,19 @@ impl CommandSpec {
             stdout: None,
             stderr: None,
             stderr_to_stdout: false,
+            stderr_before_stdout: false,
+            stdout_to_stderr: false,
             stderr_close: false,
             close_fds: Vec::new(),
             sandbox: None,
         }
     }
+
+    /// Whether stdout goes somewhere other than the pipe or terminal it
+    /// would otherwise get: to a file or, with `>&2`, to stderr.
+    pub fn redirects_stdout(&self) -> bool {
+        self.stdout.is_some() || self.stdout_to_stderr
+    }
 }
 
 impl Default for CommandSpec {
@@ -122,6 +142,64 @@ pub fn strip_markers(input: &str) -> String {
         .collect()
 }
 
+/// Renders tokens from `parse_line` back into a command line that tokenizes
+/// to the same tokens, re-quoting every character that carries a marker.
+/// Only the round-trip tests use it so far.
+#[cfg(test)]
+pub fn tokens_to_command_line(tokens: &[String]) -> String {
+    let words: Vec<String> = tokens.iter().map(|token| render_token(token)).collect();
+    words.join(" ")
+}
+
+#[cfg(test)]
+fn render_token(token: &str) -> String {
+    if let Some(op) = token.strip_prefix(OPERATOR_TOKEN_MARKER) {
+        return op.to_string();
+    }
+    if token.is_empty() {
+        return "''".to_string();
+    }
+    // The tokenizer re-derives the markers inside `(( ... ))` on its own.
+    if token.starts_with("((") && token.ends_with("))") {
+        return strip_markers(token);
+    }
+    let mut out = String::new();
+    let mut chars = token.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            ESCAPE_MARKER => match chars.next() {
+                Some('\'') => out.push_str("\\'"),
+                Some(next) => {
+                    out.push('\'');
+                    out.push(next);
+                    out.push('\'');
+                }
+                None => {}
+            },
+            NOGLOB_MARKER => match chars.next() {
+                Some(ESCAPE_MARKER) => {
+                    let escaped = match chars.next() {
+                        Some('\n') => "n".to_string(),
+                        Some('\t') => "t".to_string(),
+                        Some('\r') => "r".to_string(),
+                        Some(next) => next.to_string(),
+                        None => String::new(),
+                    };
+                    out.push_str(&format!("\"\\{escaped}\""));
+                }
+                Some(next) => {
+                    out.push('"');
+                    out.push(next);
+                    out.push('"');
+                }
+                None => {}
+            },
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 pub fn strip_all_markers(input: &str) -> String {
     let mut chars = input.chars();
@@ -139,3 +217,69 @@ pub fn strip_all_markers(input: &str) -> String {
     }
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // One piece of a word: bare text, a single- or double-quoted run, or a
+    // backslash-escaped metacharacter.
+    fn word_part() -> impl Strategy<Value = String> {
+        prop_oneof![
+            "[a-zA-Z0-9_./=:,-]{1,6}",
+            "[a-z |&;<>()*?$\"#~]{0,6}".prop_map(|text| format!("'{text}'")),
+            "[a-z |&;<>()*?'#~]{0,6}".prop_map(|text| format!("\"{text}\"")),
+            "[ |&;<>()*?$'\"\\#ntq]".prop_map(|ch| format!("\\{ch}")),
+        ]
+    }
+
+    fn word() -> impl Strategy<Value = String> {
+        prop::collection::vec(word_part(), 1..4).prop_map(|parts| parts.concat())
+    }
+
+    fn command_line() -> impl Strategy<Value = String> {
+        let separator = prop_oneof![
+            Just(" "),
+            Just(" | "),
+            Just(" && "),
+            Just(" || "),
+            Just("; "),
+            Just(" > "),
+            Just(" 2>> "),
+            Just(" <<< "),
+        ];
+        (word(), prop::collection::vec((separator, word()), 0..6)).prop_map(|(first, rest)| {
+            let mut line = first;
+            for (separator, word) in rest {
+                line.push_str(separator);
+                line.push_str(&word);
+            }
+            line
+        })
+    }
+
+    #[test]
+    fn renders_quoted_and_escaped_words() {
+        let tokens = parse_line("echo 'a b' \"c*d\" e\\;f '' ((x > 1))").unwrap();
+        let line = tokens_to_command_line(&tokens);
+        assert_eq!(parse_line(&line).unwrap(), tokens);
+    }
+
+    proptest! {
+        #[test]
+        fn parse_render_parse_round_trips(line in command_line()) {
+            let tokens = parse_line(&line).unwrap();
+            let rendered = tokens_to_command_line(&tokens);
+            prop_assert_eq!(parse_line(&rendered).unwrap(), tokens, "rendered: {}", rendered);
+        }
+
+        #[test]
+        fn strip_markers_is_idempotent(line in command_line(), raw in "\\PC{0,24}") {
+            for token in parse_line(&line).unwrap().iter().chain([&raw]) {
+                let once = strip_markers(token);
+                prop_assert_eq!(strip_markers(&once), once);
+            }
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use crate::parse::{CommandSpec, OPERATOR_TOKEN_MARKER};
+use crate::parse::{
+    CommandSpec, ESCAPE_MARKER, HEREDOC_BODY_MARKER, NOGLOB_MARKER, OPERATOR_TOKEN_MARKER,
+};
 use crate::parse::redirection_parser::apply_redirection;
 use crate::parse::redirection_parser::try_parse_sandbox_directive;
 
@@ -20,8 +22,13 @@ pub fn split_sequence(tokens: Vec<String>) -> Result<Vec<SeqSegment>, String> {
     let mut segments = Vec::new();
     let mut current = Vec::new();
     let mut next_op = SeqOp::Always;
+    let mut depth = 0usize;
 
     for token in tokens {
+        if track_group_depth(&mut depth, &current, &token) {
+            current.push(token);
+            continue;
+        }
         if let Some(stripped) = token.strip_prefix(OPERATOR_TOKEN_MARKER) {
             match stripped {
                 ";" | "&&" | "||" => {
@@ -49,6 +56,10 @@ pub fn split_sequence(tokens: Vec<String>) -> Result<Vec<SeqSegment>, String> {
     }
 
     if current.is_empty() {
+        // A trailing `;` just terminates the last command.
+        if matches!(next_op, SeqOp::Always) && !segments.is_empty() {
+            return Ok(segments);
+        }
         return Err("trailing operator".to_string());
     }
 
@@ -62,12 +73,33 @@ pub fn split_sequence(tokens: Vec<String>) -> Result<Vec<SeqSegment>, String> {
     Ok(segments)
 }
 
+/// Compound bodies and scripts are read line by line and joined with `;`,
+/// so there a `;` that opens the list or follows another `;` only stands
+/// for a line break. Drops those, which [`split_sequence`] would reject.
+#[allow(dead_code)]
+pub fn drop_line_break_separators(tokens: Vec<String>) -> Vec<String> {
+    let is_separator = |token: &str| token.strip_prefix(OPERATOR_TOKEN_MARKER) == Some(";");
+    let mut kept: Vec<String> = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        if is_separator(&token) && kept.last().is_none_or(|prev| is_separator(prev)) {
+            continue;
+        }
+        kept.push(token);
+    }
+    kept
+}
+
 pub fn split_sequence_lenient(tokens: Vec<String>) -> Vec<SeqSegment> {
     let mut segments = Vec::new();
     let mut current = Vec::new();
     let mut next_op = SeqOp::Always;
+    let mut depth = 0usize;
 
     for token in tokens {
+        if track_group_depth(&mut depth, &current, &token) {
+            current.push(token);
+            continue;
+        }
         if let Some(stripped) = token.strip_prefix(OPERATOR_TOKEN_MARKER) {
             match stripped {
                 ";" | "&&" | "||" => {
@@ -105,13 +137,39 @@ pub fn split_sequence_lenient(tokens: Vec<String>) -> Vec<SeqSegment> {
     segments
 }
 
+// Updates the `(...)`/`{...}` nesting depth for `token` and reports whether
+// the token sits inside a group, where `;`, `&&` and `||` belong to the
+// group's own command list. Openers only count in command position so a
+// literal argument such as `echo {` cannot swallow the rest of the line.
+fn track_group_depth(depth: &mut usize, current: &[String], token: &str) -> bool {
+    let command_position = current.last().is_none_or(|prev| {
+        prev.starts_with(OPERATOR_TOKEN_MARKER) || matches!(prev.as_str(), "{" | "(")
+    });
+    match token.strip_prefix(OPERATOR_TOKEN_MARKER).unwrap_or(token) {
+        "(" | "{" if command_position => *depth += 1,
+        ")" if *depth > 0 => {
+            *depth -= 1;
+            return true;
+        }
+        "}" if command_position && *depth > 0 => {
+            *depth -= 1;
+            return true;
+        }
+        _ => {}
+    }
+    *depth > 0
+}
+
+// Segments are split before expansion, so the display is the command as
+// typed: quoting markers are dropped and heredoc bodies left out.
 fn tokens_to_display(tokens: &[String]) -> String {
     let mut parts = Vec::with_capacity(tokens.len());
     for token in tokens {
         if let Some(stripped) = token.strip_prefix(OPERATOR_TOKEN_MARKER) {
             parts.push(stripped.to_string());
         } else {
-            parts.push(token.clone());
+            let word = token.split(HEREDOC_BODY_MARKER).next().unwrap_or_default();
+            parts.push(word.replace([NOGLOB_MARKER, ESCAPE_MARKER], ""));
         }
     }
     parts.join(" ")
@@ -133,8 +191,8 @@ pub fn split_pipeline(tokens: Vec<String>) -> Result<(Vec<CommandSpec>, bool), S
                     pipeline.push(current);
                     current = CommandSpec::new();
                 }
-                "<" | "<<" | "<<<" | ">" | ">>" | "&>" | "&>>"
-                | "0<" | "0<<" | "0<<<"
+                "<" | "<<" | "<<-" | "<<<" | ">" | ">>" | "&>" | "&>>"
+                | "0<" | "0<<" | "0<<-" | "0<<<"
                 | "1>" | "1>>"
                 | "2>" | "2>>" => {
                     apply_redirection(&mut current, stripped, &mut iter)?;
@@ -189,8 +247,8 @@ pub fn split_pipeline_lenient(tokens: Vec<String>) -> (Vec<CommandSpec>, bool) {
                         current = CommandSpec::new();
                     }
                 }
-                "<" | "<<" | "<<<" | ">" | ">>" | "&>" | "&>>"
-                | "0<" | "0<<" | "0<<<"
+                "<" | "<<" | "<<-" | "<<<" | ">" | ">>" | "&>" | "&>>"
+                | "0<" | "0<<" | "0<<-" | "0<<<"
                 | "1>" | "1>>"
                 | "2>" | "2>>" => {
                     let target = iter.next();
@@ -288,6 +346,52 @@ mod tests {
         assert_eq!(split_sequence(tokens).unwrap_err(), "trailing operator");
     }
 
+    #[test]
+    fn split_sequence_rejects_empty_commands() {
+        for line in ["; a", "a ; ; b", "a && ; b"] {
+            let tokens = parse_line(line).unwrap();
+            assert_eq!(
+                split_sequence(tokens).unwrap_err(),
+                "empty command",
+                "{line}"
+            );
+        }
+        let tokens = parse_line("a ; b ;").unwrap();
+        assert_eq!(split_sequence(tokens).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn line_break_separators_are_dropped_from_bodies() {
+        let tokens = drop_line_break_separators(parse_line("; a ; ; b ;").unwrap());
+        let segments = split_sequence(tokens).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[1].tokens, vec!["b"]);
+        let tokens = drop_line_break_separators(parse_line("a && ; b").unwrap());
+        assert_eq!(split_sequence(tokens).unwrap_err(), "empty command");
+    }
+
+    #[test]
+    fn split_sequence_keeps_group_operators_together() {
+        let tokens = parse_line("( a || b ) && c").unwrap();
+        let segments = split_sequence(tokens).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert!(matches!(segments[1].op, SeqOp::And));
+        assert_eq!(segments[0].display, "( a || b )");
+        assert_eq!(segments[1].tokens, vec!["c"]);
+
+        let tokens = parse_line("{ a && b ; } || c ; d").unwrap();
+        let segments = split_sequence(tokens).unwrap();
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0].display, "{ a && b ; }");
+        assert!(matches!(segments[1].op, SeqOp::Or));
+        assert!(matches!(segments[2].op, SeqOp::Always));
+
+        let tokens = parse_line("echo { ; echo }").unwrap();
+        assert_eq!(split_sequence(tokens).unwrap().len(), 2);
+        let tokens = parse_line("( a || b ) && c").unwrap();
+        assert_eq!(split_sequence_lenient(tokens).len(), 2);
+    }
+
     #[test]
     fn split_pipeline_redirects_and_background() {
         let tokens = parse_line("cmd < in > out").unwrap();
@@ -310,6 +414,35 @@ mod tests {
         let (pipeline, _) = split_pipeline(tokens).unwrap();
         assert!(pipeline[0].stderr_to_stdout);
 
+        let tokens = parse_line("cmd >out 2>&1").unwrap();
+        let (pipeline, _) = split_pipeline(tokens).unwrap();
+        assert!(pipeline[0].stderr_to_stdout && !pipeline[0].stderr_before_stdout);
+
+        let tokens = parse_line("cmd 2>&1 >out").unwrap();
+        let (pipeline, _) = split_pipeline(tokens).unwrap();
+        assert!(pipeline[0].stderr_to_stdout && pipeline[0].stderr_before_stdout);
+        assert_eq!(pipeline[0].stdout.as_ref().unwrap().path, "out");
+
+        for line in ["cmd >&2", "cmd 1>&2", "cmd >&2 2>&1"] {
+            let (pipeline, _) = split_pipeline(parse_line(line).unwrap()).unwrap();
+            assert!(pipeline[0].stdout_to_stderr, "{line}");
+            assert!(pipeline[0].stdout.is_none() && !pipeline[0].stderr_to_stdout);
+        }
+
+        let tokens = parse_line("cmd >&2 2>err").unwrap();
+        let (pipeline, _) = split_pipeline(tokens).unwrap();
+        assert!(pipeline[0].stdout_to_stderr);
+        assert_eq!(pipeline[0].stderr.as_ref().unwrap().path, "err");
+
+        let tokens = parse_line("cmd 2>err >&2").unwrap();
+        let (pipeline, _) = split_pipeline(tokens).unwrap();
+        assert!(!pipeline[0].stdout_to_stderr && pipeline[0].stderr_to_stdout);
+        assert_eq!(pipeline[0].stdout.as_ref().unwrap().path, "err");
+        assert!(pipeline[0].stderr.is_none());
+
+        assert!(split_pipeline(parse_line("cmd >out >&2").unwrap()).is_err());
+        assert!(split_pipeline(parse_line("cmd 2>&- >&2").unwrap()).is_err());
+
         let tokens = parse_line("cmd 2>&-").unwrap();
         let (pipeline, _) = split_pipeline(tokens).unwrap();
         assert!(pipeline[0].stderr_close);
@@ -345,14 +478,34 @@ mod tests {
         let tokens = parse_line("cmd > out1 > out2").unwrap();
         assert_eq!(
             split_pipeline(tokens).unwrap_err(),
-            "multiple output redirections"
+            "Redirection error: output already redirected to out1 (>)\n  \
+             hint: Keep a single target, or pipe through tee to write several files"
         );
 
-        let tokens = parse_line("cmd 2> err1 2> err2").unwrap();
+        let tokens = parse_line("cmd 2> err1 2>> err2").unwrap();
+        assert!(split_pipeline(tokens)
+            .unwrap_err()
+            .starts_with("Redirection error: stderr already redirected to err1 (>)"));
+
+        let tokens = parse_line("cmd < in1 < in2").unwrap();
         assert_eq!(
             split_pipeline(tokens).unwrap_err(),
-            "multiple stderr redirections"
+            "Redirection error: input already redirected from in1 (<)\n  \
+             hint: A command reads from one source; drop the extra <, << or <<<"
         );
+
+        let tokens = parse_line("cmd <<< word < in").unwrap();
+        assert!(split_pipeline(tokens)
+            .unwrap_err()
+            .contains("input already redirected from a here-string (<<<)"));
+    }
+
+    #[test]
+    fn split_pipeline_rejects_operator_as_redirect_target() {
+        let tokens = parse_line("cmd >< file").unwrap();
+        assert!(split_pipeline(tokens)
+            .unwrap_err()
+            .starts_with("Redirection error: expected a file name after '>', found '<'"));
     }
 
     #[test]
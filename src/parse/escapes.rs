@@ -0,0 +1,129 @@
+//! Backslash escape decoding shared by the tokenizer and `echo -e`.
+
+/// Octal escapes are spelled `\0NNN` for `echo -e` but `\NNN` for `$'...'`
+/// and `printf`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OctalStyle {
+    LeadingZero,
+    Bare,
+}
+
+/// Maps the character after a backslash to its C-style meaning.
+pub fn control_escape(ch: char) -> Option<char> {
+    match ch {
+        'n' => Some('\n'),
+        't' => Some('\t'),
+        'r' => Some('\r'),
+        'a' => Some('\x07'),
+        'b' => Some('\x08'),
+        'e' | 'E' => Some('\x1b'),
+        'f' => Some('\x0c'),
+        'v' => Some('\x0b'),
+        '\\' => Some('\\'),
+        '\'' => Some('\''),
+        '"' => Some('"'),
+        _ => None,
+    }
+}
+
+/// Decodes `\n`-style, octal, `\xHH`, `\uHHHH` and `\UHHHHHHHH` escapes.
+/// Anything that does not form a valid escape is kept literally.
+pub fn decode_escapes(input: &str, octal: OctalStyle) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+        let Some(&next) = chars.peek() else {
+            out.push('\\');
+            break;
+        };
+        let decoded = match next {
+            'x' => numeric_escape(&mut chars, 16, 2),
+            'u' => numeric_escape(&mut chars, 16, 4),
+            'U' => numeric_escape(&mut chars, 16, 8),
+            '0' if octal == OctalStyle::LeadingZero => numeric_escape(&mut chars, 8, 3),
+            '0'..='7' if octal == OctalStyle::Bare => {
+                let value = take_digits(&mut chars, 8, 3);
+                char::from_u32(value & 0xff)
+            }
+            _ => control_escape(next).inspect(|_| {
+                chars.next();
+            }),
+        };
+        match decoded {
+            Some(decoded) => out.push(decoded),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+// Consumes the introducer (`x`, `u`, `U` or `0`) and up to `max` digits.
+// Leaves the introducer in place when no digits follow so it prints literally.
+fn numeric_escape<I>(chars: &mut std::iter::Peekable<I>, radix: u32, max: usize) -> Option<char>
+where
+    I: Iterator<Item = char> + Clone,
+{
+    let start = chars.clone();
+    let introducer = chars.next()?;
+    if radix == 16 && !chars.peek().is_some_and(|ch| ch.is_digit(radix)) {
+        *chars = start;
+        return None;
+    }
+    let value = take_digits(chars, radix, max);
+    let value = if introducer == '0' {
+        value & 0xff
+    } else {
+        value
+    };
+    let decoded = char::from_u32(value);
+    if decoded.is_none() {
+        // Not a scalar value (e.g. a surrogate); keep the text as written.
+        *chars = start;
+    }
+    decoded
+}
+
+fn take_digits<I>(chars: &mut std::iter::Peekable<I>, radix: u32, max: usize) -> u32
+where
+    I: Iterator<Item = char>,
+{
+    let mut value = 0u32;
+    for _ in 0..max {
+        match chars.peek().and_then(|ch| ch.to_digit(radix)) {
+            Some(digit) => {
+                value = value * radix + digit;
+                chars.next();
+            }
+            None => break,
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_escapes_decode() {
+        assert_eq!(decode_escapes("\\x41", OctalStyle::Bare), "A");
+        assert_eq!(decode_escapes("\\101", OctalStyle::Bare), "A");
+        assert_eq!(decode_escapes("\\0101", OctalStyle::LeadingZero), "A");
+        assert_eq!(decode_escapes("\\u00e9", OctalStyle::Bare), "é");
+        assert_eq!(decode_escapes("\\U0001F600", OctalStyle::Bare), "😀");
+        assert_eq!(decode_escapes("a\\tb\\n", OctalStyle::Bare), "a\tb\n");
+    }
+
+    #[test]
+    fn invalid_escapes_stay_literal() {
+        assert_eq!(decode_escapes("\\xZZ", OctalStyle::Bare), "\\xZZ");
+        assert_eq!(decode_escapes("\\q", OctalStyle::Bare), "\\q");
+        assert_eq!(decode_escapes("\\101", OctalStyle::LeadingZero), "\\101");
+        assert_eq!(decode_escapes("\\ud800", OctalStyle::Bare), "\\ud800");
+        assert_eq!(decode_escapes("tail\\", OctalStyle::Bare), "tail\\");
+    }
+}
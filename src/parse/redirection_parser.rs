@@ -1,8 +1,73 @@
+use crate::error::{ErrorKind, ShellError};
 use crate::parse::{
     strip_markers, CommandSpec, HeredocSpec, OutputRedirection, SandboxDirective, ESCAPE_MARKER,
-    NOGLOB_MARKER,
+    HEREDOC_BODY_MARKER, NOGLOB_MARKER, OPERATOR_TOKEN_MARKER,
 };
 
+// Takes the word after a redirection operator, rejecting another operator
+// so `cmd >< file` does not write to a file literally named `<`. The
+// tokenizer keeps an unquoted `<`/`>` there as a plain word.
+fn redirect_target(
+    iter: &mut std::iter::Peekable<std::vec::IntoIter<String>>,
+    op: &str,
+    missing: &str,
+) -> Result<String, String> {
+    let target = iter.next().ok_or_else(|| missing.to_string())?;
+    let word = target.trim_start_matches(OPERATOR_TOKEN_MARKER);
+    if word.len() != target.len() || word.chars().all(|ch| matches!(ch, '<' | '>')) {
+        return Err(ShellError::new(
+            ErrorKind::Redirection,
+            format!("expected a file name after '{op}', found '{word}'"),
+        )
+        .with_context(format!("Write it as: cmd {op} file"))
+        .to_string());
+    }
+    Ok(target)
+}
+
+fn conflict(message: String, hint: &str) -> String {
+    ShellError::new(ErrorKind::Redirection, message)
+        .with_context(hint)
+        .to_string()
+}
+
+fn output_conflict(stream: &str, existing: &OutputRedirection) -> String {
+    let op = if existing.append { ">>" } else { ">" };
+    conflict(
+        format!("{stream} already redirected to {} ({op})", existing.path),
+        "Keep a single target, or pipe through tee to write several files",
+    )
+}
+
+fn check_stdout_free(current: &CommandSpec) -> Result<(), String> {
+    if let Some(ref existing) = current.stdout {
+        return Err(output_conflict("output", existing));
+    }
+    if current.stdout_to_stderr {
+        return Err(conflict(
+            "output already redirected to stderr (>&2)".to_string(),
+            "Keep a single target, or pipe through tee to write several files",
+        ));
+    }
+    Ok(())
+}
+
+// `>&2`: stdout joins stderr wherever it currently goes.
+fn duplicate_stdout_to_stderr(current: &mut CommandSpec) -> Result<(), String> {
+    if current.stderr_close {
+        return Err("2: bad file descriptor".to_string());
+    }
+    if let Some(err) = current.stderr.take() {
+        // `2>file >&2`: both share the file, as after `>file 2>&1`.
+        current.stdout = Some(err);
+        current.stderr_to_stdout = true;
+        current.stderr_before_stdout = false;
+    } else if !current.stderr_to_stdout {
+        current.stdout_to_stderr = true;
+    }
+    Ok(())
+}
+
 pub(crate) fn apply_redirection(
     current: &mut CommandSpec,
     op: &str,
@@ -12,9 +77,7 @@ pub(crate) fn apply_redirection(
         if fd == 1 || fd == 2 {
             // Let the explicit cases below handle standard fds.
         } else {
-            let target = iter
-                .next()
-                .ok_or_else(|| "missing output file".to_string())?;
+            let target = redirect_target(iter, op, "missing output file")?;
             if let Some((_dup, close)) = parse_dup_target(&target)? {
                 if close {
                     current.close_fds.push(fd);
@@ -27,40 +90,45 @@ pub(crate) fn apply_redirection(
     }
     match op {
         "<" | "0<" => {
-            let path = iter
-                .next()
-                .ok_or_else(|| "missing input file".to_string())?;
+            let path = redirect_target(iter, op, "missing input file")?;
             set_input_redirection(current, InputRedirection::File(path))
         }
-        "<<" | "0<<" => {
-            let raw = iter
-                .next()
-                .ok_or_else(|| "missing heredoc delimiter".to_string())?;
+        "<<" | "0<<" | "<<-" | "0<<-" => {
+            let target = redirect_target(iter, op, "missing heredoc delimiter")?;
+            let (raw, body) = match target.split_once(HEREDOC_BODY_MARKER) {
+                Some((raw, body)) => (raw, Some(body.to_string())),
+                None => (target.as_str(), None),
+            };
             let quoted = raw.contains(ESCAPE_MARKER) || raw.contains(NOGLOB_MARKER);
-            let delimiter = strip_markers(&raw);
+            let delimiter = strip_markers(raw);
             set_input_redirection(
                 current,
                 InputRedirection::Heredoc(HeredocSpec {
                     delimiter,
                     quoted,
+                    strip_tabs: op.ends_with('-'),
                     content: None,
+                    body,
                 }),
             )
         }
         "<<<" | "0<<<" => {
-            let raw = iter
-                .next()
-                .ok_or_else(|| "missing here-string value".to_string())?;
+            let raw = redirect_target(iter, op, "missing here-string value")?;
             let content = strip_markers(&raw);
             set_input_redirection(current, InputRedirection::HereString(content))
         }
         ">" | "1>" | ">>" | "1>>" => {
-            let path = iter
-                .next()
-                .ok_or_else(|| "missing output file".to_string())?;
-            if current.stdout.is_some() {
-                return Err("multiple output redirections".to_string());
+            let path = redirect_target(iter, op, "missing output file")?;
+            check_stdout_free(current)?;
+            if let Some((dup, close)) = parse_dup_target(&path)? {
+                return match (dup, close) {
+                    (1, false) => Ok(()),
+                    (2, false) => duplicate_stdout_to_stderr(current),
+                    _ => Err("unsupported fd redirection".to_string()),
+                };
             }
+            // After `2>&1`, stderr stays on the stdout this file replaces.
+            current.stderr_before_stdout = current.stderr_to_stdout;
             current.stdout = Some(OutputRedirection {
                 path,
                 append: op.ends_with(">>"),
@@ -68,12 +136,13 @@ pub(crate) fn apply_redirection(
             Ok(())
         }
         "2>" | "2>>" => {
-            let target = iter
-                .next()
-                .ok_or_else(|| "missing output file".to_string())?;
+            let target = redirect_target(iter, op, "missing output file")?;
             if let Some((dup, close)) = parse_dup_target(&target)? {
                 if dup == 1 {
-                    current.stderr_to_stdout = true;
+                    // After `>&2` stdout is the shell's stderr, which is
+                    // where stderr already goes.
+                    current.stderr_to_stdout = !current.stdout_to_stderr;
+                    current.stderr_before_stdout = false;
                     current.stderr_close = false;
                     current.stderr = None;
                     Ok(())
@@ -86,8 +155,8 @@ pub(crate) fn apply_redirection(
                     Err("unsupported fd redirection".to_string())
                 }
             } else {
-                if current.stderr.is_some() {
-                    return Err("multiple stderr redirections".to_string());
+                if let Some(ref existing) = current.stderr {
+                    return Err(output_conflict("stderr", existing));
                 }
                 current.stderr = Some(OutputRedirection {
                     path: target,
@@ -99,11 +168,10 @@ pub(crate) fn apply_redirection(
             }
         }
         "&>" | "&>>" => {
-            let path = iter
-                .next()
-                .ok_or_else(|| "missing output file".to_string())?;
-            if current.stdout.is_some() || current.stderr.is_some() {
-                return Err("multiple output redirections".to_string());
+            let path = redirect_target(iter, op, "missing output file")?;
+            check_stdout_free(current)?;
+            if let Some(ref existing) = current.stderr {
+                return Err(output_conflict("stderr", existing));
             }
             current.stdout = Some(OutputRedirection {
                 path: path.clone(),
@@ -128,8 +196,21 @@ enum InputRedirection {
 }
 
 fn set_input_redirection(current: &mut CommandSpec, input: InputRedirection) -> Result<(), String> {
-    if current.stdin.is_some() || current.heredoc.is_some() || current.herestring.is_some() {
-        return Err("multiple input redirections".to_string());
+    let existing = if let Some(ref path) = current.stdin {
+        Some(format!("{path} (<)"))
+    } else if let Some(ref heredoc) = current.heredoc {
+        Some(format!("a heredoc (<< {})", heredoc.delimiter))
+    } else {
+        current
+            .herestring
+            .as_ref()
+            .map(|_| "a here-string (<<<)".to_string())
+    };
+    if let Some(existing) = existing {
+        return Err(conflict(
+            format!("input already redirected from {existing}"),
+            "A command reads from one source; drop the extra <, << or <<<",
+        ));
     }
     match input {
         InputRedirection::File(path) => current.stdin = Some(path),
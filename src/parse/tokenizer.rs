@@ -1,10 +1,13 @@
 //! Tokenizer for shell input.
 //!
 //! Uses Normal/Single/Double modes to preserve quoting semantics while still
-//! emitting a flat token stream for the command parser.
+//! emitting a flat token stream for the command parser. ANSI-C `$'...'`
+//! spans are read in one go and decoded as they are tokenized.
 use crate::error::{ErrorKind, ShellError};
 use crate::parse::{ESCAPE_MARKER, NOGLOB_MARKER, OPERATOR_TOKEN_MARKER};
 
+use super::escapes::{control_escape, decode_escapes, OctalStyle};
+
 #[derive(Copy, Clone, Eq, PartialEq)]
 enum ParseMode {
     Normal,
@@ -304,10 +307,17 @@ fn parse_line_with_mode(input: &str, lenient: bool) -> Result<Vec<String>, Strin
                             };
                             args.push(format!("{OPERATOR_TOKEN_MARKER}{op}"));
                         } else {
+                            // `<<-` strips leading tabs from the heredoc body.
+                            let heredoc = if matches!(chars.peek(), Some('-')) {
+                                chars.next();
+                                "<<-"
+                            } else {
+                                "<<"
+                            };
                             let op = if let Some(prefix) = fd_prefix {
-                                format!("{prefix}<<")
+                                format!("{prefix}{heredoc}")
                             } else {
-                                "<<".to_string()
+                                heredoc.to_string()
                             };
                             args.push(format!("{OPERATOR_TOKEN_MARKER}{op}"));
                         }
@@ -367,6 +377,21 @@ fn parse_line_with_mode(input: &str, lenient: bool) -> Result<Vec<String>, Strin
                         buf.push('\\');
                     }
                 }
+                '(' if !in_token && matches!(chars.peek(), Some('(')) => {
+                    // `(( expr ))` stays one word so `<`, `>` and `&` inside it
+                    // are arithmetic rather than redirections or operators.
+                    chars.next();
+                    let (inner, closed) = read_arithmetic_command(&mut chars);
+                    if !closed && !lenient {
+                        return Err(ShellError::new(
+                            ErrorKind::Parse,
+                            "Unterminated arithmetic command ((...))",
+                        )
+                        .with_context("Missing closing )) for the arithmetic command")
+                        .to_string());
+                    }
+                    args.push(format!("(({inner}))"));
+                }
                 '\'' => {
                     in_token = true;
                     mode = ParseMode::Single;
@@ -375,6 +400,17 @@ fn parse_line_with_mode(input: &str, lenient: bool) -> Result<Vec<String>, Strin
                     in_token = true;
                     mode = ParseMode::Double;
                 }
+                '$' if matches!(chars.peek(), Some('\'')) => {
+                    chars.next();
+                    in_token = true;
+                    let (body, closed) = read_ansi_c_quote(&mut chars);
+                    if !closed && !lenient {
+                        return Err(ShellError::new(ErrorKind::Parse, "Unterminated $' quote")
+                            .with_position(input.len() - 1)
+                            .to_string());
+                    }
+                    push_ansi_c_quoted(&mut buf, &body);
+                }
                 '$' => {
                     // $() keeps nesting state to validate balanced substitutions.
                     in_token = true;
@@ -527,6 +563,66 @@ fn parse_line_with_mode(input: &str, lenient: bool) -> Result<Vec<String>, Strin
     Ok(args)
 }
 
+// Reads the body of `(( ... ))` up to the matching `))`. Whitespace and glob
+// characters are tagged so the body survives word splitting and globbing
+// intact, while `$name` references are still expanded.
+fn read_arithmetic_command<I>(chars: &mut std::iter::Peekable<I>) -> (String, bool)
+where
+    I: Iterator<Item = char>,
+{
+    let mut inner = String::new();
+    let mut depth = 0usize;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            ')' if matches!(chars.peek(), Some(')')) => {
+                chars.next();
+                return (inner, true);
+            }
+            _ => {}
+        }
+        if ch.is_whitespace() || matches!(ch, '*' | '?' | '[' | ']' | '!' | '+' | '@') {
+            inner.push(NOGLOB_MARKER);
+        }
+        inner.push(ch);
+    }
+    (inner, false)
+}
+
+// Reads the body of `$'...'` up to the closing quote, leaving escapes
+// undecoded. A backslash-escaped quote does not end the span.
+fn read_ansi_c_quote<I>(chars: &mut std::iter::Peekable<I>) -> (String, bool)
+where
+    I: Iterator<Item = char>,
+{
+    let mut body = String::new();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\'' => return (body, true),
+            '\\' => {
+                body.push('\\');
+                if let Some(next) = chars.next() {
+                    body.push(next);
+                }
+            }
+            _ => body.push(ch),
+        }
+    }
+    (body, false)
+}
+
+// Decoded characters are marked like escaped double-quoted text, so none of
+// them is expanded, split or globbed. As in bash, a NUL ends the string.
+fn push_ansi_c_quoted(buf: &mut String, body: &str) {
+    let decoded = decode_escapes(body, OctalStyle::Bare);
+    for ch in decoded.chars().take_while(|ch| *ch != '\0') {
+        buf.push(NOGLOB_MARKER);
+        buf.push(ESCAPE_MARKER);
+        buf.push(ch);
+    }
+}
+
 pub fn parse_command_substitution<I>(
     chars: &mut std::iter::Peekable<I>,
 ) -> Result<String, String>
@@ -602,6 +698,15 @@ where
                     mode = ParseMode::Double;
                     inner.push(ch);
                 }
+                '$' if matches!(chars.peek(), Some('\'')) => {
+                    chars.next();
+                    let (body, closed) = read_ansi_c_quote(chars);
+                    inner.push_str("$'");
+                    inner.push_str(&body);
+                    if closed {
+                        inner.push('\'');
+                    }
+                }
                 '$' => {
                     if matches!(chars.peek(), Some('(')) {
                         chars.next();
@@ -748,15 +853,11 @@ fn push_escaped_double(buf: &mut String, next: char) {
     buf.push(resolved);
 }
 
+// Only the common whitespace escapes are decoded outside `echo -e`; the
+// shared table keeps their meaning in sync with it.
 fn resolve_escape(ch: char) -> char {
     match ch {
-        'n' => '\n',
-        't' => '\t',
-        'r' => '\r',
-        ' ' => ' ',
-        '\\' => '\\',
-        '"' => '"',
-        '\'' => '\'',
+        'n' | 't' | 'r' => control_escape(ch).unwrap_or(ch),
         _ => ch,
     }
 }
@@ -785,6 +886,25 @@ mod tests {
         assert_eq!(stripped, vec!["printf", "%s|%s", "abcd", ""]);
     }
 
+    #[test]
+    fn ansi_c_quotes_decode_escapes() {
+        let tokens = parse_line("printf $'a\\tb'").unwrap();
+        let quoted = |ch: char| format!("{NOGLOB_MARKER}{ESCAPE_MARKER}{ch}");
+        assert_eq!(tokens[1], [quoted('a'), quoted('\t'), quoted('b')].concat());
+
+        let tokens =
+            parse_line(r"echo $'it\'s\n' x$'\x41\101\\'y $'$HOME *' $'a\0b'").unwrap();
+        let stripped: Vec<String> = tokens.iter().map(|t| strip_markers(t)).collect();
+        assert_eq!(stripped, vec!["echo", "it's\n", "xAA\\y", "$HOME *", "a"]);
+
+        let tokens = parse_line(r"echo $(printf $'a\'b')").unwrap();
+        assert_eq!(tokens[1], r"$(printf $'a\'b')");
+
+        assert!(parse_line("echo $'abc").is_err());
+        let tokens = parse_line_lenient("echo $'a\\n").unwrap();
+        assert_eq!(strip_markers(&tokens[1]), "a\n");
+    }
+
     #[test]
     fn backticks_and_command_substitution() {
         let tokens = parse_line("echo `echo hi`").unwrap();
@@ -803,6 +923,17 @@ mod tests {
         assert_eq!(strip_markers(&tokens[1]), "line\n");
     }
 
+    #[test]
+    fn arithmetic_command_is_one_word() {
+        let tokens = parse_line("(( x > 3 )) && echo big").unwrap();
+        assert_eq!(strip_markers(&tokens[0]), "(( x > 3 ))");
+        assert_eq!(tokens[1], format!("{OPERATOR_TOKEN_MARKER}&&"));
+        let tokens = parse_line("((a<(b+1)))").unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(strip_markers(&tokens[0]), "((a<(b+1)))");
+        assert!(parse_line("(( 1 + 2").is_err());
+    }
+
     #[test]
     fn error_cases() {
         assert_eq!(
@@ -840,6 +971,16 @@ mod tests {
         assert_eq!(stripped, vec!["echo", "a", "&&", "b", "||", "c", ";", "d"]);
     }
 
+    #[test]
+    fn heredoc_operators() {
+        let tokens = parse_line("cat <<-EOF 0<<-'X' <<EOF <<<here << -x").unwrap();
+        let stripped: Vec<String> = tokens.iter().map(|t| strip_all_markers(t)).collect();
+        assert_eq!(
+            stripped,
+            vec!["cat", "<<-", "EOF", "0<<-", "X", "<<", "EOF", "<<<", "here", "<<", "-x"]
+        );
+    }
+
     #[test]
     fn fd_redirection_without_space() {
         let tokens = parse_line("echo 2>err").unwrap();
@@ -12,6 +12,7 @@ use crate::expansion::GlobOptions;
 use crate::job_control::wait_for_process_group;
 use crate::parse::{parse_line, parse_line_lenient, split_pipeline, split_sequence, SeqOp};
 use crate::{build_expansion_context};
+use crate::expansion_runner::SpecialParams;
 
 pub struct ProcessSubstResult {
     pub tokens: Vec<String>,
@@ -35,6 +36,7 @@ pub fn apply_process_subst(
     assoc_arrays: std::collections::HashMap<String, std::collections::HashMap<String, String>>,
     builtin_enabled: std::collections::HashMap<String, bool>,
     glob_options: GlobOptions,
+    specials: SpecialParams,
     strict: bool,
 ) -> io::Result<ProcessSubstResult> {
     let mut out = Vec::with_capacity(tokens.len());
@@ -59,6 +61,7 @@ pub fn apply_process_subst(
                 assoc_arrays.clone(),
                 builtin_enabled.clone(),
                 glob_options,
+                specials,
                 strict,
                 child_fd,
                 kind,
@@ -105,6 +108,7 @@ fn build_subst_pipeline(
     assoc_arrays: std::collections::HashMap<String, std::collections::HashMap<String, String>>,
     builtin_enabled: std::collections::HashMap<String, bool>,
     glob_options: GlobOptions,
+    specials: SpecialParams,
     strict: bool,
     fd: RawFd,
     kind: SubstKind,
@@ -124,6 +128,7 @@ fn build_subst_pipeline(
         builtin_enabled.clone(),
         glob_options,
         &[],
+        specials,
         strict,
     );
     let expanded = expand_tokens(tokens, &ctx)
@@ -160,7 +165,7 @@ fn build_subst_pipeline(
             let last = pipeline
                 .last_mut()
                 .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
-            if last.stdout.is_some() {
+            if last.redirects_stdout() {
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidInput,
                     "process substitution conflicts with stdout redirection",
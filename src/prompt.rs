@@ -1,5 +1,7 @@
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::SystemTime;
 
 use crate::colors::{resolve_color, ColorConfig};
 
@@ -10,6 +12,40 @@ pub enum PromptTheme {
     Minimal,
 }
 
+/// Vi editing mode, for the indicator in front of the prompt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViMode {
+    Insert,
+    Normal,
+}
+
+/// The plain indicator for `mode`, padded so both modes are equally wide:
+/// the line editor measures the prompt as built, before it is recolored
+/// for the current mode.
+pub fn vi_mode_glyph(colors: &ColorConfig, mode: ViMode) -> String {
+    let insert = &colors.vi_glyph_insert;
+    let normal = &colors.vi_glyph_normal;
+    let width = insert.chars().count().max(normal.chars().count());
+    let glyph = match mode {
+        ViMode::Insert => insert,
+        ViMode::Normal => normal,
+    };
+    format!("{glyph:<width$} ")
+}
+
+/// `vi_mode_glyph` in the color configured for `mode`.
+pub fn vi_mode_indicator(colors: &ColorConfig, mode: ViMode) -> String {
+    let padded = vi_mode_glyph(colors, mode);
+    let color = match mode {
+        ViMode::Insert => resolve_color(&colors.vi_insert),
+        ViMode::Normal => resolve_color(&colors.vi_normal),
+    };
+    match padded.strip_suffix(' ') {
+        Some(glyph) if !color.is_empty() => format!("{color}{glyph}\x1b[0m "),
+        _ => padded,
+    }
+}
+
 pub fn parse_prompt_theme(value: &str) -> Option<PromptTheme> {
     match value.trim().to_lowercase().as_str() {
         "fish" | "default" => Some(PromptTheme::Fish),
@@ -19,13 +55,94 @@ pub fn parse_prompt_theme(value: &str) -> Option<PromptTheme> {
     }
 }
 
+/// Opens a non-printing span of the prompt, as readline's `\001`.
+pub const PROMPT_IGNORE_START: char = '\x01';
+/// Closes a span opened by `PROMPT_IGNORE_START`, as readline's `\002`.
+pub const PROMPT_IGNORE_END: char = '\x02';
+
+/// Wraps every escape sequence in `prompt` that is not already inside a
+/// non-printing span in `\001...\002`, so width counting skips it.
+pub fn wrap_nonprinting(prompt: &str) -> String {
+    let mut out = String::with_capacity(prompt.len());
+    let mut chars = prompt.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == PROMPT_IGNORE_START {
+            out.push(ch);
+            for inner in chars.by_ref() {
+                out.push(inner);
+                if inner == PROMPT_IGNORE_END {
+                    break;
+                }
+            }
+            continue;
+        }
+        if ch != '\x1b' {
+            out.push(ch);
+            continue;
+        }
+        out.push(PROMPT_IGNORE_START);
+        out.push(ch);
+        match chars.next() {
+            // CSI: parameters up to a final byte in `@`..=`~`.
+            Some('[') => {
+                out.push('[');
+                for inner in chars.by_ref() {
+                    out.push(inner);
+                    if ('@'..='~').contains(&inner) {
+                        break;
+                    }
+                }
+            }
+            // OSC (window titles, links): up to BEL or ESC `\`.
+            Some(']') => {
+                out.push(']');
+                while let Some(inner) = chars.next() {
+                    out.push(inner);
+                    if inner == '\x07' {
+                        break;
+                    }
+                    if inner == '\x1b' && chars.peek() == Some(&'\\') {
+                        out.extend(chars.next());
+                        break;
+                    }
+                }
+            }
+            Some(next) => out.push(next),
+            None => {}
+        }
+        out.push(PROMPT_IGNORE_END);
+    }
+    out
+}
+
+/// Splits a prompt with `\001...\002` spans into what is measured (the
+/// printable text) and what is shown (everything, minus the markers).
+pub fn split_nonprinting(prompt: &str) -> (String, String) {
+    let mut printable = String::with_capacity(prompt.len());
+    let mut shown = String::with_capacity(prompt.len());
+    let mut ignoring = false;
+    for ch in prompt.chars() {
+        match ch {
+            PROMPT_IGNORE_START => ignoring = true,
+            PROMPT_IGNORE_END => ignoring = false,
+            _ => {
+                shown.push(ch);
+                if !ignoring {
+                    printable.push(ch);
+                }
+            }
+        }
+    }
+    (printable, shown)
+}
+
 pub fn render_prompt_template(template: &str, last_status: i32, cwd: &Path) -> String {
     let status_str = last_status.to_string();
     let status_opt = if last_status == 0 { "" } else { &status_str };
     let mut out = template.replace("{status?}", status_opt);
     out = out.replace("{status}", &status_str);
     out = out.replace("{cwd}", &cwd.display().to_string());
-    out
+    wrap_nonprinting(&out)
 }
 
 pub fn render_prompt_theme(
@@ -43,7 +160,7 @@ pub fn render_prompt_theme(
             }
         }
         PromptTheme::Minimal => "> ".to_string(),
-        PromptTheme::Fish => render_fish_prompt(colors, last_status, cwd),
+        PromptTheme::Fish => wrap_nonprinting(&render_fish_prompt(colors, last_status, cwd)),
     }
 }
 
@@ -52,7 +169,6 @@ fn render_fish_prompt(colors: &ColorConfig, last_status: i32, cwd: &Path) -> Str
     let status_color = resolve_color(&colors.prompt_status);
     let cwd_color = resolve_color(&colors.prompt_cwd);
     let git_color = resolve_color(&colors.prompt_git);
-    let symbol_color = resolve_color(&colors.prompt_symbol);
     let status = if last_status == 0 {
         String::new()
     } else if status_color.is_empty() {
@@ -74,12 +190,90 @@ fn render_fish_prompt(colors: &ColorConfig, last_status: i32, cwd: &Path) -> Str
     } else {
         format!("{cwd_color}{}{reset}", cwd.display())
     };
-    let symbol = if symbol_color.is_empty() {
-        ">".to_string()
+    let symbol = prompt_symbol(colors, last_status);
+    format!("{status}{cwd_text} {git}{symbol} ")
+}
+
+/// The prompt glyph in its success or failure color, picked by `last_status`.
+fn prompt_symbol(colors: &ColorConfig, last_status: i32) -> String {
+    let (color, glyph) = if last_status == 0 {
+        (&colors.prompt_symbol, &colors.prompt_glyph_ok)
     } else {
-        format!("{symbol_color}>{reset}")
+        (&colors.prompt_symbol_err, &colors.prompt_glyph_err)
     };
-    format!("{status}{cwd_text} {git}{symbol} ")
+    let color = resolve_color(color);
+    if color.is_empty() {
+        glyph.clone()
+    } else {
+        format!("{color}{glyph}\x1b[0m")
+    }
+}
+
+/// What a prompt function's output is assumed to depend on: the function,
+/// the directory and the files git rewrites when the branch, index or
+/// commit changes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PromptCacheKey {
+    body: Vec<String>,
+    cwd: PathBuf,
+    git_mtimes: Vec<Option<SystemTime>>,
+}
+
+impl PromptCacheKey {
+    pub(crate) fn new(body: &[String], cwd: &Path) -> Self {
+        let git_mtimes = match find_git_dir(cwd) {
+            Some(git_dir) => ["HEAD", "index", "logs/HEAD"]
+                .iter()
+                .map(|file| {
+                    fs::metadata(git_dir.join(file))
+                        .and_then(|meta| meta.modified())
+                        .ok()
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        Self {
+            body: body.to_vec(),
+            cwd: cwd.to_path_buf(),
+            git_mtimes,
+        }
+    }
+}
+
+/// `set -o promptcache`: the last prompt function output, reused while its
+/// key is unchanged.
+#[derive(Debug, Default)]
+pub(crate) struct PromptCache {
+    entry: Option<(PromptCacheKey, String)>,
+}
+
+impl PromptCache {
+    pub(crate) fn get(&self, key: &PromptCacheKey) -> Option<&str> {
+        match &self.entry {
+            Some((cached, prompt)) if cached == key => Some(prompt),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn store(&mut self, key: PromptCacheKey, prompt: String) {
+        self.entry = Some((key, prompt));
+    }
+}
+
+// The `.git` directory of the repository containing `cwd`; a `.git` file
+// (worktrees, submodules) points at the real one.
+fn find_git_dir(cwd: &Path) -> Option<PathBuf> {
+    for dir in cwd.ancestors() {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Some(dot_git);
+        }
+        if let Ok(content) = fs::read_to_string(&dot_git) {
+            let target = content.strip_prefix("gitdir:")?.trim();
+            return Some(dir.join(target));
+        }
+    }
+    None
 }
 
 fn git_prompt_info(cwd: &Path) -> Option<String> {
@@ -133,3 +327,89 @@ fn git_prompt_info(cwd: &Path) -> Option<String> {
         Some(format!("({branch})"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::apply_color_setting;
+
+    #[test]
+    fn prompt_symbol_follows_last_status() {
+        let mut colors = ColorConfig::default();
+        assert_eq!(prompt_symbol(&colors, 0), "\x1b[32m>\x1b[0m");
+        assert_eq!(prompt_symbol(&colors, 1), "\x1b[31m>\x1b[0m");
+
+        apply_color_setting(&mut colors, "prompt_symbol_ok", "none").unwrap();
+        apply_color_setting(&mut colors, "prompt_glyph_ok", "$").unwrap();
+        apply_color_setting(&mut colors, "prompt_glyph_err", "!").unwrap();
+        assert_eq!(prompt_symbol(&colors, 0), "$");
+        assert_eq!(prompt_symbol(&colors, 127), "\x1b[31m!\x1b[0m");
+    }
+
+    #[test]
+    fn prompt_cache_holds_until_cwd_or_git_state_changes() {
+        let repo = tempfile::tempdir().unwrap();
+        let git_dir = repo.path().join(".git");
+        fs::create_dir_all(git_dir.join("logs")).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        let sub = repo.path().join("src");
+        fs::create_dir(&sub).unwrap();
+        let body = vec!["echo".to_string(), "prompt".to_string()];
+
+        let mut cache = PromptCache::default();
+        let key = PromptCacheKey::new(&body, &sub);
+        assert_eq!(cache.get(&key), None);
+        cache.store(key, "main> ".to_string());
+        assert_eq!(cache.get(&PromptCacheKey::new(&body, &sub)), Some("main> "));
+
+        assert_eq!(cache.get(&PromptCacheKey::new(&body, repo.path())), None);
+        let other_body = vec!["echo".to_string(), "other".to_string()];
+        assert_eq!(cache.get(&PromptCacheKey::new(&other_body, &sub)), None);
+
+        let head = fs::File::options()
+            .write(true)
+            .open(git_dir.join("HEAD"))
+            .unwrap();
+        head.set_modified(SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(cache.get(&PromptCacheKey::new(&body, &sub)), None);
+    }
+
+    #[test]
+    fn nonprinting_sequences_are_delimited_and_not_counted() {
+        let prompt = "\x1b[1;32mok\x1b[0m \x1b]0;title\x07$ ";
+        let wrapped = wrap_nonprinting(prompt);
+        assert_eq!(
+            wrapped,
+            "\x01\x1b[1;32m\x02ok\x01\x1b[0m\x02 \x01\x1b]0;title\x07\x02$ "
+        );
+        assert_eq!(wrap_nonprinting(&wrapped), wrapped);
+        assert_eq!(
+            split_nonprinting(&wrapped),
+            ("ok $ ".to_string(), prompt.to_string())
+        );
+
+        let cwd = Path::new("/tmp");
+        let rendered = render_prompt_template("\x1b[?25h{cwd} \x1b[31m>\x1b[0m ", 0, cwd);
+        assert_eq!(split_nonprinting(&rendered).0.chars().count(), 7);
+    }
+
+    #[test]
+    fn vi_mode_indicator_uses_the_glyph_for_each_mode() {
+        let mut colors = ColorConfig::default();
+        assert_eq!(vi_mode_glyph(&colors, ViMode::Insert), "[I] ");
+        assert_eq!(
+            vi_mode_indicator(&colors, ViMode::Normal),
+            "\x1b[33m[N]\x1b[0m "
+        );
+
+        apply_color_setting(&mut colors, "vi_glyph_insert", "ins").unwrap();
+        apply_color_setting(&mut colors, "vi_glyph_normal", "N").unwrap();
+        apply_color_setting(&mut colors, "vi_normal", "none").unwrap();
+        assert_eq!(vi_mode_glyph(&colors, ViMode::Insert), "ins ");
+        assert_eq!(vi_mode_indicator(&colors, ViMode::Normal), "N   ");
+        assert_eq!(
+            vi_mode_indicator(&colors, ViMode::Insert),
+            "\x1b[32mins\x1b[0m "
+        );
+    }
+}
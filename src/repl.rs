@@ -1,49 +1,73 @@
 use rustyline::history::DefaultHistory;
-use rustyline::{Config, EditMode, Editor};
+use rustyline::{Config, EditMode, Editor, ExternalPrinter};
 use std::collections::HashMap;
 use std::env;
-use std::io;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Write};
+use std::os::fd::FromRawFd;
 use std::path::PathBuf;
 use std::sync::{
     atomic::{AtomicBool, AtomicI32, Ordering},
     Arc,
 };
+use std::time::Duration;
 
 use crate::builtins::{
-    builtin_names, execute_builtin, execute_builtin_capture, execute_function, find_in_path,
-    is_builtin, is_builtin_enabled_map, load_assoc_arrays, try_execute_compound,
+    builtin_names, capture_within, execute_builtin, execute_builtin_capture, execute_function,
+    find_in_path, is_builtin, is_builtin_enabled_map,
+    load_assoc_arrays, maxtime_watchdog, prompt_timeout, source_file, try_execute_compound,
+    spawn_function_subshell, wait_for_subshell, CallFrame, LoopControl, TIMEOUT_STATUS,
 };
 use crate::completion::LineHelper;
-use crate::completions::{default_completions, load_completion_files, suggest_command, CompletionSet};
+use crate::completions::{
+    default_completions, history_command_counts, load_completion_files, suggest_command,
+    CompletionSet,
+};
 use crate::config::sandbox::apply_sandbox_env;
-use crate::config::{apply_abbreviations, apply_aliases, build_prompt, load_config};
+use crate::config::{
+    apply_abbreviations, apply_aliases, build_prompt, conf_d_dir, conf_d_fragments, load_config,
+    StartupFiles,
+};
 use crate::execution::{
-    apply_sandbox_directive, build_command, builtin_pipe, run_pipeline,
-    sandbox_options_for_command, spawn_command_background, spawn_pipeline_background,
-    status_from_error, SandboxConfig,
+    apply_sandbox_directive, build_command, builtin_pipe, builtin_pipe_capture,
+    describe_exit_status, run_pipeline, run_pipeline_into, sandbox_options_for_command,
+    spawn_command_background,
+    spawn_pipeline_background, status_from_error, SandboxConfig,
 };
-use crate::expansion::{expand_globs_with, expand_tokens};
+use crate::expansion::{expand_globs_with, expand_heredoc, expand_tokens, is_ambiguous_redirect};
 use crate::expansion::GlobOptions;
 use crate::expansion_runner::execute_tokens_capture;
 use crate::heredoc;
-use crate::io_helpers::read_input_line;
-use crate::job_control::{add_job_with_status, reap_coprocs, reap_jobs, Coprocess, Job, JobStatus, WaitOutcome};
+use crate::io_helpers::{eof_should_exit, ignoreeof_limit, read_input_line};
+use crate::job_control::{
+    add_job_with_status, reap_coprocs, reap_jobs, signal_status, status_to_description,
+    watch_jobs_while, Coprocess, Job, JobNotices, JobStatus, WaitOutcome,
+};
 use crate::parse::{
     parse_line, parse_line_lenient, split_pipeline, split_pipeline_lenient, split_sequence,
     split_sequence_lenient, tokenize_history, CommandSpec, HistoryDesignator, HistoryToken,
-    SandboxDirective, SeqOp, OPERATOR_TOKEN_MARKER,
+    SandboxDirective, SeqOp, SeqSegment, OPERATOR_TOKEN_MARKER,
+};
+use crate::prompt::{wrap_nonprinting, PromptCache, PromptCacheKey, PromptTheme};
+use crate::safeglob::{
+    glob_matches_by_command, guarded_commands_from_env, is_confirmation, should_confirm_glob,
 };
-use crate::prompt::PromptTheme;
 use crate::build_expansion_context;
+use crate::expansion_runner::SpecialParams;
 use crate::process_subst::{apply_process_subst, FdGuard, ProcessSubstResult};
 use crate::arithmetic::eval_arithmetic;
+use crate::timing::{run_timed, run_with_stats, split_time_keyword};
 
 pub(crate) struct ShellState {
     pub(crate) editor: Editor<LineHelper, DefaultHistory>,
+    // Prints job notices above the line being edited; None without a terminal.
+    pub(crate) job_printer: Option<Box<dyn ExternalPrinter + Send>>,
     // Shared across job control and signal handling to track the foreground group.
     pub(crate) fg_pgid: Arc<AtomicI32>,
     // SIGCHLD handler flips this; reaping happens in the main loop.
     pub(crate) sigchld_flag: Arc<AtomicBool>,
+    // SIGINT handler flips this; loops poll it between iterations.
+    pub(crate) interrupt_flag: Arc<AtomicBool>,
     // Used to restore terminal control after fg jobs stop/exit.
     pub(crate) shell_pgid: i32,
     pub(crate) aliases: HashMap<String, Vec<String>>,
@@ -61,16 +85,39 @@ pub(crate) struct ShellState {
     pub(crate) readonly_vars: std::collections::HashSet<String>,
     pub(crate) positional_stack: Vec<Vec<String>>,
     pub(crate) function_scopes: Vec<HashMap<String, Option<Vec<String>>>>,
+    // Innermost function call last; read by `caller`.
+    pub(crate) call_stack: Vec<CallFrame>,
     pub(crate) bindings: HashMap<String, String>,
     pub(crate) coprocs: HashMap<String, Coprocess>,
     pub(crate) jobs: Vec<Job>,
     pub(crate) next_job_id: usize,
     pub(crate) next_coproc_id: usize,
     pub(crate) last_status: i32,
+    // Process ID of the most recent background job, for `$!`.
+    pub(crate) last_background_pid: Option<i32>,
     // Mirrors bash-like pipefail behavior for pipelines.
     pub(crate) pipefail: bool,
+    // `set -o stats`: report child CPU time and max RSS after each command.
+    pub(crate) stats: bool,
+    // `set -o reportstatus`: describe non-zero exits on stderr.
+    pub(crate) reportstatus: bool,
+    // `set -o physical`: `cd` and `pwd` resolve symlinks as if given `-P`.
+    pub(crate) physical: bool,
+    // `set -o maxtime=N`: wall-clock limit for foreground commands.
+    pub(crate) maxtime: Option<Duration>,
+    // `set -o ignoreeof`: Ctrl-D at the prompt needs repeating to exit.
+    pub(crate) ignoreeof: bool,
+    // `set -o safeglob[=N]`: confirm guarded commands whose globs match N+ paths.
+    pub(crate) safeglob: Option<usize>,
+    // `set -o promptcache`: reuse prompt function output until its inputs change.
+    pub(crate) prompt_cache: Option<PromptCache>,
+    pub(crate) eof_count: u32,
     pub(crate) interactive: bool,
+    // `--posix` / `set -o posix`: strict parsing, no abbreviations or prompt functions.
+    pub(crate) posix: bool,
     pub(crate) trace: bool,
+    // Where trace lines go; stderr unless MINISHELL_XTRACE_FD says otherwise.
+    pub(crate) trace_out: Box<dyn Write>,
     pub(crate) extglob: bool,
     pub(crate) nullglob: bool,
     pub(crate) failglob: bool,
@@ -83,8 +130,16 @@ pub(crate) struct ShellState {
     pub(crate) in_debug_trap: bool,
     pub(crate) in_return_trap: bool,
     pub(crate) return_requested: Option<i32>,
+    // Set by `break`/`continue` until the targeted loop picks it up.
+    pub(crate) loop_control: Option<LoopControl>,
+    // Loops enclosing the current command within this function call.
+    pub(crate) loop_depth: usize,
+    // `while ...; done < file`: where `read` takes its lines inside the loop.
+    pub(crate) loop_input: Option<BufReader<File>>,
     pub(crate) sandbox: SandboxConfig,
     pub(crate) local_scopes: Vec<HashMap<String, Option<String>>>,
+    // Set for login shells; sourced once when the shell exits.
+    pub(crate) logout_file: Option<PathBuf>,
 }
 
 pub(crate) fn init_state(
@@ -92,6 +147,7 @@ pub(crate) fn init_state(
     interactive: bool,
     shell_pgid: i32,
     sandbox_override: Option<SandboxDirective>,
+    startup: &StartupFiles,
 ) -> io::Result<ShellState> {
     let edit_mode = match env::var("MINISHELL_EDITMODE").ok().as_deref() {
         Some("vi") | Some("VI") => EditMode::Vi,
@@ -103,17 +159,27 @@ pub(crate) fn init_state(
         .build();
     let mut editor = Editor::with_config(config).map_err(io::Error::other)?;
     editor.set_helper(Some(LineHelper::new()));
+    crate::completion::enable_last_argument(&mut editor);
+    if edit_mode == EditMode::Vi {
+        crate::completion::enable_vi_mode_indicator(&mut editor);
+    }
 
     let history_path = env::var("HOME")
         .map(PathBuf::from)
         .unwrap_or_default()
         .join(".better_shell_history");
     let _ = editor.load_history(&history_path);
+    let job_printer = interactive
+        .then(|| editor.create_external_printer().ok())
+        .flatten()
+        .map(|printer| Box::new(printer) as Box<dyn ExternalPrinter + Send>);
 
     let mut state = ShellState {
         editor,
+        job_printer,
         fg_pgid: Arc::new(AtomicI32::new(0)),
         sigchld_flag: Arc::new(AtomicBool::new(false)),
+        interrupt_flag: Arc::new(AtomicBool::new(false)),
         shell_pgid,
         aliases: HashMap::new(),
         prompt_template: None,
@@ -126,22 +192,33 @@ pub(crate) fn init_state(
         arrays: HashMap::new(),
         assoc_arrays: HashMap::new(),
         builtin_enabled: builtin_names()
-            .iter()
             .map(|name| (name.to_string(), true))
             .collect(),
         command_hash: HashMap::new(),
         readonly_vars: std::collections::HashSet::new(),
         positional_stack: Vec::new(),
         function_scopes: Vec::new(),
+        call_stack: Vec::new(),
         bindings: HashMap::new(),
         coprocs: HashMap::new(),
         jobs: Vec::new(),
         next_job_id: 1,
         next_coproc_id: 1,
         last_status: 0,
+        last_background_pid: None,
         pipefail: false,
+        stats: false,
+        reportstatus: false,
+        physical: false,
+        maxtime: None,
+        ignoreeof: false,
+        safeglob: None,
+        prompt_cache: None,
+        eof_count: 0,
         interactive,
+        posix: false,
         trace,
+        trace_out: Box::new(io::stderr()),
         extglob: false,
         nullglob: false,
         failglob: false,
@@ -154,19 +231,25 @@ pub(crate) fn init_state(
         in_debug_trap: false,
         in_return_trap: false,
         return_requested: None,
+        loop_control: None,
+        loop_depth: 0,
+        loop_input: None,
         sandbox: SandboxConfig::default(),
         local_scopes: Vec::new(),
+        logout_file: startup.logout.clone(),
     };
-    if let Err(err) = load_config(
-        &mut state.aliases,
-        &mut state.prompt_template,
-        &mut state.prompt_function,
-        &mut state.prompt_theme,
-        &mut state.colors,
-        &mut state.sandbox,
-        &mut state.abbreviations,
-    ) {
-        eprintln!("config error: {err}");
+    if startup.rc {
+        if let Err(err) = load_config(
+            &mut state.aliases,
+            &mut state.prompt_template,
+            &mut state.prompt_function,
+            &mut state.prompt_theme,
+            &mut state.colors,
+            &mut state.sandbox,
+            &mut state.abbreviations,
+        ) {
+            eprintln!("config error: {err}");
+        }
     }
     if let Err(err) = load_assoc_arrays(&mut state) {
         eprintln!("assoc load error: {err}");
@@ -179,11 +262,76 @@ pub(crate) fn init_state(
         apply_sandbox_directive(&mut state.sandbox, directive);
     }
     apply_sandbox_env(&mut state.sandbox);
+    // Read after the config so a `MINISHELL_XTRACE_FD=` line there applies.
+    if let Ok(spec) = env::var(XTRACE_FD_ENV) {
+        match open_trace_sink(&spec) {
+            Ok(sink) => state.trace_out = sink,
+            Err(err) => eprintln!("{XTRACE_FD_ENV}: {spec}: {err}"),
+        }
+    }
+    if startup.rc {
+        // Drop-in fragments run after the main config; one failing fragment
+        // does not stop the rest.
+        if let Some(dir) = conf_d_dir() {
+            for fragment in conf_d_fragments(&dir) {
+                if let Err(err) = source_file(&mut state, &fragment.to_string_lossy()) {
+                    eprintln!("conf.d error: {}: {err}", fragment.display());
+                }
+            }
+        }
+    }
 
     Ok(state)
 }
 
+pub(crate) fn run_profile_files(state: &mut ShellState, profiles: &[PathBuf]) {
+    for path in profiles {
+        if !path.is_file() {
+            continue;
+        }
+        if let Err(err) = source_file(state, &path.to_string_lossy()) {
+            eprintln!("profile error: {}: {err}", path.display());
+        }
+    }
+}
+
+pub(crate) fn run_logout_file(state: &mut ShellState) {
+    let Some(path) = state.logout_file.take() else {
+        return;
+    };
+    if path.is_file() {
+        if let Err(err) = source_file(state, &path.to_string_lossy()) {
+            eprintln!("logout error: {}: {err}", path.display());
+        }
+    }
+}
+
 impl ShellState {
+    pub(crate) fn interrupt_requested(&self) -> bool {
+        self.interrupt_flag.load(Ordering::SeqCst)
+    }
+
+    /// What `$?` and `$!` expand to right now.
+    pub(crate) fn special_params(&self) -> SpecialParams {
+        SpecialParams {
+            last_status: self.last_status,
+            last_background_pid: self.last_background_pid,
+            physical: self.physical,
+        }
+    }
+
+    /// Interactive input is parsed leniently unless POSIX mode asks for the
+    /// strict rules scripts use.
+    pub(crate) fn lenient(&self) -> bool {
+        self.interactive && !self.posix
+    }
+
+    /// True while a `return`, `break` or `continue` is skipping the rest of
+    /// the commands it cuts short.
+    pub(crate) fn unwinding(&self) -> bool {
+        self.return_requested.is_some() || self.loop_control.is_some()
+    }
+
     pub(crate) fn in_local_scope(&self) -> bool {
         !self.local_scopes.is_empty()
     }
@@ -331,11 +479,15 @@ impl ShellState {
 
 pub(crate) fn run_once(state: &mut ShellState) -> io::Result<()> {
     if state.sigchld_flag.swap(false, Ordering::SeqCst) {
-        reap_jobs(&mut state.jobs);
-        let removed = reap_coprocs(&mut state.coprocs);
+        let mut notices = JobNotices::default();
+        reap_jobs(&mut state.jobs, &mut notices);
+        let removed = reap_coprocs(&mut state.coprocs, &mut notices);
         for name in removed {
             state.unset_assoc_array(&name);
         }
+        if let Some(block) = notices.take() {
+            print!("{block}");
+        }
     }
     if state.interactive {
         crate::completion::update_completion_context(
@@ -349,24 +501,51 @@ pub(crate) fn run_once(state: &mut ShellState) -> io::Result<()> {
         );
     }
     let cwd = env::current_dir().unwrap_or_else(|_| "/".into());
-    let prompt = build_prompt(
-        state.interactive,
-        &state.prompt_template,
-        &state.prompt_function,
-        state.prompt_theme,
-        &state.colors,
-        state.last_status,
-        &cwd,
-    );
-    let prompt = if let Some(name) = state.prompt_function.clone() {
-        run_prompt_function(state, &name).unwrap_or(prompt)
-    } else {
-        prompt
+    // Prompt functions are a fish-ism; POSIX mode falls back to the template.
+    let prompt_function = state.prompt_function.clone().filter(|_| !state.posix);
+    // A failed or timed-out prompt function falls back to the usual prompt.
+    let prompt = prompt_function
+        .and_then(|name| run_prompt_function(state, &name))
+        .unwrap_or_else(|| {
+            build_prompt(
+                state.interactive,
+                &state.prompt_template,
+                &None,
+                state.prompt_theme,
+                &state.colors,
+                state.last_status,
+                &cwd,
+            )
+        });
+    let prompt = crate::completion::measured_prompt(&mut state.editor, wrap_nonprinting(&prompt));
+    let prompt = crate::completion::vi_mode_prompt(&state.editor, prompt);
+
+    // Jobs that finish mid-edit are announced right away rather than at the
+    // next prompt; the printer redraws the line underneath.
+    let read = match state.job_printer.as_deref_mut() {
+        Some(printer) if !state.jobs.is_empty() => {
+            watch_jobs_while(&mut state.jobs, &state.sigchld_flag, printer, || {
+                read_input_line(&mut state.editor, state.interactive, &prompt)
+            })
+        }
+        _ => read_input_line(&mut state.editor, state.interactive, &prompt),
     };
-
-    let line = match read_input_line(&mut state.editor, state.interactive, &prompt)? {
-        Some(line) => line,
-        None => {
+    let line = match read {
+        Ok(Some(line)) => line,
+        Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+            eprintln!("{err}");
+            state.last_status = 2;
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+        Ok(None) => {
+            state.eof_count = state.eof_count.saturating_add(1);
+            let limit = ignoreeof_limit(state.ignoreeof, env::var("IGNOREEOF").ok().as_deref());
+            if !eof_should_exit(state.interactive, limit, state.eof_count) {
+                println!();
+                eprintln!("Use \"exit\" to leave the shell.");
+                return Ok(());
+            }
             if state.interactive {
                 println!();
             }
@@ -375,9 +554,13 @@ pub(crate) fn run_once(state: &mut ShellState) -> io::Result<()> {
                 .unwrap_or_default()
                 .join(".better_shell_history");
             let _ = state.editor.save_history(&history_path);
+            run_logout_file(state);
             std::process::exit(0);
         }
     };
+    state.eof_count = 0;
+    // Interrupts delivered while idle at the prompt must not cancel this line.
+    state.interrupt_flag.store(false, Ordering::SeqCst);
 
     let line = match expand_history_line(state, &line) {
         Ok(expanded) => expanded,
@@ -394,7 +577,7 @@ pub(crate) fn run_once(state: &mut ShellState) -> io::Result<()> {
     }
     state.lineno = state.lineno.saturating_add(1);
 
-    let tokens = if state.interactive {
+    let tokens = if state.lenient() {
         match parse_line_lenient(trimmed) {
             Ok(v) => v,
             Err(msg) => {
@@ -425,6 +608,74 @@ pub(crate) fn run_once(state: &mut ShellState) -> io::Result<()> {
         }
     }
 
+    let segments = if state.lenient() {
+        split_sequence_lenient(tokens)
+    } else {
+        match split_sequence(tokens) {
+            Ok(v) => v,
+            Err(msg) => {
+                eprintln!("parse error: {msg}");
+                state.last_status = 2;
+                return Ok(());
+            }
+        }
+    };
+
+    for segment in segments {
+        let should_run = match segment.op {
+            SeqOp::Always => true,
+            SeqOp::And => state.last_status == 0,
+            SeqOp::Or => state.last_status != 0,
+        };
+        if should_run {
+            let lenient = state.lenient();
+            let Some(segment) = expand_segment(state, segment, !lenient)? else {
+                continue;
+            };
+            let run = |state: &mut ShellState| {
+                if lenient {
+                    execute_segment_lenient(state, segment.tokens, &segment.display)
+                } else {
+                    execute_segment(state, segment.tokens, &segment.display)
+                }
+            };
+            if state.stats {
+                run_with_stats(state, run)?;
+            } else {
+                run(state)?;
+            }
+            if state.reportstatus {
+                let command = segment
+                    .display
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default();
+                if let Some(message) = describe_exit_status(command, state.last_status) {
+                    eprintln!("{message}");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A sequence segment after expansion. The fds of its process substitutions
+/// stay open until it is dropped.
+pub(crate) struct ExpandedSegment {
+    pub(crate) tokens: Vec<String>,
+    pub(crate) display: String,
+    _fd_guard: FdGuard,
+}
+
+/// Expands one `;`, `&&` or `||` segment just before it runs, so `$?` and
+/// `$_` see the segments before it on the same line. Failures are reported
+/// here; `None` means there is nothing to run.
+pub(crate) fn expand_segment(
+    state: &mut ShellState,
+    segment: SeqSegment,
+    strict: bool,
+) -> io::Result<Option<ExpandedSegment>> {
     let glob_options = GlobOptions {
         extglob: state.extglob,
         nullglob: state.nullglob,
@@ -443,20 +694,20 @@ pub(crate) fn run_once(state: &mut ShellState) -> io::Result<()> {
         state.builtin_enabled.clone(),
         glob_options,
         &positional,
-        !state.interactive,
+        state.special_params(),
+        strict,
     );
-    let expanded = match expand_tokens(tokens, &ctx) {
+    let expanded = match expand_tokens(segment.tokens, &ctx) {
         Ok(v) => v,
         Err(msg) => {
-            eprintln!("parse error: {msg}");
-            state.last_status = 2;
-            return Ok(());
+            report_expansion_error(state, &msg);
+            return Ok(None);
         }
     };
     trace_tokens(state, "expanded tokens", &expanded);
 
     if expanded.is_empty() {
-        return Ok(());
+        return Ok(None);
     }
 
     let ProcessSubstResult { tokens: expanded, keep_fds } = match apply_process_subst(
@@ -468,70 +719,71 @@ pub(crate) fn run_once(state: &mut ShellState) -> io::Result<()> {
         state.assoc_arrays.clone(),
         state.builtin_enabled.clone(),
         glob_options,
-        !state.interactive,
+        state.special_params(),
+        strict,
     ) {
         Ok(v) => v,
         Err(err) => {
             eprintln!("process substitution error: {err}");
             state.last_status = 2;
-            return Ok(());
+            return Ok(None);
         }
     };
-    let _fd_guard = FdGuard(keep_fds);
+    let fd_guard = FdGuard(keep_fds);
 
-    let expanded = match expand_globs_with(
-        expanded,
-        GlobOptions {
-            extglob: state.extglob,
-            nullglob: state.nullglob,
-            failglob: state.failglob,
-            dotglob: state.dotglob,
-            nocaseglob: state.nocaseglob,
-            dirspell: state.dirspell,
-        },
-    ) {
+    if !confirm_safeglob(state, &expanded, glob_options)? {
+        state.last_status = 1;
+        return Ok(None);
+    }
+
+    let expanded = match expand_globs_with(expanded, glob_options) {
         Ok(v) => v,
         Err(msg) => {
-            eprintln!("parse error: {msg}");
-            state.last_status = 2;
-            return Ok(());
+            report_expansion_error(state, &msg);
+            return Ok(None);
         }
     };
     trace_tokens(state, "globbed tokens", &expanded);
 
     if expanded.is_empty() {
-        return Ok(());
+        return Ok(None);
     }
+    Ok(Some(ExpandedSegment {
+        tokens: expanded,
+        display: segment.display,
+        _fd_guard: fd_guard,
+    }))
+}
 
-    let segments = if state.interactive {
-        split_sequence_lenient(expanded)
-    } else {
-        match split_sequence(expanded) {
-            Ok(v) => v,
-            Err(msg) => {
-                eprintln!("parse error: {msg}");
-                state.last_status = 2;
-                return Ok(());
-            }
-        }
+// Asks before running a segment whose guarded commands (see `set -o safeglob`)
+// would receive too many glob matches. Scripts never prompt.
+fn confirm_safeglob(
+    state: &mut ShellState,
+    tokens: &[String],
+    options: GlobOptions,
+) -> io::Result<bool> {
+    let Some(threshold) = state.safeglob.filter(|_| state.interactive) else {
+        return Ok(true);
     };
-
-    for segment in segments {
-        let should_run = match segment.op {
-            SeqOp::Always => true,
-            SeqOp::And => state.last_status == 0,
-            SeqOp::Or => state.last_status != 0,
-        };
-        if should_run {
-            if state.interactive {
-                execute_segment_lenient(state, segment.tokens, &segment.display)?;
-            } else {
-                execute_segment(state, segment.tokens, &segment.display)?;
-            }
+    // Parse and glob errors are reported when the segment really runs.
+    let Ok((pipeline, _)) = split_pipeline(tokens.to_vec()) else {
+        return Ok(true);
+    };
+    let Ok(counts) = glob_matches_by_command(&pipeline, options) else {
+        return Ok(true);
+    };
+    let guarded = guarded_commands_from_env();
+    for (command, matched) in counts {
+        if !should_confirm_glob(&command, matched, threshold, &guarded) {
+            continue;
+        }
+        let prompt = format!("{command}: globs matched {matched} paths; run anyway? [y/N] ");
+        let answer = read_input_line(&mut state.editor, true, &prompt)?;
+        if !answer.as_deref().is_some_and(is_confirmation) {
+            return Ok(false);
         }
     }
-
-    Ok(())
+    Ok(true)
 }
 
 pub(crate) fn execute_segment(
@@ -539,11 +791,23 @@ pub(crate) fn execute_segment(
     tokens: Vec<String>,
     display: &str,
 ) -> io::Result<()> {
-    if state.return_requested.is_some() {
+    if state.unwinding() {
         return Ok(());
     }
+    if let Some((format, rest)) = split_time_keyword(&tokens) {
+        return run_timed(state, format, |state| {
+            if rest.is_empty() {
+                return Ok(());
+            }
+            execute_segment(state, rest, display)
+        });
+    }
     run_debug_trap(state)?;
-    let tokens = apply_abbreviations(tokens, &state.abbreviations);
+    let tokens = if state.posix {
+        tokens
+    } else {
+        apply_abbreviations(tokens, &state.abbreviations)
+    };
     let tokens = apply_aliases(tokens, &state.aliases);
     trace_tokens(state, "segment tokens", &tokens);
     if try_handle_array_assignment(state, &tokens)? {
@@ -558,12 +822,13 @@ pub(crate) fn execute_segment(
         }
     };
     apply_command_hash(state, &mut pipeline);
-    if let Err(msg) = heredoc::fill_heredocs(&mut pipeline, state.interactive, &mut state.editor) {
+    if let Err(msg) = fill_and_expand_heredocs(state, &mut pipeline) {
         eprintln!("parse error: {msg}");
         state.last_status = 2;
         return Ok(());
     }
     trace_command_specs(state, &pipeline);
+    record_last_argument(&pipeline);
 
     if pipeline.len() == 1 {
         if let Some(expr) = extract_arithmetic_expr(&pipeline[0]) {
@@ -610,11 +875,17 @@ pub(crate) fn execute_segment(
             JobStatus::Running,
         );
         println!("[{job_id}] {job_pgid}");
+        state.last_background_pid = Some(last_pid);
         state.last_status = 0;
         return Ok(());
     }
 
     if pipeline.len() > 1 {
+        if let Some((last, leading)) = pipeline.split_last() {
+            if let Some(func_tokens) = state.functions.get(&last.args[0]).cloned() {
+                return execute_function_pipeline(state, leading, last, func_tokens, display);
+            }
+        }
         let has_builtin = pipeline.iter().any(|cmd| {
             let name = cmd.args.first().map(String::as_str);
             name.is_some_and(|name| is_builtin(Some(name)) && state.is_builtin_enabled(name))
@@ -653,6 +924,7 @@ pub(crate) fn execute_segment(
                             &state.functions,
                             &state.abbreviations,
                             &state.completions,
+                            &history_command_counts(state.editor.history().iter()),
                         ) {
                             if suggestion != pipeline[0].args[0] {
                                 eprintln!("Command not found—did you mean '{suggestion}'?");
@@ -663,15 +935,19 @@ pub(crate) fn execute_segment(
                 }
             }
         } else {
-            match run_pipeline(
+            let watchdog = maxtime_watchdog(state);
+            let result = run_pipeline(
                 &pipeline,
                 &state.fg_pgid,
                 state.shell_pgid,
                 state.trace,
                 &state.sandbox,
-            ) {
+            );
+            let timed_out = watchdog.is_some_and(|watchdog| watchdog.finish());
+            match result {
                 Ok(result) => {
                     if matches!(result.outcome, WaitOutcome::Stopped) {
+                        let status = result.status_code.unwrap_or(signal_status(libc::SIGTSTP));
                         let job_id = add_job_with_status(
                             &mut state.jobs,
                             &mut state.next_job_id,
@@ -679,10 +955,12 @@ pub(crate) fn execute_segment(
                             result.last_pid,
                             pipeline.len(),
                             display,
-                            JobStatus::Stopped,
+                            JobStatus::Stopped(status),
                         );
-                        println!("[{job_id}] Stopped {display}");
-                        state.last_status = 128 + libc::SIGTSTP;
+                        println!("[{job_id}] {} {display}", status_to_description(status));
+                        state.last_status = status;
+                    } else if timed_out {
+                        state.last_status = TIMEOUT_STATUS;
                     } else {
                         let last = result.status_code.unwrap_or(0);
                         let pipefail = result.pipefail_status.unwrap_or(last);
@@ -698,6 +976,7 @@ pub(crate) fn execute_segment(
                             &state.functions,
                             &state.abbreviations,
                             &state.completions,
+                            &history_command_counts(state.editor.history().iter()),
                         ) {
                             if suggestion != pipeline[0].args[0] {
                                 eprintln!("Command not found—did you mean '{suggestion}'?");
@@ -713,22 +992,126 @@ pub(crate) fn execute_segment(
 
     let cmd = &pipeline[0];
     if let Some(func_tokens) = state.functions.get(&cmd.args[0]) {
-        execute_function(state, func_tokens.clone(), &cmd.args[1..])
+        execute_function(state, &cmd.args[0], func_tokens.clone(), &cmd.args[1..])
     } else {
         execute_builtin(state, cmd, display)
     }
 }
 
+// A pipeline ending in a function: the function reads the earlier stages'
+// output in a subshell. External stages stream into it, so it can stop
+// reading early; builtin output is captured first, as in other pipelines.
+fn execute_function_pipeline(
+    state: &mut ShellState,
+    leading: &[CommandSpec],
+    last: &CommandSpec,
+    func_tokens: Vec<String>,
+    display: &str,
+) -> io::Result<()> {
+    let builtin_enabled = state.builtin_enabled.clone();
+    let trace = state.trace;
+    let sandbox = state.sandbox.clone();
+    let is_builtin = |cmd: &CommandSpec| {
+        is_builtin_enabled_map(&builtin_enabled, cmd.args.first().map(String::as_str))
+    };
+    let (child, sink) =
+        spawn_function_subshell(state, &last.args[0], func_tokens, &last.args[1..])?;
+    let result = if leading.iter().any(is_builtin) {
+        builtin_pipe_capture(
+            leading,
+            is_builtin,
+            |cmd, stdin| execute_builtin_capture(state, cmd, display, stdin),
+            trace,
+            &sandbox,
+        )
+        .map(|result| {
+            // A function that stops reading early closes the pipe; that is fine.
+            let _ = File::from(sink).write_all(result.output.as_bytes());
+            result.pipefail_status
+        })
+    } else {
+        run_pipeline_into(leading, sink, trace, &sandbox).map(|result| result.pipefail_status)
+    };
+    let leading_status = result.unwrap_or_else(|err| {
+        eprintln!("{err}");
+        status_from_error(&err)
+    });
+    let status = wait_for_subshell(child)?;
+    let pipefail = if status == 0 { leading_status } else { status };
+    state.last_status = if state.pipefail { pipefail } else { status };
+    Ok(())
+}
+
+fn fill_and_expand_heredocs(
+    state: &mut ShellState,
+    pipeline: &mut [CommandSpec],
+) -> Result<(), String> {
+    let positional = state.current_positional().to_vec();
+    let ctx = build_expansion_context(
+        Arc::clone(&state.fg_pgid),
+        state.trace,
+        state.sandbox.clone(),
+        state.arrays.clone(),
+        state.assoc_arrays.clone(),
+        state.builtin_enabled.clone(),
+        GlobOptions {
+            extglob: state.extglob,
+            nullglob: state.nullglob,
+            failglob: state.failglob,
+            dotglob: state.dotglob,
+            nocaseglob: state.nocaseglob,
+            dirspell: state.dirspell,
+        },
+        &positional,
+        state.special_params(),
+        !state.lenient(),
+    );
+    heredoc::fill_heredocs(pipeline, state.interactive, &mut state.editor, |body| {
+        expand_heredoc(body, &ctx)
+    })
+}
+
+pub(crate) fn report_expansion_error(state: &mut ShellState, msg: &str) {
+    if is_ambiguous_redirect(msg) {
+        eprintln!("{msg}");
+        state.last_status = 1;
+    } else {
+        eprintln!("parse error: {msg}");
+        state.last_status = 2;
+    }
+}
+
+/// Sets `$_` to the last argument of the command about to run, after
+/// expansion: the final word of the pipeline's last command, so redirection
+/// targets and operators never count. A bare command leaves its own name.
+fn record_last_argument(pipeline: &[CommandSpec]) {
+    if let Some(word) = pipeline.last().and_then(|cmd| cmd.args.last()) {
+        std::env::set_var("_", word);
+    }
+}
+
 fn execute_segment_lenient(
     state: &mut ShellState,
     tokens: Vec<String>,
     display: &str,
 ) -> io::Result<()> {
-    if state.return_requested.is_some() {
+    if state.unwinding() {
         return Ok(());
     }
+    if let Some((format, rest)) = split_time_keyword(&tokens) {
+        return run_timed(state, format, |state| {
+            if rest.is_empty() {
+                return Ok(());
+            }
+            execute_segment_lenient(state, rest, display)
+        });
+    }
     run_debug_trap(state)?;
-    let tokens = apply_abbreviations(tokens, &state.abbreviations);
+    let tokens = if state.posix {
+        tokens
+    } else {
+        apply_abbreviations(tokens, &state.abbreviations)
+    };
     let tokens = apply_aliases(tokens, &state.aliases);
     trace_tokens(state, "segment tokens", &tokens);
     if try_handle_array_assignment(state, &tokens)? {
@@ -739,12 +1122,13 @@ fn execute_segment_lenient(
         return Ok(());
     }
     apply_command_hash(state, &mut pipeline);
-    if let Err(msg) = heredoc::fill_heredocs(&mut pipeline, state.interactive, &mut state.editor) {
+    if let Err(msg) = fill_and_expand_heredocs(state, &mut pipeline) {
         eprintln!("parse error: {msg}");
         state.last_status = 2;
         return Ok(());
     }
     trace_command_specs(state, &pipeline);
+    record_last_argument(&pipeline);
 
     if pipeline.len() == 1 {
         if let Some(expr) = extract_arithmetic_expr(&pipeline[0]) {
@@ -791,11 +1175,17 @@ fn execute_segment_lenient(
             JobStatus::Running,
         );
         println!("[{job_id}] {job_pgid}");
+        state.last_background_pid = Some(last_pid);
         state.last_status = 0;
         return Ok(());
     }
 
     if pipeline.len() > 1 {
+        if let Some((last, leading)) = pipeline.split_last() {
+            if let Some(func_tokens) = state.functions.get(&last.args[0]).cloned() {
+                return execute_function_pipeline(state, leading, last, func_tokens, display);
+            }
+        }
         let has_builtin = pipeline.iter().any(|cmd| {
             let name = cmd.args.first().map(String::as_str);
             name.is_some_and(|name| is_builtin(Some(name)) && state.is_builtin_enabled(name))
@@ -834,6 +1224,7 @@ fn execute_segment_lenient(
                             &state.functions,
                             &state.abbreviations,
                             &state.completions,
+                            &history_command_counts(state.editor.history().iter()),
                         ) {
                             if suggestion != pipeline[0].args[0] {
                                 eprintln!("Command not found—did you mean '{suggestion}'?");
@@ -844,15 +1235,19 @@ fn execute_segment_lenient(
                 }
             }
         } else {
-            match run_pipeline(
+            let watchdog = maxtime_watchdog(state);
+            let result = run_pipeline(
                 &pipeline,
                 &state.fg_pgid,
                 state.shell_pgid,
                 state.trace,
                 &state.sandbox,
-            ) {
+            );
+            let timed_out = watchdog.is_some_and(|watchdog| watchdog.finish());
+            match result {
                 Ok(result) => {
                     if matches!(result.outcome, WaitOutcome::Stopped) {
+                        let status = result.status_code.unwrap_or(signal_status(libc::SIGTSTP));
                         let job_id = add_job_with_status(
                             &mut state.jobs,
                             &mut state.next_job_id,
@@ -860,10 +1255,12 @@ fn execute_segment_lenient(
                             result.last_pid,
                             pipeline.len(),
                             display,
-                            JobStatus::Stopped,
+                            JobStatus::Stopped(status),
                         );
-                        println!("[{job_id}] Stopped {display}");
-                        state.last_status = 128 + libc::SIGTSTP;
+                        println!("[{job_id}] {} {display}", status_to_description(status));
+                        state.last_status = status;
+                    } else if timed_out {
+                        state.last_status = TIMEOUT_STATUS;
                     } else {
                         let last = result.status_code.unwrap_or(0);
                         let pipefail = result.pipefail_status.unwrap_or(last);
@@ -879,6 +1276,7 @@ fn execute_segment_lenient(
                             &state.functions,
                             &state.abbreviations,
                             &state.completions,
+                            &history_command_counts(state.editor.history().iter()),
                         ) {
                             if suggestion != pipeline[0].args[0] {
                                 eprintln!("Command not found—did you mean '{suggestion}'?");
@@ -894,71 +1292,98 @@ fn execute_segment_lenient(
 
     let cmd = &pipeline[0];
     if let Some(func_tokens) = state.functions.get(&cmd.args[0]) {
-        execute_function(state, func_tokens.clone(), &cmd.args[1..])
+        execute_function(state, &cmd.args[0], func_tokens.clone(), &cmd.args[1..])
     } else {
         execute_builtin(state, cmd, display)
     }
 }
 
-pub(crate) fn trace_tokens(state: &ShellState, label: &str, tokens: &[String]) {
+pub(crate) fn trace_tokens(state: &mut ShellState, label: &str, tokens: &[String]) {
     if state.trace {
-        eprintln!("{}trace: {label}: {tokens:?}", trace_prefix(state));
+        let line = format!("{}trace: {label}: {tokens:?}", trace_prefix(state));
+        emit_trace(state, &line);
     }
 }
 
-fn trace_command_specs(state: &ShellState, pipeline: &[CommandSpec]) {
+fn trace_command_specs(state: &mut ShellState, pipeline: &[CommandSpec]) {
     if !state.trace {
         return;
     }
+    let prefix = trace_prefix(state);
+    let mut lines = Vec::new();
     for (idx, cmd) in pipeline.iter().enumerate() {
-        eprintln!("{}trace: argv[{idx}]: {:?}", trace_prefix(state), cmd.args);
+        lines.push(format!("{prefix}trace: argv[{idx}]: {:?}", cmd.args));
         if let Some(directive) = cmd.sandbox {
-            eprintln!("{}trace: sandbox {directive:?}", trace_prefix(state));
+            lines.push(format!("{prefix}trace: sandbox {directive:?}"));
         }
         if let Some(ref path) = cmd.stdin {
-            eprintln!("{}trace: redirect stdin < {}", trace_prefix(state), path);
+            lines.push(format!("{prefix}trace: redirect stdin < {path}"));
         }
         if let Some(ref heredoc) = cmd.heredoc {
             if let Some(ref content) = heredoc.content {
-                eprintln!(
-                    "{}trace: redirect stdin << heredoc ({} bytes)",
-                    trace_prefix(state),
+                lines.push(format!(
+                    "{prefix}trace: redirect stdin << heredoc ({} bytes)",
                     content.len()
-                );
+                ));
             } else {
-                eprintln!(
-                    "{}trace: redirect stdin << {}",
-                    trace_prefix(state),
+                lines.push(format!(
+                    "{prefix}trace: redirect stdin << {}",
                     heredoc.delimiter
-                );
+                ));
             }
         }
         if let Some(ref content) = cmd.herestring {
-            eprintln!(
-                "{}trace: redirect stdin <<< ({} bytes)",
-                trace_prefix(state),
-                content.as_bytes().len()
-            );
+            lines.push(format!(
+                "{prefix}trace: redirect stdin <<< ({} bytes)",
+                content.len()
+            ));
         }
         if let Some(ref out) = cmd.stdout {
             let mode = if out.append { ">>" } else { ">" };
-            eprintln!(
-                "{}trace: redirect stdout {mode} {}",
-                trace_prefix(state),
+            lines.push(format!(
+                "{prefix}trace: redirect stdout {mode} {}",
                 out.path
-            );
+            ));
+        } else if cmd.stdout_to_stderr {
+            lines.push(format!("{prefix}trace: redirect stdout >&2"));
         }
         if cmd.stderr_to_stdout {
-            eprintln!("{}trace: redirect stderr >&1", trace_prefix(state));
+            lines.push(format!("{prefix}trace: redirect stderr >&1"));
         } else if cmd.stderr_close {
-            eprintln!("{}trace: redirect stderr >&-", trace_prefix(state));
+            lines.push(format!("{prefix}trace: redirect stderr >&-"));
         } else if let Some(ref err) = cmd.stderr {
             let mode = if err.append { ">>" } else { ">" };
-            eprintln!(
-                "{}trace: redirect stderr 2{mode} {}",
-                trace_prefix(state),
+            lines.push(format!(
+                "{prefix}trace: redirect stderr 2{mode} {}",
                 err.path
-            );
+            ));
+        }
+    }
+    for line in lines {
+        emit_trace(state, &line);
+    }
+}
+
+fn emit_trace(state: &mut ShellState, line: &str) {
+    let _ = writeln!(state.trace_out, "{line}");
+    let _ = state.trace_out.flush();
+}
+
+// Like BASH_XTRACEFD: a descriptor number, or a file path to append to.
+const XTRACE_FD_ENV: &str = "MINISHELL_XTRACE_FD";
+
+fn open_trace_sink(spec: &str) -> io::Result<Box<dyn Write>> {
+    match spec.parse::<i32>() {
+        Ok(libc::STDOUT_FILENO) => Ok(Box::new(io::stdout())),
+        Ok(libc::STDERR_FILENO) => Ok(Box::new(io::stderr())),
+        Ok(fd) => {
+            // Duplicate so dropping the sink never closes the caller's descriptor.
+            let dup = nix::unistd::dup(fd).map_err(io::Error::from)?;
+            Ok(Box::new(unsafe { File::from_raw_fd(dup) }))
+        }
+        Err(_) => {
+            let file = OpenOptions::new().create(true).append(true).open(spec)?;
+            Ok(Box::new(file))
         }
     }
 }
@@ -1169,6 +1594,16 @@ fn extract_arithmetic_expr(cmd: &CommandSpec) -> Option<String> {
 
 fn run_prompt_function(state: &mut ShellState, name: &str) -> Option<String> {
     let tokens = state.functions.get(name)?.clone();
+    let cache_key = state.prompt_cache.as_ref().map(|_| {
+        let cwd = env::current_dir().unwrap_or_else(|_| "/".into());
+        PromptCacheKey::new(&tokens, &cwd)
+    });
+    if let Some(prompt) = cache_key
+        .as_ref()
+        .and_then(|key| state.prompt_cache.as_ref()?.get(key))
+    {
+        return Some(prompt.to_string());
+    }
     let saved_status = state.last_status;
     let glob_options = GlobOptions {
         extglob: state.extglob,
@@ -1178,19 +1613,38 @@ fn run_prompt_function(state: &mut ShellState, name: &str) -> Option<String> {
         nocaseglob: state.nocaseglob,
         dirspell: state.dirspell,
     };
-    let result = execute_tokens_capture(
-        tokens,
-        Arc::clone(&state.fg_pgid),
-        state.trace,
-        state.sandbox.clone(),
-        state.arrays.clone(),
-        state.assoc_arrays.clone(),
-        state.builtin_enabled.clone(),
-        glob_options,
-        true,
-    )
-    .ok();
+    let capture = || {
+        execute_tokens_capture(
+            tokens,
+            Arc::clone(&state.fg_pgid),
+            state.trace,
+            state.sandbox.clone(),
+            state.arrays.clone(),
+            state.assoc_arrays.clone(),
+            state.builtin_enabled.clone(),
+            glob_options,
+            state.special_params(),
+            true,
+        )
+        .ok()
+    };
+    // A slow function (git in a huge repo, say) must not freeze the prompt.
+    let result = match prompt_timeout(env::var("MINISHELL_PROMPT_TIMEOUT").ok().as_deref()) {
+        Some(limit) => capture_within(&state.fg_pgid, limit, capture).unwrap_or_else(|| {
+            eprintln!(
+                "prompt: {name} took longer than {}s; using the default prompt",
+                limit.as_secs_f64()
+            );
+            None
+        }),
+        None => capture(),
+    };
     state.last_status = saved_status;
+    if let (Some(cache), Some(key), Some(prompt)) =
+        (state.prompt_cache.as_mut(), cache_key, result.as_ref())
+    {
+        cache.store(key, prompt.clone());
+    }
     result
 }
 
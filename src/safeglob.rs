@@ -0,0 +1,146 @@
+//! `set -o safeglob`: ask before a guarded command such as `rm` receives a
+//! glob that matched a large number of files.
+
+use std::path::Path;
+
+use crate::expansion::{expand_globs_with, glob_pattern, GlobOptions};
+use crate::parse::{strip_markers, CommandSpec};
+use crate::utils::is_valid_var_name;
+
+/// Matches needed before `set -o safeglob` asks; `set -o safeglob=N`
+/// overrides it.
+pub(crate) const DEFAULT_SAFEGLOB_THRESHOLD: usize = 20;
+
+// Whitespace- or colon-separated command names read from the environment.
+const SAFEGLOB_COMMANDS_VAR: &str = "MINISHELL_SAFEGLOB_COMMANDS";
+const DEFAULT_GUARDED: &str = "rm";
+
+/// Commands that trigger the prompt, from `MINISHELL_SAFEGLOB_COMMANDS`
+/// or just `rm` when it is unset.
+pub(crate) fn guarded_commands(var: Option<&str>) -> Vec<String> {
+    var.unwrap_or(DEFAULT_GUARDED)
+        .split(|ch: char| ch.is_whitespace() || ch == ':')
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+pub(crate) fn guarded_commands_from_env() -> Vec<String> {
+    guarded_commands(std::env::var(SAFEGLOB_COMMANDS_VAR).ok().as_deref())
+}
+
+/// True when `command` (by basename, so `/bin/rm` counts as `rm`) is guarded
+/// and its globs matched at least `threshold` paths.
+pub(crate) fn should_confirm_glob(
+    command: &str,
+    matched: usize,
+    threshold: usize,
+    guarded: &[String],
+) -> bool {
+    let name = Path::new(command)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(command);
+    matched >= threshold && guarded.iter().any(|guarded| guarded == name)
+}
+
+/// For each command of a pipeline parsed from not-yet-globbed words, returns
+/// its name and how many paths its glob arguments match, skipping commands
+/// without globs.
+pub(crate) fn glob_matches_by_command(
+    pipeline: &[CommandSpec],
+    options: GlobOptions,
+) -> Result<Vec<(String, usize)>, String> {
+    let mut counts = Vec::new();
+    for cmd in pipeline {
+        let mut words = cmd
+            .args
+            .iter()
+            .skip_while(|word| is_command_prefix(&strip_markers(word)));
+        let Some(name) = words.next() else {
+            continue;
+        };
+        let mut matched = 0;
+        for word in words {
+            if glob_pattern(word).1 {
+                matched += expand_globs_with(vec![word.clone()], options)?.len();
+            }
+        }
+        if matched > 0 {
+            counts.push((strip_markers(name), matched));
+        }
+    }
+    Ok(counts)
+}
+
+// Words ahead of the command that decides the prompt: reserved words, and
+// the `time`, `command`, `builtin` and `glob` prefixes with their options.
+const COMMAND_PREFIXES: &[&str] = &[
+    "!", "{", "(", "if", "then", "elif", "else", "while", "until", "do", "time", "command",
+    "builtin", "glob", "-p", "--",
+];
+
+// Assignments before a command only set its environment.
+fn is_command_prefix(word: &str) -> bool {
+    COMMAND_PREFIXES.contains(&word)
+        || word
+            .split_once('=')
+            .is_some_and(|(name, _)| is_valid_var_name(name))
+}
+
+/// Reads a yes/no answer; anything but `y` or `yes` declines.
+pub(crate) fn is_confirmation(answer: &str) -> bool {
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{parse_line, split_pipeline};
+
+    #[test]
+    fn prompts_only_for_guarded_commands_over_the_threshold() {
+        let guarded = guarded_commands(None);
+        assert!(should_confirm_glob("rm", 20, 20, &guarded));
+        assert!(should_confirm_glob("/bin/rm", 50, 20, &guarded));
+        assert!(!should_confirm_glob("rm", 19, 20, &guarded));
+        assert!(!should_confirm_glob("ls", 500, 20, &guarded));
+
+        let guarded = guarded_commands(Some("rm:mv shred"));
+        assert!(should_confirm_glob("shred", 3, 3, &guarded));
+        assert!(should_confirm_glob("mv", 3, 3, &guarded));
+        assert!(is_confirmation(" Yes\n"));
+        assert!(!is_confirmation(""));
+    }
+
+    #[test]
+    fn counts_glob_matches_per_command() {
+        let dir = tempfile::tempdir().unwrap();
+        for idx in 0..4 {
+            std::fs::write(dir.path().join(format!("f{idx}.log")), "").unwrap();
+        }
+        let pattern = format!("{}/*.log", dir.path().display());
+        let options = GlobOptions {
+            extglob: false,
+            nullglob: false,
+            failglob: false,
+            dotglob: false,
+            nocaseglob: false,
+            dirspell: false,
+        };
+        let counts = |line: &str| {
+            let (pipeline, _) = split_pipeline(parse_line(line).unwrap()).unwrap();
+            glob_matches_by_command(&pipeline, options).unwrap()
+        };
+        let rm = vec![("rm".to_string(), 4)];
+        assert_eq!(counts(&format!("rm -f {pattern}")), rm);
+        assert_eq!(counts(&format!("FOO=1 rm {pattern}")), rm);
+        assert_eq!(counts(&format!("time -p rm {pattern}")), rm);
+        assert_eq!(counts(&format!("command rm {pattern} > out")), rm);
+        assert_eq!(
+            counts(&format!("ls {pattern} | xargs echo")),
+            vec![("ls".to_string(), 4)]
+        );
+        assert!(counts("echo plain").is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+use std::env;
+use std::fmt::Write;
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::ShellState;
+
+// Bash's default: a blank line, then real/user/sys in long form.
+pub(crate) const DEFAULT_TIMEFORMAT: &str = "\nreal\t%3lR\nuser\t%3lU\nsys\t%3lS";
+const POSIX_TIMEFORMAT: &str = "real %2R\nuser %2U\nsys %2S";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ChildUsage {
+    pub user: Duration,
+    pub sys: Duration,
+    pub max_rss_kb: i64,
+    pub minor_faults: i64,
+}
+
+pub(crate) fn children_usage() -> ChildUsage {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+    let rc = unsafe { libc::getrusage(libc::RUSAGE_CHILDREN, usage.as_mut_ptr()) };
+    if rc != 0 {
+        return ChildUsage::default();
+    }
+    let usage = unsafe { usage.assume_init() };
+    ChildUsage {
+        user: timeval_duration(usage.ru_utime),
+        sys: timeval_duration(usage.ru_stime),
+        max_rss_kb: usage.ru_maxrss,
+        minor_faults: usage.ru_minflt,
+    }
+}
+
+fn timeval_duration(tv: libc::timeval) -> Duration {
+    Duration::from_secs(tv.tv_sec.max(0) as u64) + Duration::from_micros(tv.tv_usec.max(0) as u64)
+}
+
+/// Splits a leading `time` keyword (and `-p`) off a segment, returning the
+/// format to report with and the remaining command tokens.
+pub(crate) fn split_time_keyword(tokens: &[String]) -> Option<(Option<String>, Vec<String>)> {
+    if tokens.first().map(String::as_str) != Some("time") {
+        return None;
+    }
+    let mut rest = &tokens[1..];
+    let mut format = env::var("TIMEFORMAT").ok();
+    if rest.first().map(String::as_str) == Some("-p") {
+        format = Some(POSIX_TIMEFORMAT.to_string());
+        rest = &rest[1..];
+    }
+    Some((format, rest.to_vec()))
+}
+
+pub(crate) fn run_timed<F>(state: &mut ShellState, format: Option<String>, run: F) -> io::Result<()>
+where
+    F: FnOnce(&mut ShellState) -> io::Result<()>,
+{
+    let before = children_usage();
+    let start = Instant::now();
+    let result = run(state);
+    let real = start.elapsed();
+    let after = children_usage();
+    let format = format.as_deref().unwrap_or(DEFAULT_TIMEFORMAT);
+    // An empty TIMEFORMAT suppresses the report, as in bash.
+    if !format.is_empty() {
+        let report = format_time_report(
+            format,
+            real,
+            after.user.saturating_sub(before.user),
+            after.sys.saturating_sub(before.sys),
+        );
+        eprintln!("{report}");
+    }
+    result
+}
+
+pub(crate) fn run_with_stats<F>(state: &mut ShellState, run: F) -> io::Result<()>
+where
+    F: FnOnce(&mut ShellState) -> io::Result<()>,
+{
+    let before = children_usage();
+    let result = run(state);
+    if let Some(report) = format_usage_report(&before, &children_usage()) {
+        eprintln!("{report}");
+    }
+    result
+}
+
+/// Summarizes the child resource usage accrued between two samples. Returns
+/// `None` when no child was reaped in between, which keeps builtins quiet.
+pub(crate) fn format_usage_report(before: &ChildUsage, after: &ChildUsage) -> Option<String> {
+    if before == after {
+        return None;
+    }
+    let user = after.user.saturating_sub(before.user);
+    let sys = after.sys.saturating_sub(before.sys);
+    let mut out = String::from("stats: cpu ");
+    push_duration(&mut out, user + sys, 3, false);
+    out.push_str("s (user ");
+    push_duration(&mut out, user, 3, false);
+    out.push_str("s, sys ");
+    push_duration(&mut out, sys, 3, false);
+    // ru_maxrss is a high-water mark across all children, not a delta.
+    let _ = write!(
+        out,
+        "s), max rss {} KB, {} minor faults",
+        after.max_rss_kb,
+        after.minor_faults - before.minor_faults
+    );
+    Some(out)
+}
+
+/// Expands bash-style TIMEFORMAT specifiers: `%[p][l]R`, `%[p][l]U`,
+/// `%[p][l]S`, `%P` and `%%`, where `p` is the precision (0-3) and `l`
+/// selects the `MmSS.FFFs` long form.
+pub(crate) fn format_time_report(
+    format: &str,
+    real: Duration,
+    user: Duration,
+    sys: Duration,
+) -> String {
+    let mut out = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            out.push(ch);
+            continue;
+        }
+        let mut precision = 3;
+        if let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+            precision = digit.min(3);
+            chars.next();
+        }
+        let long = chars.next_if_eq(&'l').is_some();
+        match chars.next() {
+            Some('R') => push_duration(&mut out, real, precision, long),
+            Some('U') => push_duration(&mut out, user, precision, long),
+            Some('S') => push_duration(&mut out, sys, precision, long),
+            Some('P') => {
+                let cpu = (user + sys).as_secs_f64();
+                let real = real.as_secs_f64();
+                let percent = if real > 0.0 { cpu / real * 100.0 } else { 0.0 };
+                let _ = write!(out, "{percent:.2}");
+            }
+            Some('%') => out.push('%'),
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+            }
+            None => out.push('%'),
+        }
+    }
+    out
+}
+
+fn push_duration(out: &mut String, value: Duration, precision: u32, long: bool) {
+    // Truncate like bash so 59.9999s never prints as 60.000s.
+    let scale = 10u128.pow(precision);
+    let units = value.as_micros() * scale / 1_000_000;
+    let whole = units / scale;
+    let frac = units % scale;
+    let width = precision as usize;
+    if long {
+        let _ = write!(out, "{}m{}", whole / 60, whole % 60);
+    } else {
+        let _ = write!(out, "{whole}");
+    }
+    if precision > 0 {
+        let _ = write!(out, ".{frac:0width$}");
+    }
+    if long {
+        out.push('s');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeformat_single_line() {
+        let report = format_time_report(
+            "%R %U %S",
+            Duration::from_millis(1500),
+            Duration::from_millis(250),
+            Duration::from_micros(10_400),
+        );
+        assert_eq!(report, "1.500 0.250 0.010");
+
+        let report = format_time_report(
+            "real=%1R cpu=%P%%",
+            Duration::from_secs(2),
+            Duration::from_millis(500),
+            Duration::from_millis(500),
+        );
+        assert_eq!(report, "real=2.0 cpu=50.00%");
+    }
+
+    #[test]
+    fn usage_report_from_rusage_delta() {
+        let before = ChildUsage {
+            user: Duration::from_millis(100),
+            sys: Duration::from_millis(20),
+            max_rss_kb: 2048,
+            minor_faults: 10,
+        };
+        let after = ChildUsage {
+            user: Duration::from_millis(350),
+            sys: Duration::from_millis(45),
+            max_rss_kb: 5120,
+            minor_faults: 90,
+        };
+        assert_eq!(
+            format_usage_report(&before, &after).as_deref(),
+            Some("stats: cpu 0.275s (user 0.250s, sys 0.025s), max rss 5120 KB, 80 minor faults")
+        );
+        assert_eq!(format_usage_report(&after, &after), None);
+    }
+
+    #[test]
+    fn default_timeformat_is_three_lines() {
+        let report = format_time_report(
+            DEFAULT_TIMEFORMAT,
+            Duration::from_millis(61_234),
+            Duration::from_millis(5),
+            Duration::ZERO,
+        );
+        assert_eq!(report, "\nreal\t1m1.234s\nuser\t0m0.005s\nsys\t0m0.000s");
+    }
+}
@@ -160,6 +160,20 @@ fn pty_prompt_and_ctrl_c() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+#[serial]
+fn pipeline_into_function_at_the_prompt() -> anyhow::Result<()> {
+    let mut session = PtySession::spawn()?;
+    session.read_until_prompt(Duration::from_secs(5))?;
+    session.send_line("function shout { tr a-z A-Z; }")?;
+    session.read_until_prompt(Duration::from_secs(5))?;
+    session.send_line("echo hi | shout")?;
+    let output = session.read_until_prompt(Duration::from_secs(5))?;
+    assert!(output.contains("HI"), "output: {output}");
+    session.send_line("exit")?;
+    Ok(())
+}
+
 #[test]
 #[serial]
 fn background_pipeline_reaped() -> anyhow::Result<()> {
@@ -20,3 +20,87 @@ fn sequence_black_box() {
     assert!(matches!(segments[2].op, SeqOp::Or));
     assert!(matches!(segments[3].op, SeqOp::Always));
 }
+
+#[test]
+fn herestring_and_fd_redirects_black_box() {
+    let tokens = parse_tokens("cat <<< word 2>> log 1> out").unwrap();
+    let (pipeline, _) = parse_pipeline(tokens).unwrap();
+    assert_eq!(pipeline[0].args, vec!["cat"]);
+    assert_eq!(pipeline[0].herestring.as_deref(), Some("word"));
+    let stderr = pipeline[0].stderr.as_ref().unwrap();
+    assert_eq!((stderr.path.as_str(), stderr.append), ("log", true));
+    let stdout = pipeline[0].stdout.as_ref().unwrap();
+    assert_eq!((stdout.path.as_str(), stdout.append), ("out", false));
+}
+
+// The library and the shell share one tokenizer, so the same line must
+// behave the same way when the shell actually runs it.
+#[cfg(feature = "shell")]
+#[test]
+fn library_and_shell_agree_on_redirects() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let log = dir.path().join("log");
+    let line = format!(
+        "cat <<< word 1> {} ; ls /definitely/missing 2>> {}",
+        out.display(),
+        log.display()
+    );
+
+    let tokens = parse_tokens(&line).unwrap();
+    let segments = parse_sequence(tokens).unwrap();
+    let (first, _) = parse_pipeline(segments[0].tokens.clone()).unwrap();
+    let (second, _) = parse_pipeline(segments[1].tokens.clone()).unwrap();
+    assert_eq!(first[0].herestring.as_deref(), Some("word"));
+    assert_eq!(
+        first[0].stdout.as_ref().unwrap().path,
+        out.display().to_string()
+    );
+    assert_eq!(
+        second[0].stderr.as_ref().unwrap().path,
+        log.display().to_string()
+    );
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_better_shell"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .as_mut()
+        .unwrap()
+        .write_all(format!("{line}\nexit 0\n").as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(std::fs::read_to_string(&out).unwrap(), "word\n");
+    assert!(!std::fs::read_to_string(&log).unwrap().is_empty());
+}
+
+#[cfg(feature = "expansion")]
+#[test]
+fn pipeline_fuzz_helper_smoke() {
+    use better_shell::fuzz_pipeline_bytes;
+
+    let root = tempfile::tempdir().unwrap();
+    std::fs::write(root.path().join("a.rs"), "").unwrap();
+    for input in [
+        "cat <<< ${HOME%/*} > out 2>&1 | sort",
+        "ls *.rs ../* /etc/* \"$(date)\" && echo ${arr[@]}",
+        "cat <<EOF",
+        "echo ${x:-a} ${#y} >",
+        "",
+    ] {
+        fuzz_pipeline_bytes(input.as_bytes(), root.path());
+    }
+    fuzz_pipeline_bytes(&[0xff, b'*', 0x1e, b'|'], root.path());
+}
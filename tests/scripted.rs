@@ -5,7 +5,13 @@ use std::process::{Command, Stdio};
 use tempfile::TempDir;
 
 fn run_script(script: &str) -> (String, String, i32) {
-    let mut child = Command::new(env!("CARGO_BIN_EXE_better_shell"))
+    run_script_with(script, |_| {})
+}
+
+fn run_script_with(script: &str, configure: impl FnOnce(&mut Command)) -> (String, String, i32) {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_better_shell"));
+    configure(&mut command);
+    let mut child = command
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -75,3 +81,885 @@ fn scripted_heredoc() {
     assert!(out.contains("hello"));
     assert_eq!(code, 0);
 }
+
+#[test]
+fn scripted_rejects_nul_input_line() {
+    let dir = TempDir::new().expect("tempdir");
+    let marker = dir.path().join("created");
+    let script = format!("touch {}\0junk\necho after\nexit 0\n", marker.display());
+    let (out, err, code) = run_script(&script);
+    assert!(err.contains("NUL byte"), "stderr: {err}");
+    assert!(!marker.exists());
+    assert!(out.contains("after"));
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_sigint_breaks_loop() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_better_shell"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("spawn shell");
+    {
+        let stdin = child.stdin.as_mut().expect("stdin");
+        stdin
+            .write_all(b"for i in $(seq 1 200000); do echo $i; done\necho after\nexit 0\n")
+            .expect("write");
+    }
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    unsafe {
+        libc::kill(child.id() as i32, libc::SIGINT);
+    }
+    let output = child.wait_with_output().expect("wait");
+    let out = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        out.contains("after"),
+        "stdout tail: {}",
+        &out[out.len().saturating_sub(200)..]
+    );
+    assert!(!out.contains("\n200000\n"));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn scripted_timeout_builtin() {
+    let (out, err, code) = run_script("timeout 5 sh -c \"echo fast; exit 3\"\nexit\n");
+    assert!(err.is_empty(), "stderr: {err}");
+    assert!(out.contains("fast"));
+    assert_eq!(code, 3);
+
+    let start = std::time::Instant::now();
+    let (_, err, code) = run_script("timeout 200ms sleep 5\nexit\n");
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(code, 124);
+    assert!(start.elapsed() < std::time::Duration::from_secs(4));
+}
+
+#[test]
+fn scripted_exit_wraps_and_rejects_non_numeric_codes() {
+    let (_, err, code) = run_script("exit -1\n");
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(code, 255);
+    let (out, err, code) = run_script("exit two\necho unreachable\n");
+    assert!(out.is_empty(), "stdout: {out}");
+    assert!(
+        err.contains("exit: numeric argument required"),
+        "stderr: {err}"
+    );
+    assert_eq!(code, 2);
+}
+
+#[test]
+fn scripted_question_mark_holds_last_status() {
+    let (out, err, _) = run_script(
+        "false\necho $?\nsh -c 'exit 7'\necho ${?} $(echo $?)\necho $?\n",
+    );
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, "1\n7 7\n0\n");
+}
+
+#[test]
+fn scripted_question_mark_sees_earlier_commands_on_the_same_line() {
+    let script = "false; echo $?\ncat /nonexistent 2>/dev/null || echo \"failed: $?\"\n\
+                  function f {\nfalse; echo in=$?\n}\nf\n\
+                  if true; then false; echo if=$?; fi\nexit\n";
+    let (out, err, _) = run_script(script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, "1\nfailed: 1\nin=1\nif=1\n");
+}
+
+#[test]
+fn scripted_background_pid_and_shell_pid() {
+    let (out, err, _) = run_script("sleep 1 &\necho $! \"$!\" $(echo $!)\necho $$ \"$$\"\n");
+    assert!(err.is_empty(), "stderr: {err}");
+    let lines: Vec<&str> = out.lines().collect();
+    let job_pid = lines[0].split_whitespace().nth(1).expect("job pid");
+    assert_eq!(lines[1], format!("{job_pid} {job_pid} {job_pid}"));
+    let shell_pid: Vec<&str> = lines[2].split_whitespace().collect();
+    assert_eq!(shell_pid.len(), 2);
+    assert_eq!(shell_pid[0], shell_pid[1]);
+    assert_ne!(shell_pid[0], job_pid);
+}
+
+#[test]
+fn scripted_fg_and_bg_name_the_missing_job() {
+    let (out, err, _) = run_script("fg\necho $?\n");
+    assert_eq!(err, "fg: current: no such job\n");
+    assert_eq!(out, "1\n");
+    let (out, err, _) = run_script("sleep 1 &\nfg %7\necho $?\n");
+    assert_eq!(err, "fg: %7: no such job\n");
+    assert!(out.ends_with("\n1\n"), "stdout: {out}");
+    let (_, err, _) = run_script("bg\n");
+    assert_eq!(err, "bg: current: no such job\n");
+}
+
+#[test]
+fn scripted_read_assigns_fields_from_the_next_line() {
+    let (out, err, _) = run_script(
+        "read first rest\none two  three\necho $first / $rest\nread\nplain\necho $REPLY\nread x || echo eof\n",
+    );
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, "one / two three\nplain\neof\n");
+}
+
+#[test]
+fn scripted_read_takes_redirected_input() {
+    let dir = TempDir::new().expect("tempdir");
+    let file = dir.path().join("lines.txt");
+    std::fs::write(&file, "l1\nl2\n").expect("write");
+    let script = format!(
+        "read a < {0}\necho a=$a\nwhile read line; do echo got $line; done < {0}\necho end\n",
+        file.display()
+    );
+    let (out, err, _) = run_script(&script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, "a=l1\ngot l1\ngot l2\nend\n");
+}
+
+#[test]
+fn scripted_read_takes_pipeline_input() {
+    let (out, err, _) = run_script("printf 'p q r\\n' | read c d; echo c=$c d=$d\necho end\n");
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, "c=p d=q r\nend\n");
+}
+
+#[test]
+fn scripted_wait_n_returns_when_the_first_job_finishes() {
+    let (out, err, _) = run_script("wait -n\necho $?\n");
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, "127\n");
+
+    let started = std::time::Instant::now();
+    let (out, err, _) =
+        run_script("sh -c 'sleep 0.2; exit 4' &\nsleep 5 &\nwait -n\necho $?\njobs\nkill $!\n");
+    assert!(err.is_empty(), "stderr: {err}");
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[2], "4");
+    assert_eq!(lines[3], "[2]+ Running sleep 5");
+    assert!(started.elapsed() < std::time::Duration::from_secs(4));
+}
+
+#[test]
+fn scripted_wait_returns_when_a_job_stops() {
+    let started = std::time::Instant::now();
+    let script = "sleep 30 &\nexport pid=$!\nsh -c 'sleep 0.3; kill -STOP $pid' &\n\
+                  wait\necho stopped=$?\nwait\necho left=$?\nwait -n\necho n=$?\n\
+                  kill -KILL $pid\n";
+    let (out, err, _) = run_script(script);
+    assert!(err.is_empty(), "stderr: {err}");
+    let statuses: Vec<&str> = out.lines().filter(|line| line.contains('=')).collect();
+    assert_eq!(
+        statuses,
+        ["stopped=147", "left=0", "n=127"],
+        "stdout: {out}"
+    );
+    assert!(started.elapsed() < std::time::Duration::from_secs(10));
+}
+
+#[test]
+fn scripted_quoted_at_keeps_arguments_apart() {
+    let script = "count () {\n echo $#\n}\nf () {\n count \"$@\"\n count \"$*\"\n count $*\n count \"$@\" end\n}\nf a \"b c\"\nf\n";
+    let (out, err, _) = run_script(script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, "2\n1\n3\n3\n0\n1\n0\n1\n");
+}
+
+#[test]
+fn scripted_ansi_c_quotes_keep_decoded_text_in_one_word() {
+    let script = "printf '[%s]' $'a\\tb' $'x y' $'$HOME' \"c\\td\"\necho\necho $'one\\ntwo'\n";
+    let (out, err, _) = run_script(script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, "[a\tb][x y][$HOME][c\td]\none\ntwo\n");
+}
+
+#[test]
+fn scripted_local_does_not_outlive_the_function() {
+    let script = "export x=outer\nshow () {\n echo $x:$y\n}\nf () {\n local x=1 y=2\n show\n}\nf\necho $x\necho ${y:-unset}\n";
+    let (out, err, _) = run_script(script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, "1:2\nouter\nunset\n");
+}
+
+#[test]
+fn scripted_stdout_and_stderr_duplication_follows_redirection_order() {
+    let dir = TempDir::new().expect("tempdir");
+    let both = dir.path().join("both");
+    let only_out = dir.path().join("only_out");
+    let noisy = "sh -c 'echo out; echo err >&2'";
+    let script = format!(
+        "{noisy} >{both} 2>&1\n{noisy} 2>&1 >{only_out} | sed s/^/piped:/\necho moved >&2\nsh -c 'echo external' 1>&2\nexit 0\n",
+        both = both.display(),
+        only_out = only_out.display(),
+    );
+    let (out, err, code) = run_script(&script);
+    assert_eq!(out, "piped:err\n", "stderr: {err}");
+    assert_eq!(err, "moved\nexternal\n");
+    let read = |path: &std::path::Path| std::fs::read_to_string(path).expect("output file");
+    assert_eq!(read(&both), "out\nerr\n");
+    assert_eq!(read(&only_out), "out\n");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_env_builtin_clears_and_unsets_for_child() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_better_shell"))
+        .env("ENV_BUILTIN_PROBE", "visible")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("spawn shell");
+    {
+        let stdin = child.stdin.as_mut().expect("stdin");
+        stdin
+            .write_all(
+                b"env -i ONLY=1 /usr/bin/env\necho ---\nenv -u ENV_BUILTIN_PROBE /usr/bin/env\n\
+echo ---\nenv\nexit 0\n",
+            )
+            .expect("write");
+    }
+    let output = child.wait_with_output().expect("wait");
+    let out = String::from_utf8_lossy(&output.stdout);
+    let err = String::from_utf8_lossy(&output.stderr);
+    assert!(err.is_empty(), "stderr: {err}");
+    let sections: Vec<&str> = out.split("---\n").collect();
+    assert_eq!(sections.len(), 3, "stdout: {out}");
+    assert_eq!(sections[0], "ONLY=1\n");
+    assert!(!sections[1].contains("ENV_BUILTIN_PROBE="));
+    assert!(sections[1].contains("PATH="));
+    assert!(sections[2].contains("ENV_BUILTIN_PROBE=visible"));
+}
+
+#[test]
+fn scripted_tilde_in_for_list_and_assignment() {
+    let home = std::env::var("HOME").expect("HOME");
+    let script = "for d in ~/a ~/b; do echo $d; done\nenv X=~/a:~/b /usr/bin/printenv X\nexit 0\n";
+    let (out, err, code) = run_script(script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, format!("{home}/a\n{home}/b\n{home}/a:{home}/b\n"));
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_echo_escape_flags() {
+    let script =
+        "echo -e 'A\\x42\\0103\\u00e9|\\q'\necho -n no-newline\necho -E 'raw\\t'\nexit 0\n";
+    let (out, err, code) = run_script(script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, "ABCé|\\q\nno-newlineraw\\t\n");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_caller_reports_nested_frames() {
+    let script =
+        "function inner { caller; caller 1; }\nfunction outer { inner; }\nouter\ncaller\nexit\n";
+    let (out, err, code) = run_script(script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, "3 inner\n3 outer\n3 outer\n");
+    assert_eq!(code, 1);
+}
+
+#[test]
+fn scripted_which_builtin_resolves_names() {
+    let script = "which sh\nwhich cd\nwhich definitely_not_a_command_xyz\nexit\n";
+    let (out, err, code) = run_script(script);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 2, "stdout: {out}");
+    assert!(lines[0].starts_with('/') && lines[0].ends_with("/sh"));
+    assert_eq!(lines[1], "cd: shell builtin");
+    assert!(err.contains("definitely_not_a_command_xyz not found"));
+    assert_eq!(code, 1);
+}
+
+#[test]
+fn scripted_cd_tracks_pwd_and_oldpwd() {
+    let dir = TempDir::new().expect("tempdir");
+    let root = std::fs::canonicalize(dir.path()).unwrap();
+    std::fs::create_dir(root.join("real")).unwrap();
+    std::os::unix::fs::symlink(root.join("real"), root.join("link")).unwrap();
+    let root = root.display();
+    let script = format!(
+        "cd {root}\ncd link\necho $PWD $OLDPWD\ncd ..\necho $PWD\ncd -P link\necho $PWD\ncd -\nexit 0\n"
+    );
+    let (out, err, code) = run_script(&script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(
+        out,
+        format!("{root}/link {root}\n{root}\n{root}/real\n{root}\n")
+    );
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_pwd_logical_and_physical() {
+    let dir = TempDir::new().expect("tempdir");
+    let root = std::fs::canonicalize(dir.path()).unwrap();
+    std::fs::create_dir(root.join("real")).unwrap();
+    std::os::unix::fs::symlink(root.join("real"), root.join("link")).unwrap();
+    let root = root.display();
+    let script = format!("cd {root}/link\npwd\npwd -L\npwd -P\npwd -x\nexit\n");
+    let (out, err, code) = run_script(&script);
+    assert_eq!(out, format!("{root}/link\n{root}/link\n{root}/real\n"));
+    assert!(err.contains("pwd: invalid option '-x'"), "stderr: {err}");
+    assert_eq!(code, 2);
+}
+
+#[test]
+fn scripted_set_physical_resolves_symlinks() {
+    let dir = TempDir::new().expect("tempdir");
+    let root = std::fs::canonicalize(dir.path()).unwrap();
+    std::fs::create_dir(root.join("real")).unwrap();
+    std::os::unix::fs::symlink(root.join("real"), root.join("link")).unwrap();
+    let root = root.display();
+    let script = format!(
+        "cd {root}\nset -o physical\ncd link\necho $PWD\npwd\npwd -L\ncd -L {root}/link\n\
+         echo $PWD\nset +o physical\ncd {root}/link\necho $PWD\nexit 0\n"
+    );
+    let (out, err, code) = run_script(&script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(
+        out,
+        format!("{root}/real\n{root}/real\n{root}/real\n{root}/link\n{root}/link\n")
+    );
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_substituted_pwd_follows_set_physical() {
+    let dir = TempDir::new().expect("tempdir");
+    let root = std::fs::canonicalize(dir.path()).unwrap();
+    std::fs::create_dir(root.join("real")).unwrap();
+    std::os::unix::fs::symlink(root.join("real"), root.join("link")).unwrap();
+    let root = root.display();
+    let script = format!(
+        "cd {root}/link\necho $(pwd)\nset -o physical\necho $(pwd)\necho $(pwd -L)\nexit 0\n"
+    );
+    let (out, err, code) = run_script(&script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, format!("{root}/link\n{root}/real\n{root}/link\n"));
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_xtrace_goes_to_configured_sink() {
+    let dir = TempDir::new().expect("tempdir");
+    let sink = dir.path().join("trace.log");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_better_shell"))
+        .arg("-x")
+        .env("MINISHELL_XTRACE_FD", &sink)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("spawn shell");
+    child
+        .stdin
+        .as_mut()
+        .expect("stdin")
+        .write_all(b"echo traced\nexit 0\n")
+        .expect("write");
+    let output = child.wait_with_output().expect("wait");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stdout, "traced\n");
+    assert!(!stderr.contains("trace:"), "stderr: {stderr}");
+    let log = std::fs::read_to_string(&sink).expect("trace log");
+    assert!(
+        log.contains("trace: argv[0]: [\"echo\", \"traced\"]"),
+        "log: {log}"
+    );
+}
+
+#[test]
+fn scripted_unquoted_heredoc_expands_and_honors_escapes() {
+    let home = std::env::var("HOME").expect("HOME");
+    let script = "cat <<EOF\nhome=$HOME literal=\\$HOME slash=\\\\ other=\\q\nEOF\ncat <<'EOF'\nraw=$HOME\nEOF\nexit 0\n";
+    let (out, err, code) = run_script(script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(
+        out,
+        format!("home={home} literal=$HOME slash=\\ other=\\q\nraw=$HOME\n")
+    );
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_quoted_heredoc_delimiters_skip_substitution() {
+    let home = std::env::var("HOME").expect("HOME");
+    let script = "cat <<\"EOF\"\n$(echo sub) $HOME\nEOF\ncat <<\\EOF\n`echo tick`\nEOF\ncat <<E'O'F\n${HOME}\nEOF\ncat <<EOF\n$(echo sub) `echo tick` ${HOME}\nEOF\nexit 0\n";
+    let (out, err, code) = run_script(script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(
+        out,
+        format!("$(echo sub) $HOME\n`echo tick`\n${{HOME}}\nsub tick {home}\n")
+    );
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_arithmetic_command_sets_status() {
+    let script = "(( 1 )) && echo one\n(( 0 )) || echo zero\n(( n = 5 ))\nif (( n > 3 )); then echo big; fi\n((n < 3))\nexit\n";
+    let (out, err, code) = run_script(script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, "one\nzero\nbig\n");
+    assert_eq!(code, 1);
+}
+
+#[test]
+fn scripted_let_and_arithmetic_command_update_variables() {
+    let script = "let i=1\nlet \"i = i + 1\"\necho $i\n(( i++ ))\necho $i\nlet \"j = i++\" \"k = ++i\"\necho $i $j $k\nlet \"i *= 2\"\necho $i\nlet 0\nexit\n";
+    let (out, err, code) = run_script(script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, "2\n3\n5 3 5\n10\n");
+    assert_eq!(code, 1);
+}
+
+#[test]
+fn scripted_arithmetic_expansion() {
+    let script = "let n=4\necho $((2 + 3 * 4)) \"$((n * (n - 1)))\" $((n++)) $n\n\
+                  echo $((n / 0))\nexit\n";
+    let (out, err, code) = run_script(script);
+    assert_eq!(out, "14 12 4 5\n");
+    assert!(
+        err.contains("parse error: arithmetic expansion: division by zero"),
+        "stderr: {err}"
+    );
+    assert_eq!(code, 2);
+}
+
+#[test]
+fn scripted_underscore_holds_last_argument() {
+    let dir = TempDir::new().expect("tempdir");
+    let target = dir.path().join("made");
+    let script = format!(
+        "mkdir -p {}\ncd $_\npwd\necho one two > /dev/null\necho $_\n\
+         printf '%s\\n' x | tr a-z A-Z\necho $_\ntrue\necho ${{_}}\nexit\n",
+        target.display()
+    );
+    let (out, err, code) = run_script(&script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, format!("{}\ntwo\nX\nA-Z\ntrue\n", target.display()));
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_underscore_follows_commands_on_the_same_line() {
+    let dir = TempDir::new().expect("tempdir");
+    let target = dir.path().join("made");
+    let script = format!(
+        "mkdir -p {} && cd $_ && pwd\necho a b; echo $_\nexit\n",
+        target.display()
+    );
+    let (out, err, code) = run_script(&script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, format!("{}\na b\nb\n", target.display()));
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_cd_reports_specific_errors() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let file = dir.path().join("plain");
+    std::fs::write(&file, "").expect("write");
+    let missing = dir.path().join("missing");
+    let script = format!("cd {}\ncd {}\nexit\n", file.display(), missing.display());
+    let (_, err, code) = run_script(&script);
+    assert!(
+        err.contains(&format!("cd: {}: not a directory", file.display())),
+        "stderr: {err}"
+    );
+    assert!(
+        err.contains(&format!(
+            "cd: {}: no such file or directory",
+            missing.display()
+        )),
+        "stderr: {err}"
+    );
+    assert_eq!(code, 1);
+}
+
+#[test]
+fn scripted_cd_without_home_or_known_user_fails() {
+    let (_, err, code) = run_script_with("cd\nexit\n", |command| {
+        command.env_remove("HOME");
+    });
+    assert!(err.contains("cd: HOME not set"), "stderr: {err}");
+    assert_eq!(code, 1);
+
+    let (_, err, code) = run_script("cd ~no_such_user_here\nexit\n");
+    assert!(
+        err.contains("cd: ~no_such_user_here: no such user"),
+        "stderr: {err}"
+    );
+    assert_eq!(code, 1);
+}
+
+#[test]
+fn scripted_cd_takes_an_existing_tilde_path_literally() {
+    let dir = TempDir::new().expect("tempdir");
+    let root = std::fs::canonicalize(dir.path()).unwrap();
+    std::fs::create_dir(root.join("~lit")).unwrap();
+    let script = format!("cd {}\ncd '~lit'\npwd\nexit\n", root.display());
+    let (out, err, code) = run_script(&script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, format!("{}/~lit\n", root.display()));
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_posix_mode_skips_abbreviations() {
+    let home = TempDir::new().expect("tempdir");
+    let script = "abbr greet echo expanded\ngreet\nset -o posix\ngreet\nexit\n";
+    let (out, err, _) = run_script_with(script, |command| {
+        command.env("HOME", home.path());
+    });
+    assert_eq!(out.matches("expanded").count(), 1, "stdout: {out}");
+    assert!(err.contains("greet"), "stderr: {err}");
+}
+
+#[test]
+fn scripted_rejects_invalid_variable_names() {
+    let (out, err, code) = run_script("for 1 in a b; do echo $1; done\nexit\n");
+    assert!(
+        err.contains("for: invalid variable name '1'"),
+        "stderr: {err}"
+    );
+    assert!(!out.contains('a'), "stdout: {out}");
+    assert_eq!(code, 2);
+
+    let (_, err, code) = run_script("unset '?'\nexit\n");
+    assert!(
+        err.contains("unset: invalid variable name '?'"),
+        "stderr: {err}"
+    );
+    assert_eq!(code, 2);
+}
+
+#[test]
+fn scripted_for_binds_several_variables_per_pass() {
+    let script = "for k v in a 1 b 2; do echo pass $k $v; done\nfor x y in p q r; do echo [$x,$y]; done\nexit 0\n";
+    let (out, err, code) = run_script(script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out.matches("pass").count(), 2, "stdout: {out}");
+    assert!(out.contains("pass a 1\npass b 2"), "stdout: {out}");
+    assert!(out.contains("[r,]"), "stdout: {out}");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_select_prints_menu_and_sets_reply() {
+    let script = "select f in a b c; do echo got=$f reply=$REPLY; done\n2\n7\n";
+    let (out, err, code) = run_script(script);
+    assert!(err.contains("1) a\n2) b\n3) c\n"), "stderr: {err}");
+    assert!(out.contains("got=b reply=2\ngot= reply=7"), "stdout: {out}");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_indirect_expansion_and_prefix_listing() {
+    let script = "echo ${!CS_REF}\necho ${!CS_LIST_@}\nexit\n";
+    let (out, err, code) = run_script_with(script, |command| {
+        command
+            .env("CS_REF", "CS_LIST_B")
+            .env("CS_LIST_A", "first")
+            .env("CS_LIST_B", "second");
+    });
+    assert_eq!(code, 0, "stderr: {err}");
+    assert!(out.contains("second\n"), "stdout: {out}");
+    assert!(out.contains("CS_LIST_A CS_LIST_B\n"), "stdout: {out}");
+}
+
+#[test]
+fn scripted_substitution_globs_only_when_unquoted() {
+    let dir = TempDir::new().expect("tempdir");
+    std::fs::write(dir.path().join("a.txt"), "").expect("write a.txt");
+    std::fs::write(dir.path().join("b.txt"), "").expect("write b.txt");
+    let script =
+        "echo \"$(echo '*')\"\necho $(echo '*')\nprintf '[%s]' \"$(echo '  x  ')\"\nexit\n";
+    let (out, err, code) = run_script_with(script, |command| {
+        command.current_dir(dir.path());
+    });
+    assert_eq!(code, 0, "stderr: {err}");
+    assert_eq!(out, "*\na.txt b.txt\n[  x  ]", "stderr: {err}");
+}
+
+#[test]
+fn scripted_source_keeps_the_last_commands_status() {
+    let dir = TempDir::new().expect("tempdir");
+    let ends_false = dir.path().join("ends_false.sh");
+    let ends_true = dir.path().join("ends_true.sh");
+    std::fs::write(&ends_false, "echo sourced\nfalse\n").expect("write ends_false");
+    std::fs::write(&ends_true, "false\ntrue\n").expect("write ends_true");
+
+    let script = format!(
+        "source {} || echo failed\n. {} && echo succeeded\nsource {}\nexit\n",
+        ends_false.display(),
+        ends_true.display(),
+        ends_false.display()
+    );
+    let (out, err, code) = run_script(&script);
+    assert_eq!(
+        out, "sourced\nfailed\nsucceeded\nsourced\n",
+        "stderr: {err}"
+    );
+    assert_eq!(code, 1);
+}
+
+#[test]
+fn scripted_comment_lines_inside_compounds() {
+    let script = "if true\n# fi would end it early\nthen\n  echo \"x # kept\"\n  # done\nfi\necho after\nexit\n";
+    let (out, err, code) = run_script(script);
+    assert_eq!(out, "x # kept\nafter\n", "stderr: {err}");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_heredoc_inside_for_loop() {
+    let script = "for i in 1 2\ndo\ncat <<EOF\nline $i\ndone\nEOF\ncat <<'RAW'\nraw $i\nRAW\ndone\necho after\nexit\n";
+    let (out, err, code) = run_script(script);
+    assert_eq!(
+        out, "line 1\ndone\nraw $i\nline 2\ndone\nraw $i\nafter\n",
+        "stderr: {err}"
+    );
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_dash_heredoc_strips_leading_tabs() {
+    let script = "cat <<-EOF\n\tone\n\t\ttwo\n  three\n\tEOF\ncat <<EOF\n\tkept\nEOF\nif true\nthen\n\tcat <<-END\n\t\tinner\n\tEND\nfi\nexit\n";
+    let (out, err, code) = run_script(script);
+    assert_eq!(out, "one\ntwo\n  three\n\tkept\ninner\n", "stderr: {err}");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_unbounded_recursion_stops_at_the_limit() {
+    let script = "function f { f; }\nf\necho after\nf\nexit\n";
+    let (out, err, code) = run_script(script);
+    assert!(
+        err.contains("f: maximum function recursion depth exceeded (1000)"),
+        "stderr: {err}"
+    );
+    assert_eq!(out, "after\n");
+    assert_eq!(code, 1);
+
+    let (_, err, _) = run_script_with(script, |command| {
+        command.env("FUNCNEST", "8");
+    });
+    assert!(err.contains("exceeded (8)"), "stderr: {err}");
+}
+
+#[test]
+fn scripted_functions_shadow_builtins_and_programs() {
+    let dir = TempDir::new().expect("tempdir");
+    let script = format!(
+        "function ls {{ echo wrapped; }}\nls\ncommand ls -d {root}\nbuiltin ls\n\
+         function cd {{ builtin cd {root}; echo moved; }}\ncd\nbuiltin pwd\nexit\n",
+        root = dir.path().display()
+    );
+    let (out, err, code) = run_script(&script);
+    let root = dir.path().display();
+    assert_eq!(
+        out,
+        format!("wrapped\n{root}\nmoved\n{root}\n"),
+        "stderr: {err}"
+    );
+    assert!(
+        err.contains("builtin: ls: not a shell builtin"),
+        "stderr: {err}"
+    );
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_command_describes_names_and_works_in_substitutions() {
+    let script = "alias ll='ls -l'\nfunction f { echo fn; }\n\
+                  command -v ll f cd nosuch; echo \"status=$?\"\n\
+                  command -V f cd\ncommand -V nosuch\ncommand -p sh -c 'echo default path'\n\
+                  command -x f; echo \"status=$?\"\n\
+                  echo \"$(command echo sub) $(command -v cd)\"\nexit\n";
+    let (out, err, code) = run_script(script);
+    assert_eq!(
+        out,
+        "alias ll='ls -l'\nf\ncd\nstatus=1\nf is a shell function\ncd is a shell builtin\n\
+         default path\nstatus=2\nsub cd\n",
+        "stderr: {err}"
+    );
+    assert!(err.contains("command: nosuch: not found"), "stderr: {err}");
+    assert!(err.contains("command: -x: invalid option"), "stderr: {err}");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_pipeline_can_end_in_a_function() {
+    let dir = TempDir::new().expect("tempdir");
+    let script = format!(
+        "function shout {{ tr a-z A-Z; cd {}; }}\necho hi | shout\npwd\n\
+         function fail {{ false; }}\necho x | fail || echo failed\nexit\n",
+        dir.path().display()
+    );
+    let (out, err, code) = run_script_with(&script, |command| {
+        command.current_dir(env!("CARGO_MANIFEST_DIR"));
+    });
+    assert_eq!(
+        out,
+        format!("HI\n{}\nfailed\n", env!("CARGO_MANIFEST_DIR")),
+        "stderr: {err}"
+    );
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_function_pipeline_streams_earlier_stages() {
+    let script = "function first_two { head -n 2; }
+yes | first_two
+                  seq 3 | cat | first_two
+exit
+";
+    let (out, err, code) = run_script(script);
+    assert!(err.is_empty(), "stderr: {err}");
+    assert_eq!(out, "y\ny\n1\n2\n");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_export_reaches_children_and_lists_sorted() {
+    let script = "export GREETING=hello\nsh -c 'echo $GREETING'\n\
+                  export | grep -E '^(GREETING|ZZ_LAST)='\nexport ZZ_LAST=1 1bad\n\
+                  echo $(export -p) | grep -c ZZ_LAST=1\nexit\n";
+    let (out, err, code) = run_script(script);
+    assert_eq!(out, "hello\nGREETING=hello\n1\n", "stderr: {err}");
+    assert!(err.contains("export: invalid name '1bad'"), "stderr: {err}");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_substitution_reads_a_redirected_file_without_cat() {
+    let dir = TempDir::new().expect("tempdir");
+    let file = dir.path().join("notes.txt");
+    std::fs::write(&file, "hello world\n\n").expect("write");
+    let script = format!(
+        "echo \"<$(< {path})>\"\nexport CONTENT=\"$(<{path})\"\necho $CONTENT.\n\
+         echo $(< {path}.missing)\nexit 0\n",
+        path = file.display()
+    );
+    let (out, err, code) = run_script_with(&script, |command| {
+        command.env("PATH", "");
+    });
+    assert_eq!(out, "<hello world>\nhello world.\n", "stderr: {err}");
+    assert!(
+        err.contains("notes.txt.missing: No such file"),
+        "stderr: {err}"
+    );
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_unset_removes_exported_variables() {
+    let script = "export GONE=1\nunset GONE NEVER_SET && echo unset ok\n\
+                  sh -c 'echo ${GONE-removed}'\nexit\n";
+    let (out, err, code) = run_script(script);
+    assert_eq!(out, "unset ok\nremoved\n", "stderr: {err}");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_redirect_targets_are_expanded() {
+    let home = TempDir::new().expect("tempdir");
+    std::fs::write(home.path().join("a.log"), "").expect("write");
+    std::fs::write(home.path().join("b.log"), "").expect("write");
+    let script = "echo by-var > $HOME/var.txt\necho by-tilde >> ~/tilde.txt\n\
+                  cat ~/var.txt $HOME/tilde.txt\necho lost > *.log\nexit 0\n";
+    let (out, err, code) = run_script_with(script, |command| {
+        command.env("HOME", home.path()).current_dir(home.path());
+    });
+    assert_eq!(out, "by-var\nby-tilde\n", "stderr: {err}");
+    assert!(err.contains("*.log: ambiguous redirect"), "stderr: {err}");
+    let logs = std::fs::read_to_string(home.path().join("a.log")).expect("read");
+    assert_eq!(logs, "");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_ambiguous_redirects_fail_with_status_one() {
+    let dir = TempDir::new().expect("tempdir");
+    std::fs::write(dir.path().join("a.log"), "").expect("write");
+    std::fs::write(dir.path().join("b.log"), "").expect("write");
+    let script = "echo lost > *.log\necho next\n\
+                  shopt -s nullglob\necho lost > *.none\nexit\n";
+    let (out, err, code) = run_script_with(script, |command| {
+        command.current_dir(dir.path());
+    });
+    assert_eq!(out, "next\n", "stderr: {err}");
+    assert!(err.contains("*.log: ambiguous redirect"), "stderr: {err}");
+    assert!(err.contains("*.none: ambiguous redirect"), "stderr: {err}");
+    assert!(!err.contains("parse error"), "stderr: {err}");
+    assert_eq!(code, 1);
+}
+
+#[test]
+fn scripted_ambiguous_redirects_in_compounds_and_functions_fail_with_status_one() {
+    let dir = TempDir::new().expect("tempdir");
+    std::fs::write(dir.path().join("a.log"), "").expect("write");
+    std::fs::write(dir.path().join("b.log"), "").expect("write");
+    let script = "if true; then echo y > *.log; fi\necho if=$?\n\
+                  function f {\necho y > *.log\n}\nf\necho f=$?\nexit\n";
+    let (out, err, _) = run_script_with(script, |command| {
+        command.current_dir(dir.path());
+    });
+    assert_eq!(out, "if=1\nf=1\n", "stderr: {err}");
+    assert_eq!(err.matches("*.log: ambiguous redirect").count(), 2);
+    assert!(!err.contains("parse error"), "stderr: {err}");
+}
+
+#[test]
+fn scripted_break_and_continue_leave_loops_early() {
+    let script = "for i in 1 2 3 4 5; do test $i = 2 && continue; \
+                  test $i = 4 && break; echo i$i; done\n\
+                  while true; do echo once; break; echo never; done\n\
+                  function stop { break; }\nfor z in 1 2; do stop; echo z$z; done\n\
+                  break\nexit\n";
+    let (out, err, code) = run_script(script);
+    assert_eq!(out, "i1\ni3\nonce\nz1\nz2\n", "stderr: {err}");
+    assert_eq!(err.matches("break: only valid inside a loop").count(), 3);
+    assert_eq!(code, 2);
+}
+
+#[test]
+fn scripted_noglob_prefix_passes_patterns_literally() {
+    let dir = TempDir::new().expect("tempdir");
+    std::fs::write(dir.path().join("a.txt"), "").expect("write");
+    let script = "noglob echo *.nonexistent\nnoglob echo *.txt; echo *.txt\n\
+                  shopt -s failglob\nnoglob echo *.nonexistent\nexit\n";
+    let (out, err, code) = run_script_with(script, |command| {
+        command.current_dir(dir.path());
+    });
+    let expected = "*.nonexistent\n*.txt\na.txt\n*.nonexistent\n";
+    assert_eq!(out, expected, "stderr: {err}");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_glob_prefix_expands_patterns() {
+    let dir = TempDir::new().expect("tempdir");
+    std::fs::write(dir.path().join("a.txt"), "").expect("write");
+    let script = "glob echo *.txt\nnoglob glob echo *.txt\nglob noglob echo *.txt\nexit\n";
+    let (out, err, code) = run_script_with(script, |command| {
+        command.current_dir(dir.path());
+    });
+    assert_eq!(out, "a.txt\na.txt\n*.txt\n", "stderr: {err}");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn scripted_if_runs_the_first_matching_elif() {
+    let script = "if false; then echo one; elif true; then echo two; else echo three; fi\n\
+                  if false; then echo a; elif false; then echo b; else echo c; fi\n\
+                  if false\nthen\necho x\nelif true\nthen\necho y\nfi\nexit 0\n";
+    let (out, err, code) = run_script(script);
+    assert_eq!(out, "two\nc\ny\n", "stderr: {err}");
+    assert_eq!(code, 0);
+}